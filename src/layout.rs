@@ -0,0 +1,182 @@
+use relm4::factory::positions::GridPosition;
+use serde::{Deserialize, Serialize};
+use strum_macros::EnumIter;
+
+// 自动网格按三列排列
+const AUTO_GRID_COLUMNS: usize = 3;
+// 画中画模式下主画面占据的网格边长，缩略图各占一格
+const PICTURE_IN_PICTURE_SIZE: usize = 4;
+
+#[derive(EnumIter, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum LayoutMode {
+    AutoGrid,
+    Focus,
+    PictureInPicture,
+    CustomGrid,
+}
+
+impl ToString for LayoutMode {
+    fn to_string(&self) -> String {
+        match self {
+            LayoutMode::AutoGrid => "自动网格",
+            LayoutMode::Focus => "主画面与缩略图",
+            LayoutMode::PictureInPicture => "画中画",
+            LayoutMode::CustomGrid => "自定义网格",
+        }
+        .to_string()
+    }
+}
+
+impl Default for LayoutMode {
+    fn default() -> Self {
+        Self::AutoGrid
+    }
+}
+
+impl LayoutMode {
+    // 是否区分主画面与缩略图
+    pub fn has_main_view(&self) -> bool {
+        matches!(self, LayoutMode::Focus | LayoutMode::PictureInPicture)
+    }
+
+    // 计算每个机位在网格中的位置，主画面之外的机位按原有顺序排列
+    pub fn positions(&self, count: usize, main: usize, custom_columns: usize) -> Vec<GridPosition> {
+        let main = main.min(count.saturating_sub(1));
+        let others = (0..count).filter(|index| *index != main);
+        let mut positions: Vec<_> = (0..count).map(|_| cell(0, 0, 1, 1)).collect();
+        match self {
+            LayoutMode::AutoGrid => grid(&mut positions, AUTO_GRID_COLUMNS),
+            LayoutMode::CustomGrid => grid(&mut positions, custom_columns.max(1)),
+            LayoutMode::Focus => {
+                let thumbnails = count.saturating_sub(1);
+                if thumbnails > 0 {
+                    // 主画面占左侧三列，缩略图在右侧纵向排列
+                    positions[main] = cell(0, 0, 3, thumbnails);
+                    for (row, index) in others.enumerate() {
+                        positions[index] = cell(3, row, 1, 1);
+                    }
+                }
+            }
+            LayoutMode::PictureInPicture => {
+                let size = PICTURE_IN_PICTURE_SIZE;
+                positions[main] = cell(0, 0, size, size);
+                // 缩略图从右下角开始向左、向上叠放在主画面上
+                for (order, index) in others.enumerate() {
+                    let column = size - 1 - order % size;
+                    let row = size.saturating_sub(1 + order / size);
+                    positions[index] = cell(column, row, 1, 1);
+                }
+            }
+        }
+        positions
+    }
+}
+
+fn grid(positions: &mut [GridPosition], columns: usize) {
+    for (index, position) in positions.iter_mut().enumerate() {
+        *position = cell(index % columns, index / columns, 1, 1);
+    }
+}
+
+fn cell(column: usize, row: usize, width: usize, height: usize) -> GridPosition {
+    GridPosition {
+        column: column as i32,
+        row: row as i32,
+        width: width as i32,
+        height: height as i32,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use strum::IntoEnumIterator;
+
+    use super::*;
+
+    // (列, 行, 宽, 高)
+    fn cells(positions: &[GridPosition]) -> Vec<(i32, i32, i32, i32)> {
+        positions
+            .iter()
+            .map(|position| {
+                (
+                    position.column,
+                    position.row,
+                    position.width,
+                    position.height,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn one_position_per_slave() {
+        for mode in LayoutMode::iter() {
+            for count in 1..=4 {
+                assert_eq!(mode.positions(count, 0, 2).len(), count, "{:?}", mode);
+            }
+        }
+    }
+
+    #[test]
+    fn auto_grid_wraps_after_three_columns() {
+        let positions = LayoutMode::AutoGrid.positions(4, 0, 2);
+        assert_eq!(
+            cells(&positions),
+            vec![(0, 0, 1, 1), (1, 0, 1, 1), (2, 0, 1, 1), (0, 1, 1, 1)]
+        );
+    }
+
+    #[test]
+    fn focus_places_thumbnails_beside_main() {
+        assert_eq!(
+            cells(&LayoutMode::Focus.positions(1, 0, 2)),
+            vec![(0, 0, 1, 1)]
+        );
+        let positions = LayoutMode::Focus.positions(4, 2, 2);
+        assert_eq!(
+            cells(&positions),
+            vec![(3, 0, 1, 1), (3, 1, 1, 1), (0, 0, 3, 3), (3, 2, 1, 1)]
+        );
+    }
+
+    #[test]
+    fn picture_in_picture_stacks_thumbnails_from_bottom_right() {
+        assert_eq!(
+            cells(&LayoutMode::PictureInPicture.positions(1, 0, 2)),
+            vec![(0, 0, 4, 4)]
+        );
+        let positions = LayoutMode::PictureInPicture.positions(4, 1, 2);
+        assert_eq!(
+            cells(&positions),
+            vec![(3, 3, 1, 1), (0, 0, 4, 4), (2, 3, 1, 1), (1, 3, 1, 1)]
+        );
+        // 一行放满后继续向上叠放
+        let positions = LayoutMode::PictureInPicture.positions(6, 0, 2);
+        assert_eq!(positions[5].column, 3);
+        assert_eq!(positions[5].row, 2);
+    }
+
+    #[test]
+    fn main_index_out_of_range_falls_back_to_last() {
+        let positions = LayoutMode::Focus.positions(3, 10, 2);
+        assert_eq!(cells(&positions)[2], (0, 0, 3, 2));
+    }
+
+    #[test]
+    fn custom_grid_with_zero_columns_uses_one_column() {
+        let positions = LayoutMode::CustomGrid.positions(3, 0, 0);
+        assert_eq!(
+            cells(&positions),
+            vec![(0, 0, 1, 1), (0, 1, 1, 1), (0, 2, 1, 1)]
+        );
+    }
+
+    #[test]
+    fn custom_grid_with_more_columns_than_slaves_uses_one_row() {
+        let positions = LayoutMode::CustomGrid.positions(3, 0, 8);
+        assert_eq!(
+            cells(&positions),
+            vec![(0, 0, 1, 1), (1, 0, 1, 1), (2, 0, 1, 1)]
+        );
+    }
+}
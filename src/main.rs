@@ -1,44 +1,69 @@
 mod about;
+mod layout;
 mod preferences;
+mod session;
 mod slave;
 mod ui;
 
-use adw::{prelude::*, CenteringPolicy, ColorScheme, HeaderBar, StatusPage, StyleManager};
+use adw::{
+    prelude::*, CenteringPolicy, ColorScheme, HeaderBar, StatusPage, StyleManager, Toast,
+    ToastOverlay,
+};
+use gst::prelude::ElementExt;
 use relm4::{
-    actions::{RelmAction, RelmActionGroup},
-    factory::FactoryVecDeque,
+    actions::{AccelsPlus, RelmAction, RelmActionGroup},
+    factory::{positions::GridPosition, FactoryVecDeque},
     gtk::{
-        Align, Box as GtkBox, Button, Grid, Image, Inhibit, Label, MenuButton, Orientation,
-        Separator, Stack, ToggleButton,
+        gio, glib, Align, Box as GtkBox, Button, Grid, GridLayout, GridLayoutChild, Image, Inhibit,
+        Label, MenuButton, Orientation, Separator, Stack, ToggleButton, Widget,
     },
-    new_action_group, new_stateless_action,
+    new_action_group, new_stateful_action, new_stateless_action,
     prelude::*,
     Component, ComponentParts, RelmApp,
 };
 use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
 use strum_macros::EnumIter;
 
 use about::*;
+use layout::LayoutMode;
 use preferences::*;
-use slave::SlaveModel;
+use session::{SessionModel, SlaveSession};
+use slave::{
+    video_ext::{
+        create_composite_record_pipeline, stop_composite_record_pipeline, RecordMetadata,
+        RecordOptions,
+    },
+    SlaveInit, SlaveModel,
+};
 
 use crate::slave::SlaveInput;
 
 #[tracker::track]
 struct AppModel {
     sync_recording: Option<bool>,
+    composite_recording: bool,
     is_fullscreen: bool,
+    toast_message: Option<String>,
     #[no_eq]
     slaves: FactoryVecDeque<SlaveModel>,
     #[do_not_track]
     about_model: Controller<AboutModel>,
     #[do_not_track]
     prefermances_model: Controller<PreferencesModel>,
+    #[do_not_track]
+    composite_recorder: Option<(gst::Pipeline, Vec<gst_app::AppSrc>)>,
+    #[do_not_track]
+    main_slave: usize,
+    #[do_not_track]
+    layout_action: Option<gio::SimpleAction>,
 }
 
 new_action_group!(AppActionGroup, "main");
 new_stateless_action!(PreferencesAction, AppActionGroup, "preferences");
 new_stateless_action!(AboutDialogAction, AppActionGroup, "about");
+new_stateless_action!(CaptureAllAction, AppActionGroup, "capture-all");
+new_stateful_action!(LayoutAction, AppActionGroup, "layout", u8, u8);
 
 #[relm4::component]
 impl SimpleComponent for AppModel {
@@ -81,12 +106,40 @@ impl SimpleComponent for AppModel {
                             sender.input(AppMsg::ToggleSyncRecording);
                         }
                     },
+                    pack_start = &Button {
+                        set_icon_name: "view-grid-symbolic",
+                        #[track = "model.changed(AppModel::composite_recording())"]
+                        set_tooltip_text: Some(if model.composite_recording { "停止拼接录制" } else { "拼接录制" }),
+                        #[track = "model.changed(AppModel::composite_recording())"]
+                        set_css_classes: if model.composite_recording { &["destructive-action"] as &[&str] } else { &[] as &[&str] },
+                        #[track = "model.changed(AppModel::slaves())"]
+                        set_visible: model.get_slaves().len() > 1,
+                        connect_clicked[sender] => move |_| {
+                            sender.input(AppMsg::ToggleCompositeRecording);
+                        }
+                    },
+                    pack_start = &Button {
+                        set_icon_name: "camera-photo-symbolic",
+                        set_tooltip_text: Some("全部截图 (Ctrl+Shift+S)"),
+                        #[track = "model.changed(AppModel::slaves())"]
+                        set_visible: model.get_slaves().len() > 1,
+                        connect_clicked[sender] => move |_| {
+                            sender.input(AppMsg::CaptureAll);
+                        }
+                    },
                     pack_end = &MenuButton {
                         set_menu_model: Some(&main_menu),
                         set_icon_name: "open-menu-symbolic",
                         set_focus_on_click: false,
                         set_valign: Align::Center,
                     },
+                    pack_end = &MenuButton {
+                        set_menu_model: Some(&layout_menu),
+                        set_icon_name: "view-dual-symbolic",
+                        set_tooltip_text: Some("画面布局"),
+                        set_focus_on_click: false,
+                        set_valign: Align::Center,
+                    },
                     pack_end = &ToggleButton {
                         set_icon_name: "view-fullscreen-symbolic",
                         set_tooltip_text: Some("切换全屏模式"),
@@ -100,8 +153,8 @@ impl SimpleComponent for AppModel {
                     pack_end = &Button {
                         set_icon_name: "list-remove-symbolic",
                         set_tooltip_text: Some("移除机位"),
-                        #[track = "model.changed(AppModel::sync_recording()) || model.changed(AppModel::slaves())"]
-                        set_sensitive: model.get_slaves().len() > 0 && *model.get_sync_recording() ==  Some(false),
+                        #[track = "model.changed(AppModel::sync_recording()) || model.changed(AppModel::composite_recording()) || model.changed(AppModel::slaves())"]
+                        set_sensitive: model.get_slaves().len() > 0 && *model.get_sync_recording() ==  Some(false) && !model.composite_recording,
                         connect_clicked[sender] => move |_| {
                             sender.input(AppMsg::RemoveLastSlave);
                         },
@@ -109,27 +162,32 @@ impl SimpleComponent for AppModel {
                     pack_end = &Button {
                         set_icon_name: "list-add-symbolic",
                         set_tooltip_text: Some("新建机位"),
-                        #[track = "model.changed(AppModel::sync_recording())"]
-                        set_sensitive: model.sync_recording == Some(false),
+                        #[track = "model.changed(AppModel::sync_recording()) || model.changed(AppModel::composite_recording())"]
+                        set_sensitive: model.sync_recording == Some(false) && !model.composite_recording,
                         connect_clicked[sender] => move |_| {
                             sender.input(AppMsg::NewSlave);
                         },
                     },
                 },
-                append = &Stack {
-                    set_hexpand: true,
-                    set_vexpand: true,
-                    add_child = &StatusPage {
-                        set_icon_name: Some("window-new-symbolic"),
-                        set_title: "无机位",
-                        set_description: Some("请点击标题栏右侧按钮添加机位"),
-                        #[track = "model.changed(AppModel::slaves())"]
-                        set_visible: model.get_slaves().len() == 0,
-                    },
-                    add_child = &GtkBox {
-                        #[track = "model.changed(AppModel::slaves())"]
-                        set_visible: model.get_slaves().len() != 0,
-                        append: slave
+                append = &ToastOverlay {
+                    #[track = "model.changed(AppModel::toast_message())"]
+                    add_toast?: model.toast_message.as_ref().map(|message| Toast::new(message)),
+                    #[wrap(Some)]
+                    set_child = &Stack {
+                        set_hexpand: true,
+                        set_vexpand: true,
+                        add_child = &StatusPage {
+                            set_icon_name: Some("window-new-symbolic"),
+                            set_title: "无机位",
+                            set_description: Some("请点击标题栏右侧按钮添加机位"),
+                            #[track = "model.changed(AppModel::slaves())"]
+                            set_visible: model.get_slaves().len() == 0,
+                        },
+                        add_child = &GtkBox {
+                            #[track = "model.changed(AppModel::slaves())"]
+                            set_visible: model.get_slaves().len() != 0,
+                            append: slave
+                        }
                     }
                 }
             }
@@ -138,9 +196,10 @@ impl SimpleComponent for AppModel {
 
     menu! {
         main_menu: {
+            "全部截图" => CaptureAllAction,
             "首选项" => PreferencesAction,
             "关于"   => AboutDialogAction,
-        }
+        },
     }
 
     type Init = ();
@@ -166,16 +225,29 @@ impl SimpleComponent for AppModel {
                 }
             });
 
-        let model = AppModel {
+        let mut model = AppModel {
             is_fullscreen: false,
+            toast_message: None,
             sync_recording: Some(false),
+            composite_recording: false,
             slaves: FactoryVecDeque::new(Grid::default(), sender.input_sender()),
             about_model,
             prefermances_model,
+            composite_recorder: None,
+            main_slave: 0,
+            layout_action: None,
             tracker: 0,
         };
 
         let slave = model.get_slaves().widget();
+        // 布局菜单项与 LayoutMode 一一对应，动作的目标值为布局的序号
+        let layout_menu = gio::Menu::new();
+        for (index, mode) in LayoutMode::iter().enumerate() {
+            layout_menu.append_item(&RelmAction::<LayoutAction>::to_menu_item_with_target_value(
+                &mode.to_string(),
+                &(index as u8),
+            ));
+        }
         let widgets = view_output!();
 
         let mut app_group = RelmActionGroup::<AppActionGroup>::new();
@@ -191,15 +263,67 @@ impl SimpleComponent for AppModel {
                 RelmAction::new_stateless(move |_| sender.input(AppMsg::OpenAboutDialog));
             app_group.add_action(action_about);
         }
+        {
+            let sender = sender.clone();
+            let action_capture_all: RelmAction<CaptureAllAction> =
+                RelmAction::new_stateless(move |_| sender.input(AppMsg::CaptureAll));
+            app_group.add_action(action_capture_all);
+        }
+        {
+            let sender = sender.clone();
+            let layout_mode = *model.prefermances_model.model().get_layout_mode();
+            let action_layout: RelmAction<LayoutAction> =
+                RelmAction::new_stateful_with_target_value(
+                    &(LayoutMode::iter().position(|x| x == layout_mode).unwrap() as u8),
+                    move |_, state, value| {
+                        *state = value;
+                        sender.input(AppMsg::SetLayoutMode(
+                            LayoutMode::iter().nth(value as usize).unwrap(),
+                        ));
+                    },
+                );
+            model.layout_action = Some(action_layout.gio_action().clone());
+            app_group.add_action(action_layout);
+        }
         root.insert_action_group("main", Some(&app_group.into_action_group()));
+        relm4::main_application()
+            .set_accelerators_for_action::<CaptureAllAction>(&["<Control><Shift>s"]);
 
-        for _ in 0..*model.prefermances_model.model().get_initial_slave_num() {
-            sender.input(AppMsg::NewSlave)
+        let preferences = model.prefermances_model.model();
+        sender.input(AppMsg::SetColorScheme(
+            preferences.get_application_color_scheme().clone(),
+        ));
+        match (*preferences.get_restore_session())
+            .then(SessionModel::load)
+            .flatten()
+            .filter(|session| !session.slaves.is_empty())
+        {
+            Some(session) => {
+                model.main_slave = session.main_slave;
+                if session.layout_mode != *preferences.get_layout_mode() {
+                    sender.input(AppMsg::SetLayoutMode(session.layout_mode));
+                }
+                for slave in session.slaves {
+                    sender.input(AppMsg::RestoreSlave(slave));
+                }
+            }
+            None => {
+                for _ in 0..*preferences.get_initial_slave_num() {
+                    sender.input(AppMsg::NewSlave)
+                }
+            }
         }
         ComponentParts { model, widgets }
     }
 
-    fn update(&mut self, message: Self::Input, _sender: relm4::ComponentSender<Self>) {
+    fn shutdown(&mut self, _widgets: &mut Self::Widgets, _output: relm4::Sender<Self::Output>) {
+        // 无论是否启用恢复都保存，便于之后开启该选项
+        if let Err(err) = self.session().save() {
+            println!("错误: 无法保存会话：{}", err);
+        }
+    }
+
+    fn update(&mut self, message: Self::Input, sender: relm4::ComponentSender<Self>) {
         self.reset();
 
         use AppMsg::*;
@@ -209,21 +333,60 @@ impl SimpleComponent for AppModel {
                 for i in 0..slave.len() {
                     slave.send(i, SlaveInput::UpdataPreferences(preferences.clone()));
                 }
+                if let Some(action) = &self.layout_action {
+                    let layout_mode = *preferences.get_layout_mode();
+                    action.set_state(
+                        &(LayoutMode::iter().position(|x| x == layout_mode).unwrap() as u8)
+                            .to_variant(),
+                    );
+                }
+                self.apply_layout();
             }
             RemoveLastSlave => {
                 let index = self.get_mut_slaves().len() - 1;
                 self.get_mut_slaves().guard().remove(index);
+                self.slave_removed(index);
             }
             NewSlave => {
-                let preference = self.prefermances_model.model().clone();
-                self.get_mut_slaves().guard().push_back(preference);
+                let preferences = self.prefermances_model.model().clone();
+                self.get_mut_slaves().guard().push_back(SlaveInit {
+                    preferences,
+                    session: None,
+                });
+                self.apply_layout();
+            }
+            RestoreSlave(session) => {
+                let preferences = self.prefermances_model.model().clone();
+                self.get_mut_slaves().guard().push_back(SlaveInit {
+                    preferences,
+                    session: Some(session),
+                });
+                self.apply_layout();
             }
             DestroySlave(index) => {
                 let len = self.get_mut_slaves().len();
                 if index < len {
                     self.get_mut_slaves().guard().remove(index);
+                    self.slave_removed(index);
+                }
+            }
+            PromoteSlave(index) => {
+                self.main_slave = index;
+                let preferences = self.prefermances_model.model();
+                if preferences.get_layout_mode().has_main_view() {
+                    self.apply_layout();
+                } else {
+                    // 网格布局下双击机位时切换为主画面与缩略图布局
+                    sender.input(SetLayoutMode(LayoutMode::Focus));
                 }
             }
+            SetLayoutMode(mode) => {
+                let preferences = self.prefermances_model.sender();
+                preferences
+                    .send(PreferencesMsg::SetLayoutMode(mode))
+                    .unwrap();
+                preferences.send(PreferencesMsg::SaveToFile).unwrap();
+            }
             SetFullscreened(val) => self.set_is_fullscreen(val),
             OpenAboutDialog => self.about_model.sender().send(AboutMsg::Show).unwrap(),
             OpenPreferencesWindow => self
@@ -237,8 +400,206 @@ impl SimpleComponent for AppModel {
                 AppColorScheme::Light => ColorScheme::ForceLight,
                 AppColorScheme::Dark => ColorScheme::ForceDark,
             }),
-            ToggleSyncRecording => {}
+            CaptureAll => {
+                // 所有机位使用同一时间命名截图，便于对照
+                let preferences = self.prefermances_model.model();
+                let timestamp = glib::DateTime::now_local().unwrap();
+                let mut directory = preferences.get_image_save_path().clone();
+                if *preferences.get_screenshot_sync_use_separate_directory() {
+                    directory.push(timestamp.format_iso8601().unwrap().replace(":", "-"));
+                    if let Err(err) = std::fs::create_dir_all(&directory) {
+                        self.set_toast_message(Some(format!(
+                            "无法创建截图文件夹 {}：{}",
+                            directory.display(),
+                            err
+                        )));
+                        return;
+                    }
+                }
+                let slaves = self.get_slaves();
+                for i in 0..slaves.len() {
+                    slaves.send(
+                        i,
+                        SlaveInput::TakeSyncScreenshot(directory.clone(), timestamp.clone()),
+                    );
+                }
+            }
+            ToggleCompositeRecording => match self.composite_recorder.take() {
+                None => {
+                    // 各机位画面按界面中的网格位置合成，未在拉流的机位显示为黑色
+                    let preferences = self.prefermances_model.model();
+                    let start_time = glib::DateTime::now_local().unwrap();
+                    let container = *preferences.get_default_video_container();
+                    let mut pathbuf = preferences.get_video_save_path().clone();
+                    pathbuf.push(format!(
+                        "{}_composite.{}",
+                        start_time.format_iso8601().unwrap().replace(":", "-"),
+                        container.extension()
+                    ));
+                    let options = RecordOptions {
+                        container,
+                        segmentation: preferences.record_segmentation(),
+                        metadata: RecordMetadata {
+                            title: String::from("拼接画面"),
+                            video_url: String::new(),
+                            start_time,
+                            notes: String::new(),
+                        },
+                    };
+                    // 录制期间保持开始时的布局
+                    match create_composite_record_pipeline(
+                        &self.layout_positions(),
+                        preferences.get_default_video_encoder(),
+                        pathbuf.to_str().unwrap(),
+                        &options,
+                    ) {
+                        Ok((pipeline, appsrcs)) => {
+                            let slaves = self.get_slaves();
+                            for (i, appsrc) in appsrcs.iter().enumerate() {
+                                slaves.send(i, SlaveInput::SetCompositeFeed(Some(appsrc.clone())));
+                            }
+                            self.composite_recorder = Some((pipeline, appsrcs));
+                            self.set_composite_recording(true);
+                        }
+                        Err(err) => {
+                            self.set_toast_message(Some(format!("无法开始拼接录制：{}", err)))
+                        }
+                    }
+                }
+                Some((pipeline, appsrcs)) => {
+                    let slaves = self.get_slaves();
+                    for i in 0..slaves.len() {
+                        slaves.send(i, SlaveInput::SetCompositeFeed(None));
+                    }
+                    if let Err(err) = stop_composite_record_pipeline(&pipeline, &appsrcs) {
+                        self.set_toast_message(Some(format!("无法正常结束拼接录制：{}", err)));
+                        pipeline.set_state(gst::State::Null).ok();
+                    }
+                    self.set_composite_recording(false);
+                }
+            },
+            ToggleSyncRecording => match self.sync_recording {
+                Some(false) => {
+                    let slaves = self.get_slaves();
+                    let participants: Vec<usize> = (0..slaves.len())
+                        .filter(|index| {
+                            slaves
+                                .get(*index)
+                                .map_or(false, SlaveModel::can_sync_record)
+                        })
+                        .collect();
+                    if participants.is_empty() {
+                        self.set_toast_message(Some(String::from(
+                            "没有可以参与同步录制的机位，请先开始拉流。",
+                        )));
+                        return;
+                    }
+                    // 所有机位使用同一开始时间命名文件并写入元数据，便于事后对齐
+                    let preferences = self.prefermances_model.model();
+                    let start_time = glib::DateTime::now_local().unwrap();
+                    let mut directory = preferences.get_video_save_path().clone();
+                    if *preferences.get_video_sync_record_use_separate_directory() {
+                        directory.push(start_time.format_iso8601().unwrap().replace(":", "-"));
+                        if let Err(err) = std::fs::create_dir_all(&directory) {
+                            self.set_toast_message(Some(format!(
+                                "无法创建同步录制文件夹 {}：{}",
+                                directory.display(),
+                                err
+                            )));
+                            return;
+                        }
+                    }
+                    let slaves = self.get_slaves();
+                    for i in participants {
+                        slaves.send(
+                            i,
+                            SlaveInput::StartSyncRecord(directory.clone(), start_time.clone()),
+                        );
+                    }
+                    self.set_sync_recording(Some(true));
+                }
+                Some(true) => {
+                    let slaves = self.get_slaves();
+                    for i in 0..slaves.len() {
+                        slaves.send(i, SlaveInput::StopSyncRecord);
+                    }
+                    self.set_sync_recording(Some(false));
+                }
+                None => (),
+            },
+        }
+    }
+}
+
+impl AppModel {
+    fn session(&self) -> SessionModel {
+        let slaves = self.get_slaves();
+        SessionModel {
+            layout_mode: *self.prefermances_model.model().get_layout_mode(),
+            main_slave: self.main_slave,
+            slaves: (0..slaves.len())
+                .filter_map(|index| slaves.get(index).map(SlaveModel::session))
+                .collect(),
+        }
+    }
+
+    fn main_slave(&self) -> usize {
+        self.main_slave
+            .min(self.get_slaves().len().saturating_sub(1))
+    }
+
+    fn layout_positions(&self) -> Vec<GridPosition> {
+        let preferences = self.prefermances_model.model();
+        preferences.get_layout_mode().positions(
+            self.get_slaves().len(),
+            self.main_slave(),
+            *preferences.get_custom_layout_columns() as usize,
+        )
+    }
+
+    // 按当前布局设置每个机位在 Grid 中的位置与跨度
+    fn apply_layout(&self) {
+        let layout_mode = *self.prefermances_model.model().get_layout_mode();
+        let slaves = self.get_slaves();
+        let grid = slaves.widget();
+        let main = self.main_slave();
+        let positions = self.layout_positions();
+        let layout = match grid
+            .layout_manager()
+            .and_then(|layout| layout.downcast::<GridLayout>().ok())
+        {
+            Some(layout) => layout,
+            None => return,
+        };
+        grid.set_row_homogeneous(layout_mode.has_main_view());
+        grid.set_column_homogeneous(layout_mode.has_main_view());
+        for (index, position) in positions.iter().enumerate() {
+            if let Some(child) = slaves.get(index).and_then(SlaveModel::grid_child) {
+                if let Ok(layout_child) = layout.layout_child(child).downcast::<GridLayoutChild>() {
+                    layout_child.set_column(position.column);
+                    layout_child.set_row(position.row);
+                    layout_child.set_column_span(position.width);
+                    layout_child.set_row_span(position.height);
+                }
+            }
+            slaves.send(
+                index,
+                SlaveInput::SetCompact(layout_mode.has_main_view() && index != main),
+            );
+        }
+        // 画中画模式下缩略图叠放在主画面上，主画面需最先绘制
+        if let Some(child) = slaves.get(main).and_then(SlaveModel::grid_child) {
+            child.insert_after(grid, None::<&Widget>);
+        }
+    }
+
+    fn slave_removed(&mut self, index: usize) {
+        if index < self.main_slave {
+            self.main_slave -= 1;
+        } else if index == self.main_slave {
+            self.main_slave = 0;
         }
+        self.apply_layout();
     }
 }
 
@@ -270,11 +631,16 @@ impl Default for AppColorScheme {
 pub enum AppMsg {
     UpdataPreferences(PreferencesModel),
     NewSlave,
+    RestoreSlave(SlaveSession),
     RemoveLastSlave,
     DestroySlave(usize),
     // DispatchInputEvent(InputEvent),
     SetColorScheme(AppColorScheme),
     ToggleSyncRecording,
+    CaptureAll,
+    SetLayoutMode(LayoutMode),
+    PromoteSlave(usize),
+    ToggleCompositeRecording,
     SetFullscreened(bool),
     OpenAboutDialog,
     OpenPreferencesWindow,
@@ -5,7 +5,10 @@ use adw::{
 
 use derivative::Derivative;
 use relm4::{
-    gtk::{gdk, glib, Align, Entry, Inhibit, Label, SpinButton, StringList, Switch},
+    gtk::{
+        gdk, glib, Align, Entry, Inhibit, Label, SpinButton, StringList, Switch,
+        INVALID_LIST_POSITION,
+    },
     prelude::*,
     ComponentParts, SimpleComponent,
 };
@@ -15,9 +18,10 @@ use strum::IntoEnumIterator;
 use url::Url;
 
 use crate::{
+    layout::LayoutMode,
     slave::video_ext::{
-        ColorspaceConversion, ImageFormat, VideoCodec, VideoCodecProvider, VideoDecoder,
-        VideoEncoder,
+        ColorspaceConversion, ImageFormat, RecordSegmentation, VideoCodec, VideoCodecProvider,
+        VideoContainer, VideoDecoder, VideoDisplayBackend, VideoEncoder,
     },
     AppColorScheme,
 };
@@ -62,28 +66,57 @@ pub fn get_image_path() -> PathBuf {
 pub struct PreferencesModel {
     #[derivative(Default(value = "1"))]
     pub initial_slave_num: u8,
+    #[derivative(Default(value = "false"))]
+    pub restore_session: bool,
     pub application_color_scheme: AppColorScheme,
+    pub layout_mode: LayoutMode,
+    #[derivative(Default(value = "2"))]
+    pub custom_layout_columns: u32,
     #[derivative(Default(value = "get_video_path()"))]
     pub video_save_path: PathBuf,
     #[derivative(Default(value = "get_image_path()"))]
     pub image_save_path: PathBuf,
     #[derivative(Default(value = "ImageFormat::JPEG"))]
     pub image_save_format: ImageFormat,
+    #[derivative(Default(value = "90"))]
+    pub image_jpeg_quality: u8,
+    #[derivative(Default(value = "1"))]
+    pub screenshot_burst_count: u32,
+    #[derivative(Default(value = "200"))]
+    pub screenshot_burst_interval: u32,
+    #[derivative(Default(value = "false"))]
+    pub screenshot_save_raw: bool,
+    #[derivative(Default(value = "false"))]
+    pub screenshot_sync_use_separate_directory: bool,
     #[derivative(Default(value = "false"))]
     pub default_reencode_recording_video: bool,
+    #[derivative(Default(value = "false"))]
+    pub default_record_processed_video: bool,
     pub default_video_encoder: VideoEncoder,
     #[derivative(Default(value = "Url::from_str(\"http://192.168.137.219:8888\").unwrap()"))]
     pub default_slave_url: Url,
+    #[derivative(Default(value = "1"))]
+    pub slave_url_stride: u32,
     #[derivative(Default(
         value = "Url::from_str(\"rtp://192.168.31.100:5600?encoding-name=H264\").unwrap()"
     ))]
     pub default_video_url: Url,
+    #[derivative(Default(value = "1"))]
+    pub video_url_port_stride: u32,
     #[derivative(Default(value = "60"))]
     pub default_input_sending_rate: u16,
     #[derivative(Default(value = "true"))]
     pub default_keep_video_display_ratio: bool,
+    #[derivative(Default(value = "true"))]
+    pub default_hud_displayed: bool,
+    #[derivative(Default(value = "false"))]
+    pub default_burn_hud: bool,
     pub default_video_decoder: VideoDecoder,
     pub default_colorspace_conversion: ColorspaceConversion,
+    pub default_video_display_backend: VideoDisplayBackend,
+    pub default_video_container: VideoContainer,
+    #[derivative(Default(value = "0"))]
+    pub default_pre_record_duration: u32,
     #[derivative(Default(value = "64"))]
     pub param_tuner_graph_view_point_num_limit: u16,
     #[derivative(Default(value = "250"))]
@@ -92,10 +125,24 @@ pub struct PreferencesModel {
     pub pipeline_timeout: Duration,
     #[derivative(Default(value = "false"))]
     pub default_appsink_queue_leaky_enabled: bool,
+    #[derivative(Default(value = "0"))]
+    pub default_appsink_max_buffers: u32,
+    #[derivative(Default(value = "false"))]
+    pub default_appsink_drop: bool,
+    #[derivative(Default(value = "false"))]
+    pub default_auto_restart_pipeline: bool,
+    #[derivative(Default(value = "3000"))]
+    pub default_stall_timeout: u32,
     #[derivative(Default(value = "false"))]
     pub default_use_decodebin: bool,
     #[derivative(Default(value = "false"))]
     pub video_sync_record_use_separate_directory: bool,
+    #[derivative(Default(value = "false"))]
+    pub video_record_segmented: bool,
+    #[derivative(Default(value = "300"))]
+    pub video_record_segment_duration: u32,
+    #[derivative(Default(value = "0"))]
+    pub video_record_segment_size: u32,
     #[derivative(Default(value = "200"))]
     pub default_video_latency: u32,
     #[derivative(Default(value = "500"))]
@@ -109,23 +156,46 @@ pub enum PreferencesMsg {
     SetVideoSavePath(PathBuf),
     SetImageSavePath(PathBuf),
     SetInitialSlaveNum(u8),
+    SetRestoreSession(bool),
+    SetLayoutMode(LayoutMode),
+    SetCustomLayoutColumns(u32),
     SetInputSendingRate(u16),
     SetParamTunerGraphViewUpdateInterval(u16),
     SetDefaultKeepVideoDisplayRatio(bool),
+    SetDefaultHudDisplayed(bool),
+    SetDefaultBurnHud(bool),
     SetImageSaveFormat(ImageFormat),
+    SetImageJpegQuality(u8),
+    SetScreenshotBurstCount(u32),
+    SetScreenshotBurstInterval(u32),
+    SetScreenshotSaveRaw(bool),
+    SetScreenshotSyncUseSeparateDirectory(bool),
     SetDefaultVideoDecoderCodec(VideoCodec),
     SetDefaultVideoDecoderCodecProvider(VideoCodecProvider),
     SetDefaultVideoEncoderCodec(VideoCodec),
     SetDefaultVideoEncoderCodecProvider(VideoCodecProvider),
     SetDefaultColorspaceConversion(ColorspaceConversion),
+    SetDefaultVideoDisplayBackend(VideoDisplayBackend),
+    SetDefaultVideoContainer(VideoContainer),
+    SetDefaultPreRecordDuration(u32),
     SetParameterTunerGraphViewPointNumberLimit(u16),
     SetDefaultReencodeRecordingVideo(bool),
+    SetDefaultRecordProcessedVideo(bool),
     SetDefaultUseDecodebin(bool),
     SetDefaultAppSinkQueueLeakyEnabled(bool),
+    SetDefaultAppSinkMaxBuffers(u32),
+    SetDefaultAppSinkDrop(bool),
+    SetDefaultAutoRestartPipeline(bool),
+    SetDefaultStallTimeout(u32),
     SetVideoSyncRecordUseSeparateDirectory(bool),
+    SetVideoRecordSegmented(bool),
+    SetVideoRecordSegmentDuration(u32),
+    SetVideoRecordSegmentSize(u32),
     SetDefaultVideoLatency(u32),
     SetDefaultVideoUrl(Url),
     SetDefaultSlaveUrl(Url),
+    SetSlaveUrlStride(u32),
+    SetVideoUrlPortStride(u32),
     SetPipelineTimeout(Duration),
     SetApplicationColorScheme(AppColorScheme),
     SetDefaultStatusInfoUpdateInterval(u16),
@@ -152,6 +222,13 @@ impl PreferencesModel {
             None => Default::default(),
         }
     }
+
+    pub fn record_segmentation(&self) -> Option<RecordSegmentation> {
+        self.video_record_segmented.then(|| RecordSegmentation {
+            max_duration: Duration::from_secs(self.video_record_segment_duration as u64),
+            max_size_bytes: self.video_record_segment_size as u64 * 1024 * 1024,
+        })
+    }
 }
 
 #[relm4::component(pub)]
@@ -208,7 +285,54 @@ impl SimpleComponent for PreferencesModel {
                                 sender.input(PreferencesMsg::SetInitialSlaveNum(button.value() as u8));
                             }
                         }
-                    }
+                    },
+                    add = &ActionRow {
+                        set_title: "恢复上次会话",
+                        set_subtitle: "启动时恢复上次退出前的机位、各机位配置、布局与拉流状态，不再按初始机位数量创建",
+                        add_suffix: restore_session_switch = &Switch {
+                            #[track = "model.changed(PreferencesModel::restore_session())"]
+                            set_active: model.restore_session,
+                            set_valign: Align::Center,
+                            connect_state_set[sender] => move |_, state| {
+                                sender.input(PreferencesMsg::SetRestoreSession(state));
+                                Inhibit(false)
+                            }
+                        },
+                        set_activatable_widget: Some(&restore_session_switch),
+                    },
+                    add = &ComboRow {
+                        set_title: "画面布局",
+                        set_subtitle: "多个机位画面的排列方式，也可在标题栏中切换",
+                        set_model: Some(&{
+                            let model = StringList::new(&[]);
+                            for value in LayoutMode::iter() {
+                                model.append(&value.to_string());
+                            }
+                            model
+                        }),
+                        #[track = "model.changed(PreferencesModel::layout_mode())"]
+                        set_selected: LayoutMode::iter().position(|x| x == model.layout_mode).unwrap() as u32,
+                        connect_selected_notify[sender] => move |row| {
+                            sender.input(PreferencesMsg::SetLayoutMode(LayoutMode::iter().nth(row.selected() as usize).unwrap()))
+                        },
+                    },
+                    add = &ActionRow {
+                        set_title: "自定义网格列数",
+                        set_subtitle: "使用自定义网格布局时每行排列的机位数量",
+                        add_suffix = &SpinButton::with_range(1.0, 6.0, 1.0) {
+                            #[track = "model.changed(PreferencesModel::custom_layout_columns())"]
+                            set_value: model.custom_layout_columns as f64,
+                            set_digits: 0,
+                            set_valign: Align::Center,
+                            set_can_focus: false,
+                            connect_value_changed[sender] => move |button| {
+                                sender.input(PreferencesMsg::SetCustomLayoutColumns(button.value() as u32));
+                            }
+                        },
+                        add_suffix = &Label {
+                            set_label: "列",
+                        },
+                    },
                 },
             },
             add = &PreferencesPage {
@@ -235,6 +359,20 @@ impl SimpleComponent for PreferencesModel {
                             }
                          },
                     },
+                    add = &ActionRow {
+                        set_title: "连接 URL 地址步长",
+                        set_subtitle: "每增加一个机位，IPV4 地址累加的数值，设置为 0 时所有机位使用相同地址",
+                        add_suffix = &SpinButton::with_range(0.0, 255.0, 1.0) {
+                            #[track = "model.changed(PreferencesModel::slave_url_stride())"]
+                            set_value: model.slave_url_stride as f64,
+                            set_digits: 0,
+                            set_valign: Align::Center,
+                            set_can_focus: false,
+                            connect_value_changed[sender] => move |button| {
+                                sender.input(PreferencesMsg::SetSlaveUrlStride(button.value() as u32));
+                            }
+                        },
+                    },
                 },
                 add = &PreferencesGroup {
                     set_description: Some("机器人状态信息接收设置"),
@@ -313,6 +451,52 @@ impl SimpleComponent for PreferencesModel {
                         },
                         set_activatable_widget: Some(&default_keep_video_display_ratio_switch),
                     },
+                    add = &ActionRow {
+                        set_title: "默认显示 HUD",
+                        set_subtitle: "在画面上叠加航向、深度、锁定状态、录制状态与时间",
+                        add_suffix: default_hud_displayed_switch = &Switch {
+                            #[track = "model.changed(PreferencesModel::default_hud_displayed())"]
+                            set_active: model.default_hud_displayed,
+                            set_valign: Align::Center,
+                            connect_state_set[sender] => move |_, state| {
+                                sender.input(PreferencesMsg::SetDefaultHudDisplayed(state));
+                                Inhibit(false)
+                            }
+                        },
+                        set_activatable_widget: Some(&default_hud_displayed_switch),
+                    },
+                    add = &ActionRow {
+                        set_title: "默认录制与截图时叠加 HUD",
+                        set_subtitle: "将 HUD 绘制到截图中，录制时仅在录制增强后的画面时生效",
+                        add_suffix: default_burn_hud_switch = &Switch {
+                            #[track = "model.changed(PreferencesModel::default_burn_hud())"]
+                            set_active: model.default_burn_hud,
+                            set_valign: Align::Center,
+                            connect_state_set[sender] => move |_, state| {
+                                sender.input(PreferencesMsg::SetDefaultBurnHud(state));
+                                Inhibit(false)
+                            }
+                        },
+                        set_activatable_widget: Some(&default_burn_hud_switch),
+                    },
+                    add = &ComboRow {
+                        set_title: "默认显示方式",
+                        set_subtitle: "GTK Paintable 方式跳过图像复制以降低处理器占用，仅在未启用增强算法时生效",
+                        set_model: Some(&{
+                            let model = StringList::new(&[]);
+                            for value in VideoDisplayBackend::available() {
+                                model.append(&value.to_string());
+                            }
+                            model
+                        }),
+                        #[track = "model.changed(PreferencesModel::default_video_display_backend())"]
+                        set_selected: VideoDisplayBackend::available().iter().position(|x| *x == model.default_video_display_backend).map_or(INVALID_LIST_POSITION, |index| index as u32),
+                        connect_selected_notify[sender] => move |row| {
+                            if let Some(value) = VideoDisplayBackend::available().get(row.selected() as usize) {
+                                sender.input(PreferencesMsg::SetDefaultVideoDisplayBackend(*value));
+                            }
+                        }
+                    },
                 },
                 add = &PreferencesGroup {
                     set_title: "管道",
@@ -335,6 +519,20 @@ impl SimpleComponent for PreferencesModel {
                             }
                         },
                     },
+                    add = &ActionRow {
+                        set_title: "视频 URL 端口步长",
+                        set_subtitle: "每增加一个机位，视频 URL 端口累加的数值，设置为 0 时所有机位使用相同端口",
+                        add_suffix = &SpinButton::with_range(0.0, 1000.0, 1.0) {
+                            #[track = "model.changed(PreferencesModel::video_url_port_stride())"]
+                            set_value: model.video_url_port_stride as f64,
+                            set_digits: 0,
+                            set_valign: Align::Center,
+                            set_can_focus: false,
+                            connect_value_changed[sender] => move |button| {
+                                sender.input(PreferencesMsg::SetVideoUrlPortStride(button.value() as u32));
+                            }
+                        },
+                    },
                     add = &ActionRow {
                         set_title: "默认启用画面自动跳帧",
                         set_subtitle: "默认启用自动跳帧，当机位画面与视频流延迟过大时避免延迟提升",
@@ -349,6 +547,68 @@ impl SimpleComponent for PreferencesModel {
                         },
                         set_activatable_widget: Some(&appsink_queue_leaky_enabled_switch),
                     },
+                    add = &ActionRow {
+                        set_title: "默认显示缓冲帧数上限",
+                        set_subtitle: "显示端默认最多缓存的帧数，设置为 0 以不限制",
+                        add_suffix = &SpinButton::with_range(0.0, 60.0, 1.0) {
+                            #[track = "model.changed(PreferencesModel::default_appsink_max_buffers())"]
+                            set_value: model.default_appsink_max_buffers as f64,
+                            set_digits: 0,
+                            set_valign: Align::Center,
+                            set_can_focus: false,
+                            connect_value_changed[sender] => move |button| {
+                                sender.input(PreferencesMsg::SetDefaultAppSinkMaxBuffers(button.value() as u32));
+                            }
+                        },
+                        add_suffix = &Label {
+                            set_label: "帧",
+                        },
+                    },
+                    add = &ActionRow {
+                        set_title: "默认缓冲区满时丢弃旧帧",
+                        set_subtitle: "显示缓冲区达到上限时默认丢弃最旧的帧而不是阻塞解码",
+                        add_suffix: appsink_drop_switch = &Switch {
+                            #[track = "model.changed(PreferencesModel::default_appsink_drop())"]
+                            set_active: *model.get_default_appsink_drop(),
+                            set_valign: Align::Center,
+                            connect_state_set[sender] => move |_, state| {
+                                sender.input(PreferencesMsg::SetDefaultAppSinkDrop(state));
+                                Inhibit(false)
+                            }
+                        },
+                        set_activatable_widget: Some(&appsink_drop_switch),
+                    },
+                    add = &ActionRow {
+                        set_title: "默认出错时自动重启管道",
+                        set_subtitle: "视频流出错或中断后默认自动尝试重新拉流",
+                        add_suffix: auto_restart_pipeline_switch = &Switch {
+                            #[track = "model.changed(PreferencesModel::default_auto_restart_pipeline())"]
+                            set_active: *model.get_default_auto_restart_pipeline(),
+                            set_valign: Align::Center,
+                            connect_state_set[sender] => move |_, state| {
+                                sender.input(PreferencesMsg::SetDefaultAutoRestartPipeline(state));
+                                Inhibit(false)
+                            }
+                        },
+                        set_activatable_widget: Some(&auto_restart_pipeline_switch),
+                    },
+                    add = &ActionRow {
+                        set_title: "默认画面中断超时",
+                        set_subtitle: "超过该时间没有收到新画面时视为信号丢失并重新拉流，设置为 0 以禁用",
+                        add_suffix = &SpinButton::with_range(0.0, 60000.0, 500.0) {
+                            #[track = "model.changed(PreferencesModel::default_stall_timeout())"]
+                            set_value: model.default_stall_timeout as f64,
+                            set_digits: 0,
+                            set_valign: Align::Center,
+                            set_can_focus: false,
+                            connect_value_changed[sender] => move |button| {
+                                sender.input(PreferencesMsg::SetDefaultStallTimeout(button.value() as u32));
+                            }
+                        },
+                        add_suffix = &Label {
+                            set_label: "毫秒",
+                        },
+                    },
                     add = &ExpanderRow {
                         set_title: "默认手动配置管道",
                         set_show_enable_switch: true,
@@ -380,48 +640,55 @@ impl SimpleComponent for PreferencesModel {
                             set_subtitle: "指定解码视频流默认使用的解码器",
                             set_model: Some(&{
                                 let model = StringList::new(&[]);
-                                // for value in VideoCodec::iter() {
-                                //     model.append(&value.to_string());
-                                // }
+                                for value in VideoCodec::available(false) {
+                                    model.append(&value.to_string());
+                                }
                                 model
                             }),
-                            // #[track = "model.changed(PreferencesModel::default_video_decoder())"]
-                            // set_selected: VideoCodec::iter().position(|x| x == model.default_video_decoder.0).unwrap() as u32,
-                            // connect_selected_notify[sender] => move |row| {
-                            //     sender.input(PreferencesMsg::SetDefaultVideoDecoderCodec(VideoCodec::iter().nth(row.selected() as usize).unwrap()))
-                            // }
+                            #[track = "model.changed(PreferencesModel::default_video_decoder())"]
+                            set_selected: VideoCodec::available(false).iter().position(|x| *x == model.default_video_decoder.0).map_or(INVALID_LIST_POSITION, |index| index as u32),
+                            connect_selected_notify[sender] => move |row| {
+                                if let Some(value) = VideoCodec::available(false).get(row.selected() as usize) {
+                                    sender.input(PreferencesMsg::SetDefaultVideoDecoderCodec(*value));
+                                }
+                            }
                         },
                         add_row = &ComboRow {
                             set_title: "默认解码器接口",
-                            set_subtitle: "指定解码视频流默认使用的解码器接口",
+                            #[track = "model.changed(PreferencesModel::default_video_decoder())"]
+                            set_subtitle: &model.default_video_decoder.missing_element().map_or_else(|| String::from("指定解码视频流默认使用的解码器接口"), |name| format!("当前系统缺少元素 {}，请更换解码器接口", name)),
                             set_model: Some(&{
                                 let model = StringList::new(&[]);
-                                // for value in VideoCodecProvider::iter() {
-                                //     model.append(&value.to_string());
-                                // }
+                                for value in VideoCodecProvider::available(false) {
+                                    model.append(&value.to_string());
+                                }
                                 model
                             }),
-                            // #[track = "model.changed(PreferencesModel::default_video_decoder())"]
-                            // set_selected: VideoCodecProvider::iter().position(|x| x == model.default_video_decoder.1).unwrap() as u32,
-                            // connect_selected_notify[sender] => move |row| {
-                            //     sender.input(PreferencesMsg::SetDefaultVideoDecoderCodecProvider(VideoCodecProvider::iter().nth(row.selected() as usize).unwrap()))
-                            // }
+                            #[track = "model.changed(PreferencesModel::default_video_decoder())"]
+                            set_selected: VideoCodecProvider::available(false).iter().position(|x| *x == model.default_video_decoder.1).map_or(INVALID_LIST_POSITION, |index| index as u32),
+                            connect_selected_notify[sender] => move |row| {
+                                if let Some(value) = VideoCodecProvider::available(false).get(row.selected() as usize) {
+                                    sender.input(PreferencesMsg::SetDefaultVideoDecoderCodecProvider(*value));
+                                }
+                            }
                         },
                         add_row = &ComboRow {
                             set_title: "默认色彩空间转换",
                             set_subtitle: "设置视频编解码、视频流显示要求的色彩空间转换所使用的默认硬件",
                             set_model: Some(&{
                                 let model = StringList::new(&[]);
-                                // for value in ColorspaceConversion::iter() {
-                                //     model.append(&value.to_string());
-                                // }
+                                for value in ColorspaceConversion::available() {
+                                    model.append(&value.to_string());
+                                }
                                 model
                             }),
-                            // #[track = "model.changed(PreferencesModel::default_colorspace_conversion())"]
-                            // set_selected: ColorspaceConversion::iter().position(|x| x == model.default_colorspace_conversion).unwrap() as u32,
-                            // connect_selected_notify[sender] => move |row| {
-                            //     sender.input(PreferencesMsg::SetDefaultColorspaceConversion(ColorspaceConversion::iter().nth(row.selected() as usize).unwrap()));
-                            // }
+                            #[track = "model.changed(PreferencesModel::default_colorspace_conversion())"]
+                            set_selected: ColorspaceConversion::available().iter().position(|x| *x == model.default_colorspace_conversion).map_or(INVALID_LIST_POSITION, |index| index as u32),
+                            connect_selected_notify[sender] => move |row| {
+                                if let Some(value) = ColorspaceConversion::available().get(row.selected() as usize) {
+                                    sender.input(PreferencesMsg::SetDefaultColorspaceConversion(*value));
+                                }
+                            }
                         },
                     },
                     add = &ActionRow {
@@ -459,16 +726,96 @@ impl SimpleComponent for PreferencesModel {
                         set_subtitle: "截图保存的图片格式",
                         set_model: Some(&{
                             let model = StringList::new(&[]);
-                            // for value in ImageFormat::iter() {
-                            //     model.append(&value.to_string());
-                            // }
+                            for value in ImageFormat::iter() {
+                                model.append(&value.to_string());
+                            }
                             model
                         }),
-                        // #[track = "model.changed(PreferencesModel::image_save_format())"]
-                        // set_selected: ImageFormat::iter().position(|x| x == model.image_save_format).unwrap() as u32,
-                        // connect_selected_notify[sender] => move |row| {
-                        //     sender.input(PreferencesMsg::SetImageSaveFormat(ImageFormat::iter().nth(row.selected() as usize).unwrap()))
-                        // }
+                        #[track = "model.changed(PreferencesModel::image_save_format())"]
+                        set_selected: ImageFormat::iter().position(|x| x == model.image_save_format).unwrap() as u32,
+                        connect_selected_notify[sender] => move |row| {
+                            sender.input(PreferencesMsg::SetImageSaveFormat(ImageFormat::iter().nth(row.selected() as usize).unwrap()))
+                        }
+                    },
+                    add = &ActionRow {
+                        set_title: "JPEG 图片质量",
+                        set_subtitle: "以 JPEG 格式保存截图时的压缩质量，数值越大画质越好、文件越大",
+                        #[track = "model.changed(PreferencesModel::image_save_format())"]
+                        set_sensitive: model.image_save_format == ImageFormat::JPEG,
+                        add_suffix = &SpinButton::with_range(1.0, 100.0, 1.0) {
+                            #[track = "model.changed(PreferencesModel::image_jpeg_quality())"]
+                            set_value: model.image_jpeg_quality as f64,
+                            set_digits: 0,
+                            set_valign: Align::Center,
+                            set_can_focus: false,
+                            connect_value_changed[sender] => move |button| {
+                                sender.input(PreferencesMsg::SetImageJpegQuality(button.value() as u8));
+                            }
+                        },
+                    },
+                    add = &ActionRow {
+                        set_title: "连拍张数",
+                        set_subtitle: "每次截图连续保存的画面数量，设置为 1 以禁用连拍",
+                        add_suffix = &SpinButton::with_range(1.0, 100.0, 1.0) {
+                            #[track = "model.changed(PreferencesModel::screenshot_burst_count())"]
+                            set_value: model.screenshot_burst_count as f64,
+                            set_digits: 0,
+                            set_valign: Align::Center,
+                            set_can_focus: false,
+                            connect_value_changed[sender] => move |button| {
+                                sender.input(PreferencesMsg::SetScreenshotBurstCount(button.value() as u32));
+                            }
+                        },
+                        add_suffix = &Label {
+                            set_label: "张",
+                        },
+                    },
+                    add = &ActionRow {
+                        set_title: "连拍间隔",
+                        set_subtitle: "连拍时相邻两张截图之间的时间间隔",
+                        #[track = "model.changed(PreferencesModel::screenshot_burst_count())"]
+                        set_sensitive: model.screenshot_burst_count > 1,
+                        add_suffix = &SpinButton::with_range(50.0, 10000.0, 50.0) {
+                            #[track = "model.changed(PreferencesModel::screenshot_burst_interval())"]
+                            set_value: model.screenshot_burst_interval as f64,
+                            set_digits: 0,
+                            set_valign: Align::Center,
+                            set_can_focus: false,
+                            connect_value_changed[sender] => move |button| {
+                                sender.input(PreferencesMsg::SetScreenshotBurstInterval(button.value() as u32));
+                            }
+                        },
+                        add_suffix = &Label {
+                            set_label: "毫秒",
+                        },
+                    },
+                    add = &ActionRow {
+                        set_title: "同时保存原始画面",
+                        set_subtitle: "启用增强算法时，额外保存一张未经处理的画面，文件名以 _raw 结尾",
+                        add_suffix: screenshot_save_raw_switch = &Switch {
+                            #[track = "model.changed(PreferencesModel::screenshot_save_raw())"]
+                            set_active: model.screenshot_save_raw,
+                            set_valign: Align::Center,
+                            connect_state_set[sender] => move |_, state| {
+                                sender.input(PreferencesMsg::SetScreenshotSaveRaw(state));
+                                Inhibit(false)
+                            }
+                        },
+                        set_activatable_widget: Some(&screenshot_save_raw_switch),
+                    },
+                    add = &ActionRow {
+                        set_title: "全部截图时使用单独文件夹",
+                        set_subtitle: "每次对所有机位同时截图时，都在图片保存目录下创建新的文件夹，并在其中保存截图",
+                        add_suffix: screenshot_sync_use_separate_directory_switch = &Switch {
+                            #[track = "model.changed(PreferencesModel::screenshot_sync_use_separate_directory())"]
+                            set_active: *model.get_screenshot_sync_use_separate_directory(),
+                            set_valign: Align::Center,
+                            connect_state_set[sender] => move |_, state| {
+                                sender.input(PreferencesMsg::SetScreenshotSyncUseSeparateDirectory(state));
+                                Inhibit(false)
+                            }
+                        },
+                        set_activatable_widget: Some(&screenshot_sync_use_separate_directory_switch),
                     },
                 },
                 add = &PreferencesGroup {
@@ -497,6 +844,86 @@ impl SimpleComponent for PreferencesModel {
                         },
                         set_activatable_widget: Some(&video_sync_record_use_separate_directory_switch),
                     },
+                    add = &ExpanderRow {
+                        set_title: "分段录制",
+                        set_subtitle: "将录制的视频按时长或大小切分为多个文件，异常退出时仅会损坏最后一个分段",
+                        set_show_enable_switch: true,
+                        set_expanded: *model.get_video_record_segmented(),
+                        #[track = "model.changed(PreferencesModel::video_record_segmented())"]
+                        set_enable_expansion: *model.get_video_record_segmented(),
+                        connect_enable_expansion_notify[sender] => move |expander| {
+                            sender.input(PreferencesMsg::SetVideoRecordSegmented(expander.enables_expansion()));
+                        },
+                        add_row = &ActionRow {
+                            set_title: "分段时长",
+                            set_subtitle: "单个分段的最长时长，设置为 0 以不限制",
+                            add_suffix = &SpinButton::with_range(0.0, 86400.0, 60.0) {
+                                #[track = "model.changed(PreferencesModel::video_record_segment_duration())"]
+                                set_value: model.video_record_segment_duration as f64,
+                                set_digits: 0,
+                                set_valign: Align::Center,
+                                set_can_focus: false,
+                                connect_value_changed[sender] => move |button| {
+                                    sender.input(PreferencesMsg::SetVideoRecordSegmentDuration(button.value() as u32));
+                                }
+                            },
+                            add_suffix = &Label {
+                                set_label: "秒",
+                            },
+                        },
+                        add_row = &ActionRow {
+                            set_title: "分段大小",
+                            set_subtitle: "单个分段的最大文件大小，设置为 0 以不限制",
+                            add_suffix = &SpinButton::with_range(0.0, 65536.0, 100.0) {
+                                #[track = "model.changed(PreferencesModel::video_record_segment_size())"]
+                                set_value: model.video_record_segment_size as f64,
+                                set_digits: 0,
+                                set_valign: Align::Center,
+                                set_can_focus: false,
+                                connect_value_changed[sender] => move |button| {
+                                    sender.input(PreferencesMsg::SetVideoRecordSegmentSize(button.value() as u32));
+                                }
+                            },
+                            add_suffix = &Label {
+                                set_label: "MB",
+                            },
+                        },
+                    },
+                    add = &ComboRow {
+                        set_title: "默认录制格式",
+                        set_subtitle: "录制视频默认使用的封装格式",
+                        set_model: Some(&{
+                            let model = StringList::new(&[]);
+                            for value in VideoContainer::available() {
+                                model.append(&value.to_string());
+                            }
+                            model
+                        }),
+                        #[track = "model.changed(PreferencesModel::default_video_container())"]
+                        set_selected: VideoContainer::available().iter().position(|x| *x == model.default_video_container).map_or(INVALID_LIST_POSITION, |index| index as u32),
+                        connect_selected_notify[sender] => move |row| {
+                            if let Some(value) = VideoContainer::available().get(row.selected() as usize) {
+                                sender.input(PreferencesMsg::SetDefaultVideoContainer(*value));
+                            }
+                        }
+                    },
+                    add = &ActionRow {
+                        set_title: "默认预录时长",
+                        set_subtitle: "开始录制时一并写入此前缓存的画面，仅在录制时不重新编码且未使用 decodebin 时生效，设置为 0 以禁用",
+                        add_suffix = &SpinButton::with_range(0.0, 300.0, 5.0) {
+                            #[track = "model.changed(PreferencesModel::default_pre_record_duration())"]
+                            set_value: model.default_pre_record_duration as f64,
+                            set_digits: 0,
+                            set_valign: Align::Center,
+                            set_can_focus: false,
+                            connect_value_changed[sender] => move |button| {
+                                sender.input(PreferencesMsg::SetDefaultPreRecordDuration(button.value() as u32));
+                            }
+                        },
+                        add_suffix = &Label {
+                            set_label: "秒",
+                        },
+                    },
                     add = &ExpanderRow {
                         set_title: "默认录制时重新编码",
                         set_show_enable_switch: true,
@@ -511,32 +938,51 @@ impl SimpleComponent for PreferencesModel {
                             set_subtitle: "视频录制时默认使用的编码器",
                             set_model: Some(&{
                                 let model = StringList::new(&[]);
-                                // for value in VideoCodec::iter() {
-                                //     model.append(&value.to_string());
-                                // }
+                                for value in VideoCodec::available(true) {
+                                    model.append(&value.to_string());
+                                }
                                 model
                             }),
-                            // #[track = "model.changed(PreferencesModel::default_video_encoder())"]
-                            // set_selected: VideoCodec::iter().position(|x| x == model.default_video_encoder.0).unwrap() as u32,
-                            // connect_selected_notify[sender] => move |row| {
-                            //     sender.input(PreferencesMsg::SetDefaultVideoEncoderCodec(VideoCodec::iter().nth(row.selected() as usize).unwrap()))
-                            // }
+                            #[track = "model.changed(PreferencesModel::default_video_encoder())"]
+                            set_selected: VideoCodec::available(true).iter().position(|x| *x == model.default_video_encoder.0).map_or(INVALID_LIST_POSITION, |index| index as u32),
+                            connect_selected_notify[sender] => move |row| {
+                                if let Some(value) = VideoCodec::available(true).get(row.selected() as usize) {
+                                    sender.input(PreferencesMsg::SetDefaultVideoEncoderCodec(*value));
+                                }
+                            }
                         },
                         add_row = &ComboRow {
                             set_title: "默认编码器接口",
-                            set_subtitle: "视频录制时默认调用的编码器接口",
+                            #[track = "model.changed(PreferencesModel::default_video_encoder())"]
+                            set_subtitle: &model.default_video_encoder.missing_element().map_or_else(|| String::from("视频录制时默认调用的编码器接口"), |name| format!("当前系统缺少元素 {}，请更换编码器接口", name)),
                             set_model: Some(&{
                                 let model = StringList::new(&[]);
-                                // for value in VideoCodecProvider::iter() {
-                                //     model.append(&value.to_string());
-                                // }
+                                for value in VideoCodecProvider::available(true) {
+                                    model.append(&value.to_string());
+                                }
                                 model
                             }),
-                            // #[track = "model.changed(PreferencesModel::default_video_encoder())"]
-                            // set_selected: VideoCodecProvider::iter().position(|x| x == model.default_video_encoder.1).unwrap() as u32,
-                            // connect_selected_notify[sender] => move |row| {
-                            //     sender.input(PreferencesMsg::SetDefaultVideoEncoderCodecProvider(VideoCodecProvider::iter().nth(row.selected() as usize).unwrap()))
-                            // }
+                            #[track = "model.changed(PreferencesModel::default_video_encoder())"]
+                            set_selected: VideoCodecProvider::available(true).iter().position(|x| *x == model.default_video_encoder.1).map_or(INVALID_LIST_POSITION, |index| index as u32),
+                            connect_selected_notify[sender] => move |row| {
+                                if let Some(value) = VideoCodecProvider::available(true).get(row.selected() as usize) {
+                                    sender.input(PreferencesMsg::SetDefaultVideoEncoderCodecProvider(*value));
+                                }
+                            }
+                        },
+                        add_row = &ActionRow {
+                            set_title: "默认录制增强后的画面",
+                            set_subtitle: "默认录制经过增强算法处理后显示的画面，而非原始视频流",
+                            add_suffix: default_record_processed_video_switch = &Switch {
+                                #[track = "model.changed(PreferencesModel::default_record_processed_video())"]
+                                set_active: *model.get_default_record_processed_video(),
+                                set_valign: Align::Center,
+                                connect_state_set[sender] => move |_, state| {
+                                    sender.input(PreferencesMsg::SetDefaultRecordProcessedVideo(state));
+                                    Inhibit(false)
+                                }
+                            },
+                            set_activatable_widget: Some(&default_record_processed_video_switch),
                         },
                     },
                 },
@@ -592,7 +1038,9 @@ impl SimpleComponent for PreferencesModel {
         root: &Self::Root,
         sender: relm4::ComponentSender<Self>,
     ) -> relm4::ComponentParts<Self> {
-        let model: PreferencesModel = Default::default();
+        let mut model = PreferencesModel::load_or_default();
+        // 保存时窗口处于打开状态，载入后需隐藏
+        model.is_show = false;
         let widgets = view_output!();
         ComponentParts { model, widgets }
     }
@@ -604,29 +1052,44 @@ impl SimpleComponent for PreferencesModel {
             SetVideoSavePath(path) => self.set_video_save_path(path),
             SetImageSavePath(path) => self.set_image_save_path(path),
             SetInitialSlaveNum(num) => self.set_initial_slave_num(num),
+            SetRestoreSession(val) => self.set_restore_session(val),
+            SetLayoutMode(mode) => self.set_layout_mode(mode),
+            SetCustomLayoutColumns(columns) => self.set_custom_layout_columns(columns),
             SetInputSendingRate(rate) => self.set_default_input_sending_rate(rate),
             SetParamTunerGraphViewUpdateInterval(interval) => {
                 self.set_param_tuner_graph_view_update_interval(interval)
             }
             SetDefaultKeepVideoDisplayRatio(val) => self.set_default_keep_video_display_ratio(val),
+            SetDefaultHudDisplayed(val) => self.set_default_hud_displayed(val),
+            SetDefaultBurnHud(val) => self.set_default_burn_hud(val),
             SetParameterTunerGraphViewPointNumberLimit(limit) => {
                 self.set_param_tuner_graph_view_point_num_limit(limit)
             }
             SetDefaultAppSinkQueueLeakyEnabled(val) => {
                 self.set_default_appsink_queue_leaky_enabled(val)
             }
+            SetDefaultAppSinkMaxBuffers(val) => self.set_default_appsink_max_buffers(val),
+            SetDefaultAppSinkDrop(val) => self.set_default_appsink_drop(val),
+            SetDefaultAutoRestartPipeline(val) => self.set_default_auto_restart_pipeline(val),
+            SetDefaultStallTimeout(val) => self.set_default_stall_timeout(val),
             SetVideoSyncRecordUseSeparateDirectory(val) => {
                 self.set_video_sync_record_use_separate_directory(val)
             }
+            SetVideoRecordSegmented(val) => self.set_video_record_segmented(val),
+            SetVideoRecordSegmentDuration(val) => self.set_video_record_segment_duration(val),
+            SetVideoRecordSegmentSize(val) => self.set_video_record_segment_size(val),
             SetDefaultVideoLatency(val) => self.set_default_video_latency(val),
             SetDefaultVideoUrl(url) => self.default_video_url = url, // 放置光标移动到最前面
             SetDefaultSlaveUrl(url) => self.default_slave_url = url,
+            SetSlaveUrlStride(stride) => self.set_slave_url_stride(stride),
+            SetVideoUrlPortStride(stride) => self.set_video_url_port_stride(stride),
             SetDefaultReencodeRecordingVideo(val) => {
                 if !val {
                     self.set_default_use_decodebin(false);
                 }
                 self.set_default_reencode_recording_video(val)
             }
+            SetDefaultRecordProcessedVideo(val) => self.set_default_record_processed_video(val),
             SetDefaultUseDecodebin(val) => {
                 if val {
                     self.set_default_reencode_recording_video(true);
@@ -666,15 +1129,51 @@ impl SimpleComponent for PreferencesModel {
             SetDefaultColorspaceConversion(conversion) => {
                 self.set_default_colorspace_conversion(conversion)
             }
-            SetDefaultVideoDecoderCodec(codec) => self.get_mut_default_video_decoder().0 = codec,
+            SetDefaultVideoDisplayBackend(backend) => {
+                self.set_default_video_display_backend(backend)
+            }
+            SetDefaultVideoContainer(container) => self.set_default_video_container(container),
+            SetDefaultPreRecordDuration(duration) => self.set_default_pre_record_duration(duration),
+            SetDefaultVideoDecoderCodec(codec) => {
+                let decoder = self.get_mut_default_video_decoder();
+                decoder.0 = codec;
+                if !decoder.1.is_available(codec, false) {
+                    if let Some(provider) = VideoCodecProvider::best_available(codec, false) {
+                        decoder.1 = provider;
+                    }
+                }
+            }
             SetDefaultVideoDecoderCodecProvider(provider) => {
-                self.get_mut_default_video_decoder().1 = provider
+                // 接口不支持当前格式时保留原选择，get_mut 使下拉框恢复显示原接口
+                let decoder = self.get_mut_default_video_decoder();
+                if provider.is_available(decoder.0, false) {
+                    decoder.1 = provider;
+                }
+            }
+            SetDefaultVideoEncoderCodec(codec) => {
+                let encoder = self.get_mut_default_video_encoder();
+                encoder.0 = codec;
+                if !encoder.1.is_available(codec, true) {
+                    if let Some(provider) = VideoCodecProvider::best_available(codec, true) {
+                        encoder.1 = provider;
+                    }
+                }
             }
-            SetDefaultVideoEncoderCodec(codec) => self.get_mut_default_video_encoder().0 = codec,
             SetDefaultVideoEncoderCodecProvider(provider) => {
-                self.get_mut_default_video_encoder().1 = provider
+                // 接口不支持当前格式时保留原选择，get_mut 使下拉框恢复显示原接口
+                let encoder = self.get_mut_default_video_encoder();
+                if provider.is_available(encoder.0, true) {
+                    encoder.1 = provider;
+                }
             }
             SetImageSaveFormat(format) => self.set_image_save_format(format),
+            SetImageJpegQuality(quality) => self.set_image_jpeg_quality(quality),
+            SetScreenshotBurstCount(count) => self.set_screenshot_burst_count(count),
+            SetScreenshotBurstInterval(interval) => self.set_screenshot_burst_interval(interval),
+            SetScreenshotSaveRaw(save_raw) => self.set_screenshot_save_raw(save_raw),
+            SetScreenshotSyncUseSeparateDirectory(val) => {
+                self.set_screenshot_sync_use_separate_directory(val)
+            }
         }
 
         sender
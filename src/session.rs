@@ -0,0 +1,67 @@
+use std::{fs, path::PathBuf};
+
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+
+use crate::{layout::LayoutMode, preferences::get_data_path, slave::SlaveConfigModel};
+
+pub fn get_session_path() -> PathBuf {
+    let mut path = get_data_path();
+    path.push("session.json");
+    path
+}
+
+// 退出时保存的机位配置与布局，下次启动时可恢复
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionModel {
+    pub layout_mode: LayoutMode,
+    pub main_slave: usize,
+    pub slaves: Vec<SlaveSession>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SlaveSession {
+    #[serde(
+        serialize_with = "serialize_config",
+        deserialize_with = "deserialize_config"
+    )]
+    pub config: SlaveConfigModel,
+    pub polling: bool,
+}
+
+impl SessionModel {
+    pub fn load() -> Option<SessionModel> {
+        fs::read_to_string(get_session_path())
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|err| err.to_string())?;
+        fs::write(get_session_path(), json).map_err(|err| err.to_string())
+    }
+}
+
+// tracker 字段由宏生成，无法直接标注 #[serde(skip)]，因此在会话文件中去掉该字段
+const TRACKER_FIELD: &str = "tracker";
+
+fn serialize_config<S: Serializer>(
+    config: &SlaveConfigModel,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let mut value = serde_json::to_value(config).map_err(serde::ser::Error::custom)?;
+    if let Value::Object(fields) = &mut value {
+        fields.remove(TRACKER_FIELD);
+    }
+    value.serialize(serializer)
+}
+
+fn deserialize_config<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<SlaveConfigModel, D::Error> {
+    let mut value = Value::deserialize(deserializer)?;
+    if let Value::Object(fields) = &mut value {
+        fields.insert(String::from(TRACKER_FIELD), Value::from(0));
+    }
+    serde_json::from_value(value).map_err(D::Error::custom)
+}
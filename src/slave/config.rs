@@ -3,23 +3,28 @@ use std::str::FromStr;
 use relm4::{
     adw::{prelude::*, ActionRow, ComboRow, ExpanderRow, PreferencesGroup},
     gtk::{
-        Align, Box as GtkBox, Entry, Inhibit, Label, Orientation, ScrolledWindow, Separator,
-        SpinButton, StringList, Switch, Viewport,
+        Align, Box as GtkBox, Button, Entry, Inhibit, Label, ListBox, Orientation, ScrolledWindow,
+        SelectionMode, Separator, SpinButton, StringList, Switch, Viewport, INVALID_LIST_POSITION,
     },
-    ComponentParts, RelmWidgetExt, SimpleComponent,
+    ComponentParts, ComponentSender, RelmWidgetExt, SimpleComponent,
 };
+use serde::{Deserialize, Serialize};
 use strum::IntoEnumIterator;
 use url::Url;
 
 use crate::preferences::PreferencesModel;
 
-use super::video_ext::{
-    ColorspaceConversion, VideoAlgorithm, VideoCodec, VideoCodecProvider, VideoDecoder,
-    VideoEncoder,
+use super::{
+    url_ext,
+    video_ext::{
+        ColorspaceConversion, VideoCodec, VideoCodecProvider, VideoContainer, VideoDecoder,
+        VideoDisplayBackend, VideoEncoder,
+    },
+    video_processor::{VideoAlgorithm, VideoAlgorithmPreset},
 };
 
 #[tracker::track]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SlaveConfigModel {
     polling: Option<bool>,
     connected: Option<bool>,
@@ -27,26 +32,53 @@ pub struct SlaveConfigModel {
     pub video_url: Url,
     pub video_algorithms: Vec<VideoAlgorithm>,
     pub keep_video_display_ratio: bool,
+    pub hud_displayed: bool,
+    pub burn_hud: bool,
     pub video_decoder: VideoDecoder,
     pub colorspace_conversion: ColorspaceConversion,
+    pub video_display_backend: VideoDisplayBackend,
+    pub video_container: VideoContainer,
+    pub pre_record_duration: u32,
+    pub recording_notes: String,
     pub swap_xy: bool,
     pub use_decodebin: bool,
     pub video_encoder: VideoEncoder,
     pub reencode_recording_video: bool,
+    pub record_processed_video: bool,
     pub appsink_queue_leaky_enabled: bool,
+    pub appsink_max_buffers: u32,
+    pub appsink_drop: bool,
+    pub auto_restart_pipeline: bool,
+    pub stall_timeout: u32,
     pub video_latency: u32,
 }
 
+pub struct SlaveConfigInit {
+    pub preferences: PreferencesModel,
+    pub index: usize,
+    pub config: Option<SlaveConfigModel>, // 恢复会话时使用保存的配置
+}
+
 #[derive(Debug)]
 pub enum SlaveConfigInput {
     SetVideoUrl(Url),
     SetSlaveUrl(Url),
     SetKeepVideoDisplayRatio(bool),
+    SetHudDisplayed(bool),
+    SetBurnHud(bool),
     SetPolling(Option<bool>),
     SetConnected(Option<bool>),
-    SetVideoAlgorithm(Option<VideoAlgorithm>),
+    AddVideoAlgorithm(VideoAlgorithm),
+    RemoveVideoAlgorithm(usize),
+    MoveVideoAlgorithm(usize, usize),
+    SetVideoAlgorithmParameter(usize, usize, f64),
+    ApplyVideoAlgorithmPreset(Option<VideoAlgorithmPreset>),
     SetVideoDecoder(VideoDecoder),
     SetColorspaceConversion(ColorspaceConversion),
+    SetVideoDisplayBackend(VideoDisplayBackend),
+    SetVideoContainer(VideoContainer),
+    SetPreRecordDuration(u32),
+    SetRecordingNotes(String),
     SetVideoDecoderCodec(VideoCodec),
     SetVideoDecoderCodecProvider(VideoCodecProvider),
     SetSwapXY(bool),
@@ -55,6 +87,11 @@ pub enum SlaveConfigInput {
     SetVideoEncoderCodecProvider(VideoCodecProvider),
     SetReencodeRecordingVideo(bool),
     SetAppSinkQueueLeakyEnabled(bool),
+    SetAppSinkMaxBuffers(u32),
+    SetAppSinkDrop(bool),
+    SetAutoRestartPipeline(bool),
+    SetStallTimeout(u32),
+    SetRecordProcessedVideo(bool),
     SetVideoLatency(u32),
 
     UpdatePreferences(PreferencesModel),
@@ -63,6 +100,7 @@ pub enum SlaveConfigInput {
 #[derive(Debug)]
 pub enum SlaveConfigOutput {
     UpdateConfig(SlaveConfigModel),
+    ShowToastMessage(String),
 }
 
 #[relm4::component(pub)]
@@ -143,23 +181,88 @@ impl SimpleComponent for SlaveConfigModel {
                                 },
                                 set_activatable_widget: Some(&default_keep_video_display_ratio_switch),
                             },
+                            add = &ActionRow {
+                                set_title: "显示 HUD",
+                                set_subtitle: "在画面上叠加航向、深度、锁定状态、录制状态与时间",
+                                add_suffix: hud_displayed_switch = &Switch {
+                                    #[track = "model.changed(SlaveConfigModel::hud_displayed())"]
+                                    set_active: *model.get_hud_displayed(),
+                                    set_valign: Align::Center,
+                                    connect_state_set[sender] => move |_, state| {
+                                        sender.input(SlaveConfigInput::SetHudDisplayed(state));
+                                        Inhibit(false)
+                                    }
+                                },
+                                set_activatable_widget: Some(&hud_displayed_switch),
+                            },
+                            add = &ActionRow {
+                                set_title: "录制与截图时叠加 HUD",
+                                set_subtitle: "将 HUD 绘制到截图中，录制时仅在录制增强后的画面时生效",
+                                add_suffix: burn_hud_switch = &Switch {
+                                    #[track = "model.changed(SlaveConfigModel::burn_hud())"]
+                                    set_active: *model.get_burn_hud(),
+                                    set_valign: Align::Center,
+                                    connect_state_set[sender] => move |_, state| {
+                                        sender.input(SlaveConfigInput::SetBurnHud(state));
+                                        Inhibit(false)
+                                    }
+                                },
+                                set_activatable_widget: Some(&burn_hud_switch),
+                            },
+                        },
+                        append = &PreferencesGroup {
+                            set_title: "增强算法",
+                            set_description: Some("按顺序依次对画面应用的增强算法"),
                             add = &ComboRow {
-                                set_title: "增强算法",
-                                set_subtitle: "对画面使用的增强算法",
+                                set_title: "预设",
+                                set_subtitle: "使用预设的算法组合替换当前算法",
                                 set_model: Some(&{
                                     let model = StringList::new(&[]);
                                     model.append("无");
-                                    for value in VideoAlgorithm::iter() {
+                                    for value in VideoAlgorithmPreset::iter() {
                                         model.append(&value.to_string());
                                     }
+                                    model.append("自定义");
                                     model
                                 }),
                                 #[track = "model.changed(SlaveConfigModel::video_algorithms())"]
-                                set_selected: VideoAlgorithm::iter().position(|x| model.video_algorithms.first().map_or_else(|| false, |y| *y == x)).map_or_else(|| 0, |x| x + 1) as u32,
+                                set_selected: if model.video_algorithms.is_empty() { 0 } else { VideoAlgorithmPreset::iter().position(|x| x.algorithms() == model.video_algorithms).map_or_else(|| VideoAlgorithmPreset::iter().count() + 1, |x| x + 1) } as u32,
                                 connect_selected_notify[sender] => move |row| {
-                                    sender.input(SlaveConfigInput::SetVideoAlgorithm(if row.selected() > 0 { Some(VideoAlgorithm::iter().nth(row.selected().wrapping_sub(1) as usize).unwrap()) } else { None }));
+                                    match row.selected() as usize {
+                                        0 => sender.input(SlaveConfigInput::ApplyVideoAlgorithmPreset(None)),
+                                        index => if let Some(preset) = VideoAlgorithmPreset::iter().nth(index - 1) {
+                                            sender.input(SlaveConfigInput::ApplyVideoAlgorithmPreset(Some(preset)));
+                                        },
+                                    }
                                 }
-                            }
+                            },
+                            add: video_algorithm_list = &ListBox {
+                                set_margin_top: 10,
+                                set_margin_bottom: 10,
+                                set_selection_mode: SelectionMode::None,
+                                add_css_class: "boxed-list",
+                            },
+                            add: new_video_algorithm_row = &ComboRow {
+                                set_title: "添加算法",
+                                set_model: Some(&{
+                                    let model = StringList::new(&[]);
+                                    for value in VideoAlgorithm::all() {
+                                        model.append(&value.to_string());
+                                    }
+                                    model
+                                }),
+                                add_suffix = &Button {
+                                    set_icon_name: "list-add-symbolic",
+                                    set_valign: Align::Center,
+                                    set_tooltip_text: Some("将选中的算法添加到末尾"),
+                                    add_css_class: "flat",
+                                    connect_clicked[sender, new_video_algorithm_row] => move |_| {
+                                        if let Some(algorithm) = VideoAlgorithm::all().nth(new_video_algorithm_row.selected() as usize) {
+                                            sender.input(SlaveConfigInput::AddVideoAlgorithm(algorithm));
+                                        }
+                                    }
+                                },
+                            },
                         },
                         append = &PreferencesGroup {
                             #[track = "model.changed(SlaveConfigModel::polling())"]
@@ -184,6 +287,24 @@ impl SimpleComponent for SlaveConfigModel {
                                     }
                                 },
                             },
+                            add = &ComboRow {
+                                set_title: "显示方式",
+                                set_subtitle: "GTK Paintable 方式跳过图像复制以降低处理器占用，仅在未启用增强算法时生效",
+                                set_model: Some(&{
+                                    let model = StringList::new(&[]);
+                                    for value in VideoDisplayBackend::available() {
+                                        model.append(&value.to_string());
+                                    }
+                                    model
+                                }),
+                                #[track = "model.changed(SlaveConfigModel::video_display_backend())"]
+                                set_selected: VideoDisplayBackend::available().iter().position(|x| *x == model.video_display_backend).map_or(INVALID_LIST_POSITION, |index| index as u32),
+                                connect_selected_notify[sender] => move |row| {
+                                    if let Some(value) = VideoDisplayBackend::available().get(row.selected() as usize) {
+                                        sender.input(SlaveConfigInput::SetVideoDisplayBackend(*value));
+                                    }
+                                }
+                            },
                             add = &ActionRow {
                                 set_title: "启用画面自动跳帧",
                                 set_subtitle: "当机位画面与视频流延迟过大时，自动跳帧以避免延迟提升",
@@ -198,6 +319,68 @@ impl SimpleComponent for SlaveConfigModel {
                                 },
                                 set_activatable_widget: Some(&appsink_queue_leaky_enabled_switch),
                             },
+                            add = &ActionRow {
+                                set_title: "显示缓冲帧数上限",
+                                set_subtitle: "显示端最多缓存的帧数，设置为 0 以不限制",
+                                add_suffix = &SpinButton::with_range(0.0, 60.0, 1.0) {
+                                    #[track = "model.changed(SlaveConfigModel::appsink_max_buffers())"]
+                                    set_value: model.appsink_max_buffers as f64,
+                                    set_digits: 0,
+                                    set_valign: Align::Center,
+                                    set_can_focus: false,
+                                    connect_value_changed[sender] => move |button| {
+                                        sender.input(SlaveConfigInput::SetAppSinkMaxBuffers(button.value() as u32));
+                                    }
+                                },
+                                add_suffix = &Label {
+                                    set_label: "帧",
+                                },
+                            },
+                            add = &ActionRow {
+                                set_title: "缓冲区满时丢弃旧帧",
+                                set_subtitle: "显示缓冲区达到上限时丢弃最旧的帧而不是阻塞解码",
+                                add_suffix: appsink_drop_switch = &Switch {
+                                    #[track = "model.changed(SlaveConfigModel::appsink_drop())"]
+                                    set_active: *model.get_appsink_drop(),
+                                    set_valign: Align::Center,
+                                    connect_state_set[sender] => move |_, state| {
+                                        sender.input(SlaveConfigInput::SetAppSinkDrop(state));
+                                        Inhibit(false)
+                                    }
+                                },
+                                set_activatable_widget: Some(&appsink_drop_switch),
+                            },
+                            add = &ActionRow {
+                                set_title: "出错时自动重启管道",
+                                set_subtitle: "视频流出错或中断后自动尝试重新拉流",
+                                add_suffix: auto_restart_pipeline_switch = &Switch {
+                                    #[track = "model.changed(SlaveConfigModel::auto_restart_pipeline())"]
+                                    set_active: *model.get_auto_restart_pipeline(),
+                                    set_valign: Align::Center,
+                                    connect_state_set[sender] => move |_, state| {
+                                        sender.input(SlaveConfigInput::SetAutoRestartPipeline(state));
+                                        Inhibit(false)
+                                    }
+                                },
+                                set_activatable_widget: Some(&auto_restart_pipeline_switch),
+                            },
+                            add = &ActionRow {
+                                set_title: "画面中断超时",
+                                set_subtitle: "超过该时间没有收到新画面时视为信号丢失并重新拉流，设置为 0 以禁用",
+                                add_suffix = &SpinButton::with_range(0.0, 60000.0, 500.0) {
+                                    #[track = "model.changed(SlaveConfigModel::stall_timeout())"]
+                                    set_value: model.stall_timeout as f64,
+                                    set_digits: 0,
+                                    set_valign: Align::Center,
+                                    set_can_focus: false,
+                                    connect_value_changed[sender] => move |button| {
+                                        sender.input(SlaveConfigInput::SetStallTimeout(button.value() as u32));
+                                    }
+                                },
+                                add_suffix = &Label {
+                                    set_label: "毫秒",
+                                },
+                            },
                             add = &ExpanderRow {
                                 set_title: "手动配置管道",
                                 set_show_enable_switch: true,
@@ -229,15 +412,17 @@ impl SimpleComponent for SlaveConfigModel {
                                     set_subtitle: "设置视频编解码、视频流显示要求的色彩空间转换所使用的硬件",
                                     set_model: Some(&{
                                         let model = StringList::new(&[]);
-                                        for value in ColorspaceConversion::iter() {
+                                        for value in ColorspaceConversion::available() {
                                             model.append(&value.to_string());
                                         }
                                         model
                                     }),
                                     #[track = "model.changed(SlaveConfigModel::colorspace_conversion())"]
-                                    set_selected: ColorspaceConversion::iter().position(|x| x == model.colorspace_conversion).unwrap() as u32,
+                                    set_selected: ColorspaceConversion::available().iter().position(|x| *x == model.colorspace_conversion).map_or(INVALID_LIST_POSITION, |index| index as u32),
                                     connect_selected_notify[sender] => move |row| {
-                                        sender.input(SlaveConfigInput::SetColorspaceConversion(ColorspaceConversion::iter().nth(row.selected() as usize).unwrap()));
+                                        if let Some(value) = ColorspaceConversion::available().get(row.selected() as usize) {
+                                            sender.input(SlaveConfigInput::SetColorspaceConversion(*value));
+                                        }
                                     }
                                 },
                                 add_row = &ComboRow {
@@ -245,34 +430,91 @@ impl SimpleComponent for SlaveConfigModel {
                                     set_subtitle: "解码视频流使用的解码器",
                                     set_model: Some(&{
                                         let model = StringList::new(&[]);
-                                        for value in VideoCodec::iter() {
+                                        for value in VideoCodec::available(false) {
                                             model.append(&value.to_string());
                                         }
                                         model
                                     }),
                                     #[track = "model.changed(SlaveConfigModel::video_decoder())"]
-                                    set_selected: VideoCodec::iter().position(|x| x == model.video_decoder.0).unwrap() as u32,
+                                    set_selected: VideoCodec::available(false).iter().position(|x| *x == model.video_decoder.0).map_or(INVALID_LIST_POSITION, |index| index as u32),
                                     connect_selected_notify[sender] => move |row| {
-                                        sender.input(SlaveConfigInput::SetVideoDecoderCodec(VideoCodec::iter().nth(row.selected() as usize).unwrap()))
+                                        if let Some(value) = VideoCodec::available(false).get(row.selected() as usize) {
+                                            sender.input(SlaveConfigInput::SetVideoDecoderCodec(*value));
+                                        }
                                     }
                                 },
                                 add_row = &ComboRow {
                                     set_title: "解码器接口",
-                                    set_subtitle: "解码视频流使用的解码器接口",
+                                    #[track = "model.changed(SlaveConfigModel::video_decoder())"]
+                                    set_subtitle: &model.video_decoder.missing_element().map_or_else(|| String::from("解码视频流使用的解码器接口"), |name| format!("当前系统缺少元素 {}，请更换解码器接口", name)),
                                     set_model: Some(&{
                                         let model = StringList::new(&[]);
-                                        for value in VideoCodecProvider::iter() {
+                                        for value in VideoCodecProvider::available(false) {
                                             model.append(&value.to_string());
                                         }
                                         model
                                     }),
                                     #[track = "model.changed(SlaveConfigModel::video_decoder())"]
-                                    set_selected: VideoCodecProvider::iter().position(|x| x == model.video_decoder.1).unwrap() as u32,
+                                    set_selected: VideoCodecProvider::available(false).iter().position(|x| *x == model.video_decoder.1).map_or(INVALID_LIST_POSITION, |index| index as u32),
                                     connect_selected_notify[sender] => move |row| {
-                                        sender.input(SlaveConfigInput::SetVideoDecoderCodecProvider(VideoCodecProvider::iter().nth(row.selected() as usize).unwrap()))
+                                        if let Some(value) = VideoCodecProvider::available(false).get(row.selected() as usize) {
+                                            sender.input(SlaveConfigInput::SetVideoDecoderCodecProvider(*value));
+                                        }
                                     },
                                 },
                             },
+                            add = &ComboRow {
+                                set_title: "录制格式",
+                                #[track = "model.changed(SlaveConfigModel::video_container()) || model.changed(SlaveConfigModel::video_decoder()) || model.changed(SlaveConfigModel::video_encoder()) || model.changed(SlaveConfigModel::reencode_recording_video())"]
+                                set_subtitle: &if model.video_container.supports(model.record_codec()) {
+                                    String::from("录制视频使用的封装格式")
+                                } else {
+                                    format!("{} 不支持 {} 编码，请更换录制格式或录制编码器", model.video_container.short_name(), model.record_codec().to_string())
+                                },
+                                set_model: Some(&{
+                                    let model = StringList::new(&[]);
+                                    for value in VideoContainer::available() {
+                                        model.append(&value.to_string());
+                                    }
+                                    model
+                                }),
+                                #[track = "model.changed(SlaveConfigModel::video_container())"]
+                                set_selected: VideoContainer::available().iter().position(|x| *x == model.video_container).map_or(INVALID_LIST_POSITION, |index| index as u32),
+                                connect_selected_notify[sender] => move |row| {
+                                    if let Some(value) = VideoContainer::available().get(row.selected() as usize) {
+                                        sender.input(SlaveConfigInput::SetVideoContainer(*value));
+                                    }
+                                }
+                            },
+                            add = &ActionRow {
+                                set_title: "录制备注",
+                                set_subtitle: "写入录制文件的备注信息，如潜次与作业内容",
+                                add_suffix = &Entry {
+                                    set_text: model.get_recording_notes().as_str(),
+                                    set_valign: Align::Center,
+                                    set_width_request: 160,
+                                    connect_changed[sender] => move |entry| {
+                                        sender.input(SlaveConfigInput::SetRecordingNotes(entry.text().to_string()));
+                                    }
+                                },
+                            },
+                            add = &ActionRow {
+                                set_title: "预录时长",
+                                set_subtitle: "开始录制时一并写入此前缓存的画面，仅在录制时不重新编码且未使用 decodebin 时生效，设置为 0 以禁用，修改后需重新拉流",
+                                add_suffix = &SpinButton::with_range(0.0, 300.0, 5.0) {
+                                    #[track = "model.changed(SlaveConfigModel::pre_record_duration())"]
+                                    set_value: model.pre_record_duration as f64,
+                                    set_digits: 0,
+                                    set_valign: Align::Center,
+                                    set_can_focus: false,
+                                    connect_value_changed[sender] => move |button| {
+                                        sender.input(SlaveConfigInput::SetPreRecordDuration(button.value() as u32));
+                                    }
+                                },
+                                add_suffix = &Label {
+                                    set_label: "秒",
+                                },
+                            },
                             add = &ExpanderRow {
                                 set_title: "录制时重新编码",
                                 set_show_enable_switch: true,
@@ -287,33 +529,52 @@ impl SimpleComponent for SlaveConfigModel {
                                     set_subtitle: "视频录制时使用的编码器",
                                     set_model: Some(&{
                                         let model = StringList::new(&[]);
-                                        for value in VideoCodec::iter() {
+                                        for value in VideoCodec::available(true) {
                                             model.append(&value.to_string());
                                         }
                                         model
                                     }),
                                     #[track = "model.changed(SlaveConfigModel::video_encoder())"]
-                                    set_selected: VideoCodec::iter().position(|x| x == model.video_encoder.0).unwrap() as u32,
+                                    set_selected: VideoCodec::available(true).iter().position(|x| *x == model.video_encoder.0).map_or(INVALID_LIST_POSITION, |index| index as u32),
                                     connect_selected_notify[sender] => move |row| {
-                                        sender.input(SlaveConfigInput::SetVideoEncoderCodec(VideoCodec::iter().nth(row.selected() as usize).unwrap()))
+                                        if let Some(value) = VideoCodec::available(true).get(row.selected() as usize) {
+                                            sender.input(SlaveConfigInput::SetVideoEncoderCodec(*value));
+                                        }
                                     }
                                 },
                                 add_row = &ComboRow {
                                     set_title: "编码器接口",
-                                    set_subtitle: "视频录制时调用的编码器接口",
+                                    #[track = "model.changed(SlaveConfigModel::video_encoder())"]
+                                    set_subtitle: &model.video_encoder.missing_element().map_or_else(|| String::from("视频录制时调用的编码器接口"), |name| format!("当前系统缺少元素 {}，请更换编码器接口", name)),
                                     set_model: Some(&{
                                         let model = StringList::new(&[]);
-                                        for value in VideoCodecProvider::iter() {
+                                        for value in VideoCodecProvider::available(true) {
                                             model.append(&value.to_string());
                                         }
                                         model
                                     }),
                                     #[track = "model.changed(SlaveConfigModel::video_encoder())"]
-                                    set_selected:  VideoCodecProvider::iter().position(|x| x == model.video_encoder.1).unwrap() as u32,
+                                    set_selected: VideoCodecProvider::available(true).iter().position(|x| *x == model.video_encoder.1).map_or(INVALID_LIST_POSITION, |index| index as u32),
                                     connect_selected_notify[sender] => move |row| {
-                                        sender.input(SlaveConfigInput::SetVideoEncoderCodecProvider(VideoCodecProvider::iter().nth(row.selected() as usize).unwrap()))
+                                        if let Some(value) = VideoCodecProvider::available(true).get(row.selected() as usize) {
+                                            sender.input(SlaveConfigInput::SetVideoEncoderCodecProvider(*value));
+                                        }
                                     }
                                 },
+                                add_row = &ActionRow {
+                                    set_title: "录制增强后的画面",
+                                    set_subtitle: "录制经过增强算法处理后显示的画面，而非原始视频流",
+                                    add_suffix: record_processed_video_switch = &Switch {
+                                        #[track = "model.changed(SlaveConfigModel::record_processed_video())"]
+                                        set_active: *model.get_record_processed_video(),
+                                        set_valign: Align::Center,
+                                        connect_state_set[sender] => move |_, state| {
+                                            sender.input(SlaveConfigInput::SetRecordProcessedVideo(state));
+                                            Inhibit(false)
+                                        }
+                                    },
+                                    set_activatable_widget: Some(&record_processed_video_switch),
+                                },
                             },
                         },
                     },
@@ -322,36 +583,97 @@ impl SimpleComponent for SlaveConfigModel {
         }
     }
 
-    type Init = PreferencesModel;
+    type Init = SlaveConfigInit;
     type Input = SlaveConfigInput;
     type Output = SlaveConfigOutput;
 
     fn init(
-        preference: Self::Init,
+        init: Self::Init,
         root: &Self::Root,
         sender: relm4::ComponentSender<Self>,
     ) -> relm4::ComponentParts<Self> {
-        let model = SlaveConfigModel {
-            polling: Some(false),
-            connected: Some(false),
-            swap_xy: false,
-            video_algorithms: Vec::new(),
-            slave_url: preference.default_slave_url.clone(),
-            video_url: preference.default_video_url.clone(),
-            keep_video_display_ratio: preference.default_keep_video_display_ratio,
-            video_decoder: preference.default_video_decoder.clone(),
-            colorspace_conversion: preference.default_colorspace_conversion,
-            use_decodebin: preference.default_use_decodebin,
-            video_encoder: preference.default_video_encoder.clone(),
-            reencode_recording_video: preference.default_reencode_recording_video,
-            appsink_queue_leaky_enabled: preference.default_appsink_queue_leaky_enabled,
-            video_latency: preference.default_video_latency,
-            tracker: 0,
+        let preference = init.preferences;
+        let model = match init.config {
+            // 恢复的配置不保留连接状态
+            Some(config) => SlaveConfigModel {
+                polling: Some(false),
+                connected: Some(false),
+                tracker: 0,
+                ..config
+            },
+            None => {
+                let slave_url = url_ext::slave_url(
+                    &preference.default_slave_url,
+                    init.index,
+                    preference.slave_url_stride,
+                );
+                let video_url = url_ext::video_url(
+                    &preference.default_video_url,
+                    init.index,
+                    preference.video_url_port_stride,
+                );
+                // 无法按间隔生成地址时沿用默认地址，并提示用户手动修改以免与其他机位重复
+                let failed: Vec<&str> = [
+                    ("连接 URL", slave_url.is_none()),
+                    ("拉流 URL", video_url.is_none()),
+                ]
+                .into_iter()
+                .filter_map(|(name, failed)| failed.then_some(name))
+                .collect();
+                if !failed.is_empty() {
+                    sender
+                        .output(SlaveConfigOutput::ShowToastMessage(format!(
+                            "无法按设定间隔为机位 {} 生成{}，已沿用默认地址，请手动修改。",
+                            init.index + 1,
+                            failed.join("与")
+                        )))
+                        .unwrap();
+                }
+                SlaveConfigModel {
+                    polling: Some(false),
+                    connected: Some(false),
+                    swap_xy: false,
+                    video_algorithms: Vec::new(),
+                    slave_url: slave_url.unwrap_or_else(|| preference.default_slave_url.clone()),
+                    video_url: video_url.unwrap_or_else(|| preference.default_video_url.clone()),
+                    keep_video_display_ratio: preference.default_keep_video_display_ratio,
+                    hud_displayed: preference.default_hud_displayed,
+                    burn_hud: preference.default_burn_hud,
+                    video_decoder: preference.default_video_decoder.clone(),
+                    colorspace_conversion: preference.default_colorspace_conversion,
+                    video_display_backend: preference.default_video_display_backend,
+                    video_container: preference.default_video_container,
+                    pre_record_duration: preference.default_pre_record_duration,
+                    recording_notes: String::new(),
+                    use_decodebin: preference.default_use_decodebin,
+                    video_encoder: preference.default_video_encoder.clone(),
+                    reencode_recording_video: preference.default_reencode_recording_video,
+                    record_processed_video: preference.default_record_processed_video,
+                    appsink_queue_leaky_enabled: preference.default_appsink_queue_leaky_enabled,
+                    appsink_max_buffers: preference.default_appsink_max_buffers,
+                    appsink_drop: preference.default_appsink_drop,
+                    auto_restart_pipeline: preference.default_auto_restart_pipeline,
+                    stall_timeout: preference.default_stall_timeout,
+                    video_latency: preference.default_video_latency,
+                    tracker: 0,
+                }
+            }
         };
         let widgets = view_output!();
+        rebuild_video_algorithm_list(
+            &widgets.video_algorithm_list,
+            &model.video_algorithms,
+            &sender,
+        );
         ComponentParts { model, widgets }
     }
 
+    fn post_view() {
+        if model.changed(SlaveConfigModel::video_algorithms()) {
+            rebuild_video_algorithm_list(video_algorithm_list, &model.video_algorithms, &sender);
+        }
+    }
+
     fn update(&mut self, message: Self::Input, sender: relm4::ComponentSender<Self>) {
         self.reset();
 
@@ -362,20 +684,54 @@ impl SimpleComponent for SlaveConfigModel {
                 self.video_latency = preference.default_video_latency;
             }
             SetKeepVideoDisplayRatio(value) => self.set_keep_video_display_ratio(value),
+            SetHudDisplayed(value) => self.set_hud_displayed(value),
+            SetBurnHud(value) => self.set_burn_hud(value),
             SetPolling(polling) => self.set_polling(polling),
             SetConnected(connected) => self.set_connected(connected),
-            SetVideoAlgorithm(algorithm) => {
-                self.get_mut_video_algorithms().clear();
-                if let Some(algorithm) = algorithm {
-                    self.get_mut_video_algorithms().push(algorithm);
+            AddVideoAlgorithm(algorithm) => self.get_mut_video_algorithms().push(algorithm),
+            RemoveVideoAlgorithm(index) => {
+                if index < self.video_algorithms.len() {
+                    self.get_mut_video_algorithms().remove(index);
+                }
+            }
+            MoveVideoAlgorithm(from, to) => {
+                if from < self.video_algorithms.len() && to < self.video_algorithms.len() {
+                    let algorithm = self.get_mut_video_algorithms().remove(from);
+                    self.get_mut_video_algorithms().insert(to, algorithm);
                 }
             }
+            SetVideoAlgorithmParameter(index, parameter, value) => {
+                // 不标记改变，避免重建列表导致正在编辑的输入框失去焦点
+                if let Some(algorithm) = self.video_algorithms.get_mut(index) {
+                    algorithm.set_parameter(parameter, value);
+                }
+            }
+            ApplyVideoAlgorithmPreset(preset) => self
+                .set_video_algorithms(preset.map_or_else(Vec::new, |preset| preset.algorithms())),
             SetVideoDecoder(decoder) => self.set_video_decoder(decoder),
             SetColorspaceConversion(conversion) => self.set_colorspace_conversion(conversion),
+            SetVideoDisplayBackend(backend) => self.set_video_display_backend(backend),
+            SetVideoContainer(container) => self.set_video_container(container),
+            SetPreRecordDuration(duration) => self.set_pre_record_duration(duration),
+            SetRecordingNotes(notes) => self.recording_notes = notes, // 防止光标移动到最前面
             SetVideoUrl(url) => self.video_url = url,
             SetSlaveUrl(url) => self.slave_url = url,
-            SetVideoDecoderCodec(codec) => self.get_mut_video_decoder().0 = codec,
-            SetVideoDecoderCodecProvider(provider) => self.get_mut_video_decoder().1 = provider,
+            SetVideoDecoderCodec(codec) => {
+                let decoder = self.get_mut_video_decoder();
+                decoder.0 = codec;
+                if !decoder.1.is_available(codec, false) {
+                    if let Some(provider) = VideoCodecProvider::best_available(codec, false) {
+                        decoder.1 = provider;
+                    }
+                }
+            }
+            SetVideoDecoderCodecProvider(provider) => {
+                // 接口不支持当前格式时保留原选择，get_mut 使下拉框恢复显示原接口
+                let decoder = self.get_mut_video_decoder();
+                if provider.is_available(decoder.0, false) {
+                    decoder.1 = provider;
+                }
+            }
             SetSwapXY(swap) => self.set_swap_xy(swap),
             SetUsePlaybin(use_decodebin) => {
                 if use_decodebin {
@@ -383,8 +739,22 @@ impl SimpleComponent for SlaveConfigModel {
                 }
                 self.set_use_decodebin(use_decodebin);
             }
-            SetVideoEncoderCodec(codec) => self.get_mut_video_encoder().0 = codec,
-            SetVideoEncoderCodecProvider(provider) => self.get_mut_video_encoder().1 = provider,
+            SetVideoEncoderCodec(codec) => {
+                let encoder = self.get_mut_video_encoder();
+                encoder.0 = codec;
+                if !encoder.1.is_available(codec, true) {
+                    if let Some(provider) = VideoCodecProvider::best_available(codec, true) {
+                        encoder.1 = provider;
+                    }
+                }
+            }
+            SetVideoEncoderCodecProvider(provider) => {
+                // 接口不支持当前格式时保留原选择，get_mut 使下拉框恢复显示原接口
+                let encoder = self.get_mut_video_encoder();
+                if provider.is_available(encoder.0, true) {
+                    encoder.1 = provider;
+                }
+            }
             SetReencodeRecordingVideo(reencode) => {
                 if !reencode {
                     self.set_use_decodebin(false);
@@ -392,6 +762,11 @@ impl SimpleComponent for SlaveConfigModel {
                 self.set_reencode_recording_video(reencode)
             }
             SetAppSinkQueueLeakyEnabled(leaky) => self.set_appsink_queue_leaky_enabled(leaky),
+            SetAppSinkMaxBuffers(max_buffers) => self.set_appsink_max_buffers(max_buffers),
+            SetAppSinkDrop(drop) => self.set_appsink_drop(drop),
+            SetAutoRestartPipeline(restart) => self.set_auto_restart_pipeline(restart),
+            SetStallTimeout(timeout) => self.set_stall_timeout(timeout),
+            SetRecordProcessedVideo(processed) => self.set_record_processed_video(processed),
             SetVideoLatency(latency) => self.set_video_latency(latency),
         }
 
@@ -400,3 +775,80 @@ impl SimpleComponent for SlaveConfigModel {
             .unwrap()
     }
 }
+
+impl SlaveConfigModel {
+    // 录制文件中视频流的编码，不重新编码时与拉流的编码相同
+    pub fn record_codec(&self) -> VideoCodec {
+        if self.reencode_recording_video {
+            self.video_encoder.0
+        } else {
+            self.video_decoder.0
+        }
+    }
+}
+
+fn rebuild_video_algorithm_list(
+    list: &ListBox,
+    algorithms: &[VideoAlgorithm],
+    sender: &ComponentSender<SlaveConfigModel>,
+) {
+    while let Some(row) = list.first_child() {
+        list.remove(&row);
+    }
+    list.set_visible(!algorithms.is_empty());
+    for (index, algorithm) in algorithms.iter().enumerate() {
+        let row = ExpanderRow::new();
+        row.set_title(&format!("{}. {}", index + 1, algorithm.to_string()));
+        let buttons = [
+            ("go-up-symbolic", "上移", index > 0, index.wrapping_sub(1)),
+            (
+                "go-down-symbolic",
+                "下移",
+                index + 1 < algorithms.len(),
+                index + 1,
+            ),
+        ];
+        for (icon_name, tooltip, sensitive, target) in buttons {
+            let button = Button::from_icon_name(icon_name);
+            button.set_valign(Align::Center);
+            button.set_tooltip_text(Some(tooltip));
+            button.set_sensitive(sensitive);
+            button.add_css_class("flat");
+            let sender = sender.clone();
+            button.connect_clicked(move |_| {
+                sender.input(SlaveConfigInput::MoveVideoAlgorithm(index, target));
+            });
+            row.add_action(&button);
+        }
+        let remove_button = Button::from_icon_name("list-remove-symbolic");
+        remove_button.set_valign(Align::Center);
+        remove_button.set_tooltip_text(Some("移除"));
+        remove_button.add_css_class("flat");
+        {
+            let sender = sender.clone();
+            remove_button.connect_clicked(move |_| {
+                sender.input(SlaveConfigInput::RemoveVideoAlgorithm(index));
+            });
+        }
+        row.add_action(&remove_button);
+        for (parameter_index, parameter) in algorithm.parameters().into_iter().enumerate() {
+            let parameter_row = ActionRow::new();
+            parameter_row.set_title(parameter.name);
+            let spin_button = SpinButton::with_range(parameter.min, parameter.max, parameter.step);
+            spin_button.set_digits(parameter.digits);
+            spin_button.set_value(parameter.value);
+            spin_button.set_valign(Align::Center);
+            let sender = sender.clone();
+            spin_button.connect_value_changed(move |button| {
+                sender.input(SlaveConfigInput::SetVideoAlgorithmParameter(
+                    index,
+                    parameter_index,
+                    button.value(),
+                ));
+            });
+            parameter_row.add_suffix(&spin_button);
+            row.add_row(&parameter_row);
+        }
+        list.append(&row);
+    }
+}
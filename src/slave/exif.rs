@@ -0,0 +1,332 @@
+use super::screenshot::ScreenshotMetadata;
+
+// EXIF 采用 TIFF 的目录（IFD）结构：JPEG 将其放入 APP1 段，
+// TIFF 文件则直接在第一个 IFD 中追加标签
+
+const TYPE_ASCII: u16 = 2;
+const TYPE_LONG: u16 = 4;
+const TYPE_UNDEFINED: u16 = 7;
+
+const TAG_DOCUMENT_NAME: u16 = 0x010d;
+const TAG_IMAGE_DESCRIPTION: u16 = 0x010e;
+const TAG_DATE_TIME: u16 = 0x0132;
+const TAG_EXIF_IFD: u16 = 0x8769;
+const TAG_DATE_TIME_ORIGINAL: u16 = 0x9003;
+const TAG_USER_COMMENT: u16 = 0x9286;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ByteOrder {
+    Little,
+    Big,
+}
+
+impl ByteOrder {
+    fn u16(self, bytes: &[u8]) -> Option<u16> {
+        let bytes = bytes.get(0..2)?.try_into().ok()?;
+        Some(match self {
+            ByteOrder::Little => u16::from_le_bytes(bytes),
+            ByteOrder::Big => u16::from_be_bytes(bytes),
+        })
+    }
+
+    fn u32(self, bytes: &[u8]) -> Option<u32> {
+        let bytes = bytes.get(0..4)?.try_into().ok()?;
+        Some(match self {
+            ByteOrder::Little => u32::from_le_bytes(bytes),
+            ByteOrder::Big => u32::from_be_bytes(bytes),
+        })
+    }
+
+    fn u16_bytes(self, value: u16) -> [u8; 2] {
+        match self {
+            ByteOrder::Little => value.to_le_bytes(),
+            ByteOrder::Big => value.to_be_bytes(),
+        }
+    }
+
+    fn u32_bytes(self, value: u32) -> [u8; 4] {
+        match self {
+            ByteOrder::Little => value.to_le_bytes(),
+            ByteOrder::Big => value.to_be_bytes(),
+        }
+    }
+}
+
+// 不超过 4 字节的值直接存放在目录项中，否则存放在目录之后的数据区
+#[derive(Debug, Clone)]
+struct Entry {
+    tag: u16,
+    type_: u16,
+    count: u32,
+    value: Vec<u8>,
+}
+
+impl Entry {
+    fn ascii(tag: u16, text: &str) -> Self {
+        let mut value = text.as_bytes().to_vec();
+        value.push(0);
+        Self {
+            tag,
+            type_: TYPE_ASCII,
+            count: value.len() as u32,
+            value,
+        }
+    }
+
+    fn long(order: ByteOrder, tag: u16, value: u32) -> Self {
+        Self {
+            tag,
+            type_: TYPE_LONG,
+            count: 1,
+            value: order.u32_bytes(value).to_vec(),
+        }
+    }
+
+    fn undefined(tag: u16, value: Vec<u8>) -> Self {
+        Self {
+            tag,
+            type_: TYPE_UNDEFINED,
+            count: value.len() as u32,
+            value,
+        }
+    }
+}
+
+// 在 JPEG 的 SOI 与 JFIF 段之后插入包含截图信息的 APP1 段
+pub fn add_to_jpeg(jpeg: &[u8], metadata: &ScreenshotMetadata) -> Option<Vec<u8>> {
+    if jpeg.get(0..2)? != [0xff, 0xd8] {
+        return None;
+    }
+    let mut position = 2;
+    if jpeg.get(2..4)? == [0xff, 0xe0] {
+        position += 2 + ByteOrder::Big.u16(jpeg.get(4..6)?)? as usize;
+        if position > jpeg.len() {
+            return None;
+        }
+    }
+    let order = ByteOrder::Little;
+    let mut tiff = b"II*\0".to_vec();
+    tiff.extend(order.u32_bytes(8));
+    tiff.extend(metadata_ifds(order, Vec::new(), metadata, 8, 0));
+    let length = u16::try_from(2 + 6 + tiff.len()).ok()?;
+    let mut result = Vec::with_capacity(jpeg.len() + length as usize + 2);
+    result.extend(&jpeg[..position]);
+    result.extend([0xff, 0xe1]);
+    result.extend(length.to_be_bytes());
+    result.extend(b"Exif\0\0");
+    result.extend(tiff);
+    result.extend(&jpeg[position..]);
+    Some(result)
+}
+
+// 在 TIFF 文件末尾写入合并了截图信息的新 IFD，并让文件头指向它；
+// 原目录项引用的数据保持原位，因此可以直接复制
+pub fn add_to_tiff(tiff: &mut Vec<u8>, metadata: &ScreenshotMetadata) -> Option<()> {
+    let order = match tiff.get(0..2)? {
+        b"II" => ByteOrder::Little,
+        b"MM" => ByteOrder::Big,
+        _ => return None,
+    };
+    let first = order.u32(tiff.get(4..8)?)?;
+    let (entries, next) = read_ifd(tiff, order, first)?;
+    if tiff.len() % 2 == 1 {
+        tiff.push(0);
+    }
+    let offset = u32::try_from(tiff.len()).ok()?;
+    let ifds = metadata_ifds(order, entries, metadata, offset, next);
+    tiff.extend(ifds);
+    tiff[4..8].copy_from_slice(&order.u32_bytes(offset));
+    Some(())
+}
+
+fn read_ifd(tiff: &[u8], order: ByteOrder, offset: u32) -> Option<(Vec<Entry>, u32)> {
+    let offset = offset as usize;
+    let count = order.u16(tiff.get(offset..)?)? as usize;
+    let entries = (0..count)
+        .map(|index| {
+            let entry = tiff.get(offset + 2 + 12 * index..offset + 14 + 12 * index)?;
+            Some(Entry {
+                tag: order.u16(&entry[0..2])?,
+                type_: order.u16(&entry[2..4])?,
+                count: order.u32(&entry[4..8])?,
+                value: entry[8..12].to_vec(),
+            })
+        })
+        .collect::<Option<Vec<_>>>()?;
+    let next = order.u32(tiff.get(offset + 2 + 12 * count..)?)?;
+    Some((entries, next))
+}
+
+// 生成 IFD0 及其指向的 EXIF IFD，offset 为 IFD0 在 TIFF 数据中的位置
+fn metadata_ifds(
+    order: ByteOrder,
+    mut entries: Vec<Entry>,
+    metadata: &ScreenshotMetadata,
+    offset: u32,
+    next: u32,
+) -> Vec<u8> {
+    let tags = [
+        TAG_DOCUMENT_NAME,
+        TAG_IMAGE_DESCRIPTION,
+        TAG_DATE_TIME,
+        TAG_EXIF_IFD,
+    ];
+    entries.retain(|entry| !tags.contains(&entry.tag));
+    entries.push(Entry::ascii(TAG_DOCUMENT_NAME, &metadata.video_url));
+    entries.push(Entry::ascii(TAG_IMAGE_DESCRIPTION, &metadata.slave));
+    let mut exif_entries = Vec::new();
+    if let Some(date_time) = exif_date_time(&metadata.timestamp) {
+        entries.push(Entry::ascii(TAG_DATE_TIME, &date_time));
+        exif_entries.push(Entry::ascii(TAG_DATE_TIME_ORIGINAL, &date_time));
+    }
+    // 状态信息以 JSON 写入用户注释，前 8 字节为字符编码标识
+    let mut comment = b"ASCII\0\0\0".to_vec();
+    comment.extend(
+        serde_json::to_string(&metadata.infos)
+            .unwrap_or_default()
+            .bytes(),
+    );
+    exif_entries.push(Entry::undefined(TAG_USER_COMMENT, comment));
+    entries.push(Entry::long(order, TAG_EXIF_IFD, 0));
+    // IFD0 的长度与 EXIF IFD 的位置无关，先计算长度再填入指针
+    let length = write_ifd(order, entries.clone(), offset, next).len() as u32;
+    if let Some(pointer) = entries.last_mut() {
+        pointer.value = order.u32_bytes(offset + length).to_vec();
+    }
+    let mut ifds = write_ifd(order, entries, offset, next);
+    ifds.extend(write_ifd(order, exif_entries, offset + length, 0));
+    ifds
+}
+
+fn write_ifd(order: ByteOrder, mut entries: Vec<Entry>, offset: u32, next: u32) -> Vec<u8> {
+    entries.sort_by_key(|entry| entry.tag);
+    let data_offset = offset + 2 + 12 * entries.len() as u32 + 4;
+    let mut ifd = order.u16_bytes(entries.len() as u16).to_vec();
+    let mut data = Vec::new();
+    for entry in entries {
+        ifd.extend(order.u16_bytes(entry.tag));
+        ifd.extend(order.u16_bytes(entry.type_));
+        ifd.extend(order.u32_bytes(entry.count));
+        if entry.value.len() <= 4 {
+            let mut value = entry.value;
+            value.resize(4, 0);
+            ifd.extend(value);
+        } else {
+            ifd.extend(order.u32_bytes(data_offset + data.len() as u32));
+            data.extend(entry.value);
+            // 数据需要按字对齐
+            if data.len() % 2 == 1 {
+                data.push(0);
+            }
+        }
+    }
+    ifd.extend(order.u32_bytes(next));
+    ifd.extend(data);
+    ifd
+}
+
+// 将 ISO 8601 时间转换为 EXIF 使用的 "YYYY:MM:DD HH:MM:SS" 格式
+fn exif_date_time(timestamp: &str) -> Option<String> {
+    let date = timestamp.get(0..10)?;
+    let time = timestamp.get(11..19)?;
+    Some(format!("{} {}", date.replace('-', ":"), time))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    fn metadata() -> ScreenshotMetadata {
+        ScreenshotMetadata {
+            slave: String::from("机位 1"),
+            timestamp: String::from("2023-05-06T07:08:09.123456+08"),
+            video_url: String::from("rtsp://192.168.137.123:8554/video"),
+            infos: BTreeMap::from([(String::from("depth"), String::from("12.5"))]),
+        }
+    }
+
+    fn value<'a>(tiff: &'a [u8], order: ByteOrder, entry: &'a Entry) -> &'a [u8] {
+        if entry.count <= 4 {
+            &entry.value[..entry.count as usize]
+        } else {
+            let offset = order.u32(&entry.value).unwrap() as usize;
+            &tiff[offset..offset + entry.count as usize]
+        }
+    }
+
+    fn find(entries: &[Entry], tag: u16) -> &Entry {
+        entries.iter().find(|entry| entry.tag == tag).unwrap()
+    }
+
+    // 检查 TIFF 数据中的 IFD0 与 EXIF IFD 是否包含截图信息
+    fn check_metadata(tiff: &[u8], order: ByteOrder) -> Vec<Entry> {
+        let (entries, _) = read_ifd(tiff, order, order.u32(&tiff[4..8]).unwrap()).unwrap();
+        let description = find(&entries, TAG_IMAGE_DESCRIPTION);
+        assert_eq!(value(tiff, order, description), "机位 1\0".as_bytes());
+        let date_time = find(&entries, TAG_DATE_TIME);
+        assert_eq!(value(tiff, order, date_time), b"2023:05:06 07:08:09\0");
+        let pointer = order.u32(&find(&entries, TAG_EXIF_IFD).value).unwrap();
+        let (exif_entries, _) = read_ifd(tiff, order, pointer).unwrap();
+        let comment = find(&exif_entries, TAG_USER_COMMENT);
+        assert_eq!(
+            value(tiff, order, comment),
+            b"ASCII\0\0\0{\"depth\":\"12.5\"}"
+        );
+        entries
+    }
+
+    #[test]
+    fn converts_iso8601_to_exif_date_time() {
+        assert_eq!(
+            exif_date_time("2023-05-06T07:08:09.123456+08").as_deref(),
+            Some("2023:05:06 07:08:09")
+        );
+        assert_eq!(exif_date_time(""), None);
+    }
+
+    #[test]
+    fn inserts_app1_after_jfif_segment() {
+        let mut jpeg = vec![0xff, 0xd8, 0xff, 0xe0, 0x00, 0x04, 0x4a, 0x46];
+        jpeg.extend([0xff, 0xd9]);
+        let result = add_to_jpeg(&jpeg, &metadata()).unwrap();
+        assert_eq!(&result[..8], &jpeg[..8]);
+        assert_eq!(&result[8..10], &[0xff, 0xe1]);
+        let length = ByteOrder::Big.u16(&result[10..12]).unwrap() as usize;
+        assert_eq!(&result[12..18], b"Exif\0\0");
+        assert_eq!(&result[10 + length..], &[0xff, 0xd9]);
+        check_metadata(&result[18..10 + length], ByteOrder::Little);
+    }
+
+    #[test]
+    fn rejects_data_that_is_not_jpeg() {
+        assert!(add_to_jpeg(&[0x89, 0x50, 0x4e, 0x47], &metadata()).is_none());
+    }
+
+    #[test]
+    fn keeps_existing_tiff_entries() {
+        // 只有一个 ImageWidth 目录项的大端 TIFF
+        let order = ByteOrder::Big;
+        let mut tiff = b"MM\0*".to_vec();
+        tiff.extend(order.u32_bytes(8));
+        tiff.extend(write_ifd(
+            order,
+            vec![Entry {
+                tag: 0x0100,
+                type_: 3,
+                count: 1,
+                value: vec![0x00, 0x40, 0x00, 0x00],
+            }],
+            8,
+            0,
+        ));
+        add_to_tiff(&mut tiff, &metadata()).unwrap();
+        let entries = check_metadata(&tiff, order);
+        assert_eq!(find(&entries, 0x0100).value, vec![0x00, 0x40, 0x00, 0x00]);
+        let tags: Vec<u16> = entries.iter().map(|entry| entry.tag).collect();
+        let mut sorted = tags.clone();
+        sorted.sort();
+        assert_eq!(tags, sorted);
+    }
+}
@@ -0,0 +1,40 @@
+use std::fmt::Display;
+
+use opencv as cv;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FrameError {
+    OpenCV(String),
+    MissingCaps,
+    UnsupportedFormat(String),
+    InvalidBuffer { expected: usize, actual: usize },
+    PixbufAllocation(i32, i32),
+}
+
+impl Display for FrameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FrameError::OpenCV(message) => write!(f, "画面处理失败：{}", message),
+            FrameError::MissingCaps => write!(f, "尚未收到视频格式信息，无法解析画面"),
+            FrameError::UnsupportedFormat(format) => {
+                write!(f, "不支持的画面格式 {}", format)
+            }
+            FrameError::InvalidBuffer { expected, actual } => write!(
+                f,
+                "画面数据大小异常，应至少为 {} 字节，实际为 {} 字节",
+                expected, actual
+            ),
+            FrameError::PixbufAllocation(width, height) => {
+                write!(f, "无法为 {}x{} 的画面分配显示缓冲区", width, height)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FrameError {}
+
+impl From<cv::Error> for FrameError {
+    fn from(error: cv::Error) -> Self {
+        FrameError::OpenCV(error.message)
+    }
+}
@@ -0,0 +1,82 @@
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Condvar, Mutex,
+};
+
+// 只保留最新一帧的信箱，接收方来不及取走的旧帧会被直接覆盖并计入丢帧数
+#[derive(Debug)]
+pub struct FrameMailbox<T> {
+    slot: Mutex<Option<T>>,
+    available: Condvar,
+    closed: AtomicBool,
+    delivered: AtomicU64,
+    dropped: AtomicU64,
+}
+
+impl<T> FrameMailbox<T> {
+    pub fn new() -> Self {
+        Self {
+            slot: Mutex::new(None),
+            available: Condvar::new(),
+            closed: AtomicBool::new(false),
+            delivered: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    // 返回值表示信箱此前是否为空，只有此时才需要唤醒接收方
+    pub fn post(&self, frame: T) -> bool {
+        let previous = self.slot.lock().unwrap().replace(frame);
+        self.available.notify_one();
+        match previous {
+            Some(_) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                false
+            }
+            None => true,
+        }
+    }
+
+    pub fn take(&self) -> Option<T> {
+        let frame = self.slot.lock().unwrap().take();
+        if frame.is_some() {
+            self.delivered.fetch_add(1, Ordering::Relaxed);
+        }
+        frame
+    }
+
+    // 阻塞等待下一帧，信箱关闭后返回 None
+    pub fn wait_take(&self) -> Option<T> {
+        let mut slot = self.slot.lock().unwrap();
+        loop {
+            if self.closed.load(Ordering::Acquire) {
+                return None;
+            }
+            if let Some(frame) = slot.take() {
+                self.delivered.fetch_add(1, Ordering::Relaxed);
+                return Some(frame);
+            }
+            slot = self.available.wait(slot).unwrap();
+        }
+    }
+
+    pub fn close(&self) {
+        let _slot = self.slot.lock().unwrap();
+        self.closed.store(true, Ordering::Release);
+        self.available.notify_all();
+    }
+
+    pub fn delivered(&self) -> u64 {
+        self.delivered.load(Ordering::Relaxed)
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl<T> Default for FrameMailbox<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
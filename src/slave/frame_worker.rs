@@ -0,0 +1,174 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use opencv::core::Mat;
+use opencv::prelude::*;
+use relm4::gtk::glib::Sender;
+
+use super::{
+    frame_error::FrameError,
+    frame_mailbox::FrameMailbox,
+    hud::{burn_hud, current_clock, HudData},
+    video_ext::push_mat_to_appsrc,
+    video_processor::FrameProcessorChain,
+};
+
+// 在独立线程中运行增强算法，避免阻塞 GStreamer 的流线程；
+// 输入信箱只保留最新一帧，处理速度跟不上时直接丢弃旧帧。
+// 处理链由工作线程独占，界面线程只投递新的处理链，不会等待正在处理的帧
+#[derive(Debug)]
+pub struct FrameWorker {
+    input: Arc<FrameMailbox<Result<Mat, FrameError>>>,
+    pending_processor: Arc<Mutex<Option<FrameProcessorChain>>>,
+    processing_time: Arc<Mutex<Option<Duration>>>,
+    recorder: Arc<Mutex<Option<gst_app::AppSrc>>>,
+    hud: Arc<Mutex<Option<HudData>>>,
+    keep_raw: Arc<AtomicBool>,
+    raw_frame: Arc<Mutex<Option<Mat>>>,
+}
+
+impl FrameWorker {
+    pub fn spawn(
+        mut processor: FrameProcessorChain,
+        output: Arc<FrameMailbox<Mat>>,
+        waker: Sender<()>,
+        errors: Sender<FrameError>,
+    ) -> Self {
+        let input = Arc::new(FrameMailbox::new());
+        let pending_processor: Arc<Mutex<Option<FrameProcessorChain>>> = Arc::new(Mutex::new(None));
+        let processing_time = Arc::new(Mutex::new(None));
+        let recorder: Arc<Mutex<Option<gst_app::AppSrc>>> = Arc::new(Mutex::new(None));
+        let hud: Arc<Mutex<Option<HudData>>> = Arc::new(Mutex::new(None));
+        let keep_raw = Arc::new(AtomicBool::new(false));
+        let raw_frame: Arc<Mutex<Option<Mat>>> = Arc::new(Mutex::new(None));
+        std::thread::Builder::new()
+            .name(String::from("frame-worker"))
+            .spawn({
+                let input = input.clone();
+                let pending_processor = pending_processor.clone();
+                let processing_time = processing_time.clone();
+                let recorder = recorder.clone();
+                let hud = hud.clone();
+                let keep_raw = keep_raw.clone();
+                let raw_frame = raw_frame.clone();
+                move || {
+                    let mut last_error: Option<FrameError> = None;
+                    while let Some(frame) = input.wait_take() {
+                        if let Some(pending) = pending_processor.lock().unwrap().take() {
+                            processor = pending;
+                        }
+                        let start = Instant::now();
+                        // 保留处理前的原始画面，供截图时一并保存
+                        if keep_raw.load(Ordering::Relaxed) {
+                            if let Ok(frame) = &frame {
+                                *raw_frame.lock().unwrap() = frame.try_clone().ok();
+                            }
+                        }
+                        match frame.and_then(|frame| processor.process(frame)) {
+                            Ok(frame) => {
+                                let elapsed = start.elapsed();
+                                let mut average = processing_time.lock().unwrap();
+                                *average = Some(average.map_or(elapsed, |average: Duration| {
+                                    average.mul_f64(0.9) + elapsed.mul_f64(0.1)
+                                }));
+                                drop(average);
+                                last_error = None;
+                                if let Some(appsrc) = recorder.lock().unwrap().as_ref() {
+                                    // 叠加 HUD 的画面只用于录制，显示端由界面单独绘制
+                                    match hud.lock().unwrap().as_ref() {
+                                        Some(hud) => {
+                                            if let Ok(mut burned) = frame.try_clone() {
+                                                burn_hud(&mut burned, hud, &current_clock()).ok();
+                                                push_mat_to_appsrc(appsrc, &burned).ok();
+                                            }
+                                        }
+                                        None => {
+                                            push_mat_to_appsrc(appsrc, &frame).ok();
+                                        }
+                                    }
+                                }
+                                if output.post(frame) && waker.send(()).is_err() {
+                                    break;
+                                }
+                            }
+                            Err(error) => {
+                                // 同一错误只报告一次，避免逐帧弹出错误
+                                if last_error.as_ref() != Some(&error) {
+                                    if errors.send(error.clone()).is_err() {
+                                        break;
+                                    }
+                                    last_error = Some(error);
+                                }
+                            }
+                        }
+                    }
+                }
+            })
+            .expect("Cannot spawn frame worker thread");
+        Self {
+            input,
+            pending_processor,
+            processing_time,
+            recorder,
+            hud,
+            keep_raw,
+            raw_frame,
+        }
+    }
+
+    pub fn input(&self) -> Arc<FrameMailbox<Result<Mat, FrameError>>> {
+        self.input.clone()
+    }
+
+    // 新的处理链从下一帧开始生效
+    pub fn set_processor(&self, processor: FrameProcessorChain) {
+        *self.pending_processor.lock().unwrap() = Some(processor);
+    }
+
+    // 经过平滑的单帧处理耗时
+    pub fn processing_time(&self) -> Option<Duration> {
+        *self.processing_time.lock().unwrap()
+    }
+
+    // 设置后每一帧处理结果都会送入录制管道
+    pub fn set_recorder(&self, appsrc: Option<gst_app::AppSrc>) {
+        *self.recorder.lock().unwrap() = appsrc;
+    }
+
+    // 设置后录制的画面会叠加 HUD
+    pub fn set_hud(&self, hud: Option<HudData>) {
+        *self.hud.lock().unwrap() = hud;
+    }
+
+    pub fn set_keep_raw(&self, keep_raw: bool) {
+        self.keep_raw.store(keep_raw, Ordering::Relaxed);
+        if !keep_raw {
+            self.raw_frame.lock().unwrap().take();
+        }
+    }
+
+    // 最近一帧未经增强处理的原始画面
+    pub fn raw_frame(&self) -> Option<Mat> {
+        self.raw_frame
+            .lock()
+            .unwrap()
+            .as_ref()
+            .and_then(|frame| frame.try_clone().ok())
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.input.dropped()
+    }
+}
+
+// 关闭信箱后工作线程处理完当前帧即自行退出，不在界面线程上等待
+impl Drop for FrameWorker {
+    fn drop(&mut self) {
+        self.input.close();
+    }
+}
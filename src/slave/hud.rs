@@ -0,0 +1,520 @@
+use std::collections::HashMap;
+
+use opencv::{self as cv, core::Mat, prelude::*};
+use relm4::gtk::{cairo, glib::DateTime};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HudData {
+    pub depth: Option<f32>,
+    pub heading: Option<f32>,
+    pub depth_locked: bool,
+    pub direction_locked: bool,
+    pub recording: bool,
+}
+
+impl HudData {
+    // 从下位机上报的状态信息中提取 HUD 所需的数据，缺失的项不绘制
+    pub fn from_infos(infos: &HashMap<String, String>, recording: bool) -> Self {
+        let number = |key: &str| infos.get(key).and_then(|value| value.parse::<f32>().ok());
+        let flag = |key: &str| {
+            infos
+                .get(key)
+                .map_or(false, |value| value == "1" || value == "true")
+        };
+        Self {
+            depth: number("depth"),
+            heading: number("heading"),
+            depth_locked: flag("depth_locked"),
+            direction_locked: flag("direction_locked"),
+            recording,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct HudColor(f64, f64, f64, f64);
+
+const BACKGROUND: HudColor = HudColor(0.0, 0.0, 0.0, 0.45);
+const FOREGROUND: HudColor = HudColor(1.0, 1.0, 1.0, 1.0);
+const INACTIVE: HudColor = HudColor(0.7, 0.7, 0.7, 1.0);
+const HIGHLIGHT: HudColor = HudColor(1.0, 0.85, 0.2, 1.0);
+const ACTIVE: HudColor = HudColor(0.35, 0.9, 0.45, 1.0);
+const RECORDING: HudColor = HudColor(0.95, 0.2, 0.2, 1.0);
+
+// 显示与叠加到画面共用同一套布局，分别由 Cairo 与 OpenCV 实现绘制；
+// OpenCV 的内置字体只支持 ASCII，因此 HUD 中的文字统一使用英文缩写
+pub trait HudCanvas {
+    type Error;
+
+    fn fill_rect(
+        &mut self,
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+        color: HudColor,
+    ) -> Result<(), Self::Error>;
+    fn line(
+        &mut self,
+        x1: f64,
+        y1: f64,
+        x2: f64,
+        y2: f64,
+        color: HudColor,
+    ) -> Result<(), Self::Error>;
+    fn circle(&mut self, x: f64, y: f64, radius: f64, color: HudColor) -> Result<(), Self::Error>;
+    // 以左下角（基线）为起点绘制文字
+    fn text(
+        &mut self,
+        x: f64,
+        y: f64,
+        size: f64,
+        text: &str,
+        color: HudColor,
+    ) -> Result<(), Self::Error>;
+    fn text_width(&mut self, size: f64, text: &str) -> f64;
+}
+
+pub fn draw_hud<C: HudCanvas>(
+    canvas: &mut C,
+    width: f64,
+    height: f64,
+    data: &HudData,
+    clock: &str,
+) -> Result<(), C::Error> {
+    let scale = (height / 720.0).max(0.5);
+    let margin = 16.0 * scale;
+    let font = 18.0 * scale;
+    if data.recording {
+        canvas.circle(
+            margin + 8.0 * scale,
+            margin + 8.0 * scale,
+            7.0 * scale,
+            RECORDING,
+        )?;
+        canvas.text(
+            margin + 22.0 * scale,
+            margin + 15.0 * scale,
+            font,
+            "REC",
+            RECORDING,
+        )?;
+    }
+    let clock_width = canvas.text_width(font, clock);
+    canvas.text(
+        width - margin - clock_width,
+        margin + 15.0 * scale,
+        font,
+        clock,
+        FOREGROUND,
+    )?;
+    if let Some(heading) = data.heading {
+        draw_compass(canvas, width, scale, heading as f64)?;
+    }
+    if let Some(depth) = data.depth {
+        draw_depth_gauge(canvas, width, height, scale, depth as f64)?;
+    }
+    let locks = [
+        ("HDG LOCK", data.direction_locked),
+        ("DEPTH LOCK", data.depth_locked),
+    ];
+    for (row, (label, locked)) in locks.into_iter().enumerate() {
+        let label_width = canvas.text_width(font, label);
+        canvas.text(
+            width - margin - label_width,
+            height - margin - row as f64 * font * 1.4,
+            font,
+            label,
+            if locked { ACTIVE } else { INACTIVE },
+        )?;
+    }
+    Ok(())
+}
+
+// 顶部的航向刻度带，中心指针对应当前航向，可见范围为 ±45°
+fn draw_compass<C: HudCanvas>(
+    canvas: &mut C,
+    width: f64,
+    scale: f64,
+    heading: f64,
+) -> Result<(), C::Error> {
+    const SPAN: f64 = 90.0;
+    let tape_width = (width * 0.5).min(480.0 * scale);
+    let tape_height = 36.0 * scale;
+    let center = width / 2.0;
+    let top = 48.0 * scale;
+    let left = center - tape_width / 2.0;
+    let pixels_per_degree = tape_width / SPAN;
+    let font = 14.0 * scale;
+    canvas.fill_rect(left, top, tape_width, tape_height, BACKGROUND)?;
+    let first = ((heading - SPAN / 2.0) / 5.0).ceil() as i32 * 5;
+    for degree in (first..=(heading + SPAN / 2.0).floor() as i32).step_by(5) {
+        let x = center + (degree as f64 - heading) * pixels_per_degree;
+        let major = degree % 10 == 0;
+        let tick = if major { 0.4 } else { 0.2 } * tape_height;
+        canvas.line(x, top, x, top + tick, FOREGROUND)?;
+        if degree % 30 == 0 {
+            let label = match degree.rem_euclid(360) {
+                0 => String::from("N"),
+                90 => String::from("E"),
+                180 => String::from("S"),
+                270 => String::from("W"),
+                other => other.to_string(),
+            };
+            let label_width = canvas.text_width(font, &label);
+            canvas.text(
+                x - label_width / 2.0,
+                top + tape_height - 4.0 * scale,
+                font,
+                &label,
+                FOREGROUND,
+            )?;
+        }
+    }
+    canvas.line(center, top, center, top + tape_height, HIGHLIGHT)?;
+    let readout = format!("HDG {:03.0}", heading.rem_euclid(360.0));
+    let readout_width = canvas.text_width(font, &readout);
+    canvas.fill_rect(
+        center - readout_width / 2.0 - 4.0 * scale,
+        top + tape_height,
+        readout_width + 8.0 * scale,
+        font * 1.4,
+        BACKGROUND,
+    )?;
+    canvas.text(
+        center - readout_width / 2.0,
+        top + tape_height + font * 1.1,
+        font,
+        &readout,
+        HIGHLIGHT,
+    )
+}
+
+// 右侧的深度计，刻度随深度滚动，中心指针对应当前深度，可见范围为 ±5 米
+fn draw_depth_gauge<C: HudCanvas>(
+    canvas: &mut C,
+    width: f64,
+    height: f64,
+    scale: f64,
+    depth: f64,
+) -> Result<(), C::Error> {
+    const SPAN: f64 = 10.0;
+    let gauge_width = 56.0 * scale;
+    let gauge_height = (height * 0.5).min(300.0 * scale);
+    let margin = 16.0 * scale;
+    let left = width - margin - gauge_width;
+    let center = height / 2.0;
+    let top = center - gauge_height / 2.0;
+    let pixels_per_meter = gauge_height / SPAN;
+    let font = 14.0 * scale;
+    canvas.fill_rect(left, top, gauge_width, gauge_height, BACKGROUND)?;
+    let first = (depth - SPAN / 2.0).ceil() as i32;
+    for meter in first..=(depth + SPAN / 2.0).floor() as i32 {
+        let y = center + (meter as f64 - depth) * pixels_per_meter;
+        let major = meter % 2 == 0;
+        let tick = if major { 0.3 } else { 0.15 } * gauge_width;
+        canvas.line(
+            left + gauge_width - tick,
+            y,
+            left + gauge_width,
+            y,
+            FOREGROUND,
+        )?;
+        if major {
+            canvas.text(
+                left + 4.0 * scale,
+                y + font * 0.35,
+                font,
+                &meter.to_string(),
+                FOREGROUND,
+            )?;
+        }
+    }
+    canvas.line(left, center, left + gauge_width, center, HIGHLIGHT)?;
+    let readout = format!("{:.1} m", depth);
+    let readout_width = canvas.text_width(font, &readout);
+    canvas.fill_rect(
+        left - readout_width - 12.0 * scale,
+        center - font * 0.8,
+        readout_width + 8.0 * scale,
+        font * 1.6,
+        BACKGROUND,
+    )?;
+    canvas.text(
+        left - readout_width - 8.0 * scale,
+        center + font * 0.4,
+        font,
+        &readout,
+        HIGHLIGHT,
+    )?;
+    let title_width = canvas.text_width(font, "DEPTH");
+    canvas.text(
+        left + (gauge_width - title_width) / 2.0,
+        top - 6.0 * scale,
+        font,
+        "DEPTH",
+        FOREGROUND,
+    )
+}
+
+impl HudCanvas for cairo::Context {
+    type Error = cairo::Error;
+
+    fn fill_rect(
+        &mut self,
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+        HudColor(r, g, b, a): HudColor,
+    ) -> Result<(), Self::Error> {
+        self.set_source_rgba(r, g, b, a);
+        self.rectangle(x, y, width, height);
+        self.fill()
+    }
+
+    fn line(
+        &mut self,
+        x1: f64,
+        y1: f64,
+        x2: f64,
+        y2: f64,
+        HudColor(r, g, b, a): HudColor,
+    ) -> Result<(), Self::Error> {
+        self.set_source_rgba(r, g, b, a);
+        self.set_line_width(2.0);
+        self.move_to(x1, y1);
+        self.line_to(x2, y2);
+        self.stroke()
+    }
+
+    fn circle(
+        &mut self,
+        x: f64,
+        y: f64,
+        radius: f64,
+        HudColor(r, g, b, a): HudColor,
+    ) -> Result<(), Self::Error> {
+        self.set_source_rgba(r, g, b, a);
+        self.arc(x, y, radius, 0.0, std::f64::consts::TAU);
+        self.fill()
+    }
+
+    fn text(
+        &mut self,
+        x: f64,
+        y: f64,
+        size: f64,
+        text: &str,
+        HudColor(r, g, b, a): HudColor,
+    ) -> Result<(), Self::Error> {
+        self.set_source_rgba(r, g, b, a);
+        self.select_font_face(
+            "monospace",
+            cairo::FontSlant::Normal,
+            cairo::FontWeight::Bold,
+        );
+        self.set_font_size(size);
+        self.move_to(x, y);
+        self.show_text(text)
+    }
+
+    fn text_width(&mut self, size: f64, text: &str) -> f64 {
+        self.select_font_face(
+            "monospace",
+            cairo::FontSlant::Normal,
+            cairo::FontWeight::Bold,
+        );
+        self.set_font_size(size);
+        self.text_extents(text)
+            .map_or(0.0, |extents| extents.x_advance())
+    }
+}
+
+// Hershey 字体在缩放系数为 1 时的大致字高
+const HERSHEY_BASE_SIZE: f64 = 22.0;
+
+fn cv_color(HudColor(r, g, b, _): HudColor) -> cv::core::Scalar {
+    // 画面帧为 RGB 顺序
+    cv::core::Scalar::new(r * 255.0, g * 255.0, b * 255.0, 0.0)
+}
+
+fn cv_point(x: f64, y: f64) -> cv::core::Point {
+    cv::core::Point::new(x.round() as i32, y.round() as i32)
+}
+
+impl HudCanvas for Mat {
+    type Error = cv::Error;
+
+    fn fill_rect(
+        &mut self,
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+        color: HudColor,
+    ) -> Result<(), Self::Error> {
+        // 裁剪到画面范围内
+        let left = (x.round() as i32).clamp(0, self.cols());
+        let top = (y.round() as i32).clamp(0, self.rows());
+        let right = ((x + width).round() as i32).clamp(0, self.cols());
+        let bottom = ((y + height).round() as i32).clamp(0, self.rows());
+        if right <= left || bottom <= top {
+            return Ok(());
+        }
+        let rect = cv::core::Rect::new(left, top, right - left, bottom - top);
+        // 半透明背景需要与原画面混合
+        let mut roi = Mat::roi(self, rect)?;
+        let source = roi.try_clone()?;
+        let overlay = Mat::new_size_with_default(rect.size(), self.typ(), cv_color(color))?;
+        let alpha = color.3;
+        cv::core::add_weighted(&source, 1.0 - alpha, &overlay, alpha, 0.0, &mut roi, -1)
+    }
+
+    fn line(
+        &mut self,
+        x1: f64,
+        y1: f64,
+        x2: f64,
+        y2: f64,
+        color: HudColor,
+    ) -> Result<(), Self::Error> {
+        cv::imgproc::line(
+            self,
+            cv_point(x1, y1),
+            cv_point(x2, y2),
+            cv_color(color),
+            2,
+            cv::imgproc::LINE_AA,
+            0,
+        )
+    }
+
+    fn circle(&mut self, x: f64, y: f64, radius: f64, color: HudColor) -> Result<(), Self::Error> {
+        cv::imgproc::circle(
+            self,
+            cv_point(x, y),
+            radius.round() as i32,
+            cv_color(color),
+            cv::imgproc::FILLED,
+            cv::imgproc::LINE_AA,
+            0,
+        )
+    }
+
+    fn text(
+        &mut self,
+        x: f64,
+        y: f64,
+        size: f64,
+        text: &str,
+        color: HudColor,
+    ) -> Result<(), Self::Error> {
+        cv::imgproc::put_text(
+            self,
+            text,
+            cv_point(x, y),
+            cv::imgproc::FONT_HERSHEY_SIMPLEX,
+            size / HERSHEY_BASE_SIZE,
+            cv_color(color),
+            (size / 10.0).ceil() as i32,
+            cv::imgproc::LINE_AA,
+            false,
+        )
+    }
+
+    fn text_width(&mut self, size: f64, text: &str) -> f64 {
+        let mut baseline = 0;
+        cv::imgproc::get_text_size(
+            text,
+            cv::imgproc::FONT_HERSHEY_SIMPLEX,
+            size / HERSHEY_BASE_SIZE,
+            (size / 10.0).ceil() as i32,
+            &mut baseline,
+        )
+        .map_or(0.0, |size| size.width as f64)
+    }
+}
+
+pub fn current_clock() -> String {
+    DateTime::now_local()
+        .and_then(|now| now.format("%H:%M:%S"))
+        .map_or_else(|_| String::new(), String::from)
+}
+
+// 按 ContentFit::Contain 的方式计算画面在控件中的实际区域 (x, y, 宽, 高)，
+// 画面比例未知时使用整个控件
+pub fn contain_rect(width: f64, height: f64, aspect_ratio: f64) -> (f64, f64, f64, f64) {
+    if aspect_ratio <= 0.0 || width <= 0.0 || height <= 0.0 {
+        return (0.0, 0.0, width, height);
+    }
+    if width / height > aspect_ratio {
+        let video_width = height * aspect_ratio;
+        ((width - video_width) / 2.0, 0.0, video_width, height)
+    } else {
+        let video_height = width / aspect_ratio;
+        (0.0, (height - video_height) / 2.0, width, video_height)
+    }
+}
+
+// 将 HUD 叠加到画面上，用于录制与截图
+pub fn burn_hud(mat: &mut Mat, data: &HudData, clock: &str) -> cv::Result<()> {
+    let (width, height) = (mat.cols() as f64, mat.rows() as f64);
+    draw_hud(mat, width, height, data, clock)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn infos(entries: &[(&str, &str)]) -> HashMap<String, String> {
+        entries
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn parses_reported_infos() {
+        let data = HudData::from_infos(
+            &infos(&[
+                ("depth", "12.5"),
+                ("heading", "270"),
+                ("depth_locked", "true"),
+                ("direction_locked", "1"),
+            ]),
+            true,
+        );
+        assert_eq!(
+            data,
+            HudData {
+                depth: Some(12.5),
+                heading: Some(270.0),
+                depth_locked: true,
+                direction_locked: true,
+                recording: true,
+            }
+        );
+    }
+
+    #[test]
+    fn contain_rect_letterboxes_video() {
+        // 较宽的控件左右留边，较高的控件上下留边
+        assert_eq!(contain_rect(800.0, 300.0, 2.0), (100.0, 0.0, 600.0, 300.0));
+        assert_eq!(contain_rect(400.0, 400.0, 2.0), (0.0, 100.0, 400.0, 200.0));
+        assert_eq!(contain_rect(400.0, 300.0, 0.0), (0.0, 0.0, 400.0, 300.0));
+    }
+
+    #[test]
+    fn missing_or_invalid_infos_are_not_drawn() {
+        let data = HudData::from_infos(
+            &infos(&[
+                ("depth", "unknown"),
+                ("depth_locked", "false"),
+                ("direction_locked", "0"),
+            ]),
+            false,
+        );
+        assert_eq!(data, HudData::default());
+    }
+}
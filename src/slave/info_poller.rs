@@ -0,0 +1,147 @@
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+    net::TcpStream,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use relm4::gtk::glib::Sender;
+use serde_json::{json, Value};
+use url::Url;
+
+const METHOD_GET_INFO: &str = "get_info";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+
+pub type InfoResult = Result<HashMap<String, String>, String>;
+
+// 按固定间隔通过 JSON-RPC 向机位请求状态信息（深度、航向角、锁定状态等），
+// 结果经由 glib 通道送回界面线程；请求失败后停止轮询，由界面决定是否重新连接
+#[derive(Debug)]
+pub struct InfoPoller {
+    stopped: Arc<AtomicBool>,
+}
+
+impl InfoPoller {
+    pub fn spawn(url: Url, interval: Duration, sender: Sender<InfoResult>) -> Self {
+        let stopped = Arc::new(AtomicBool::new(false));
+        std::thread::Builder::new()
+            .name(String::from("info-poller"))
+            .spawn({
+                let stopped = stopped.clone();
+                move || {
+                    let mut id = 0_u64;
+                    while !stopped.load(Ordering::Relaxed) {
+                        id += 1;
+                        let result = request_info(&url, id);
+                        let failed = result.is_err();
+                        if sender.send(result).is_err() || failed {
+                            break;
+                        }
+                        std::thread::sleep(interval);
+                    }
+                }
+            })
+            .expect("Cannot spawn info poller thread");
+        Self { stopped }
+    }
+}
+
+// 停止后线程在当前请求结束时自行退出，不在界面线程上等待
+impl Drop for InfoPoller {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+}
+
+fn request_info(url: &Url, id: u64) -> InfoResult {
+    let body = json!({
+        "jsonrpc": "2.0",
+        "method": METHOD_GET_INFO,
+        "params": [],
+        "id": id,
+    })
+    .to_string();
+    let address = url
+        .socket_addrs(|| Some(80))
+        .map_err(|err| err.to_string())?
+        .into_iter()
+        .next()
+        .ok_or_else(|| String::from("无法解析机位地址"))?;
+    let mut stream =
+        TcpStream::connect_timeout(&address, REQUEST_TIMEOUT).map_err(|err| err.to_string())?;
+    stream
+        .set_read_timeout(Some(REQUEST_TIMEOUT))
+        .and_then(|_| stream.set_write_timeout(Some(REQUEST_TIMEOUT)))
+        .map_err(|err| err.to_string())?;
+    // 使用 HTTP/1.0 请求，响应以关闭连接结束，无需处理分块传输
+    let request = format!(
+        "POST {} HTTP/1.0\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+        url.path(),
+        url.host_str().unwrap_or_default(),
+        body.len(),
+        body
+    );
+    stream
+        .write_all(request.as_bytes())
+        .map_err(|err| err.to_string())?;
+    let mut response = String::new();
+    stream
+        .read_to_string(&mut response)
+        .map_err(|err| err.to_string())?;
+    parse_response(&response)
+}
+
+fn parse_response(response: &str) -> InfoResult {
+    let (head, body) = response
+        .split_once("\r\n\r\n")
+        .ok_or_else(|| String::from("机位返回的响应不完整"))?;
+    let status = head.lines().next().unwrap_or_default();
+    if status.split_whitespace().nth(1) != Some("200") {
+        return Err(format!("机位返回错误状态：{}", status));
+    }
+    let value: Value = serde_json::from_str(body).map_err(|err| err.to_string())?;
+    if let Some(error) = value.get("error") {
+        return Err(format!("机位返回错误：{}", error));
+    }
+    match value.get("result") {
+        Some(Value::Object(result)) => Ok(result
+            .iter()
+            .map(|(key, value)| {
+                let value = match value {
+                    Value::String(value) => value.clone(),
+                    value => value.to_string(),
+                };
+                (key.clone(), value)
+            })
+            .collect()),
+        _ => Err(String::from("机位返回的状态信息格式有误")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_result_values_as_strings() {
+        let response = "HTTP/1.0 200 OK\r\nContent-Type: application/json\r\n\r\n\
+            {\"jsonrpc\":\"2.0\",\"id\":1,\"result\":\
+            {\"depth\":12.5,\"heading\":\"270\",\"depth_locked\":true}}";
+        let infos = parse_response(response).unwrap();
+        assert_eq!(infos.get("depth").map(String::as_str), Some("12.5"));
+        assert_eq!(infos.get("heading").map(String::as_str), Some("270"));
+        assert_eq!(infos.get("depth_locked").map(String::as_str), Some("true"));
+    }
+
+    #[test]
+    fn rejects_error_status_and_rpc_error() {
+        assert!(parse_response("HTTP/1.0 500 Internal Server Error\r\n\r\n").is_err());
+        let response = "HTTP/1.0 200 OK\r\n\r\n\
+            {\"jsonrpc\":\"2.0\",\"id\":1,\"error\":{\"code\":-32601,\"message\":\"Method not found\"}}";
+        assert!(parse_response(response).is_err());
+    }
+}
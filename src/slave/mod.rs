@@ -1,29 +1,51 @@
 mod async_glib;
 mod config;
+mod exif;
+mod frame_error;
+mod frame_mailbox;
+mod frame_worker;
+mod hud;
+mod info_poller;
+mod pre_record;
+mod screenshot;
+mod url_ext;
 mod video;
+mod video_error;
+mod video_processor;
+mod video_stats;
 
 pub mod video_ext;
 
+use std::{collections::HashMap, path::PathBuf, time::Duration};
+
 use relm4::{
-    adw::{prelude::*, Flap, ToastOverlay},
+    adw::{prelude::*, Flap, Toast, ToastOverlay},
     factory::{positions::GridPosition, Position},
     gtk::{
-        glib::DateTime, Align, Box as GtkBox, Button as GtkButton, CenterBox, Frame, Grid, Image,
+        glib::{self, Continue, DateTime, MainContext},
+        Align, Box as GtkBox, Button as GtkButton, CenterBox, Frame, GestureClick, Grid, Image,
         Label, MenuButton, Orientation, Overlay, PackType, Popover, Revealer, Separator,
-        ToggleButton,
+        ToggleButton, Widget,
     },
     prelude::*,
 };
 
 use crate::{
     preferences::PreferencesModel,
+    session::SlaveSession,
     slave::{config::SlaveConfigInput, video::SlaveVideoInput},
     AppMsg,
 };
 
+pub use self::config::SlaveConfigModel;
+
 use self::{
-    config::{SlaveConfigModel, SlaveConfigOutput},
+    config::{SlaveConfigInit, SlaveConfigOutput},
+    hud::HudData,
+    info_poller::InfoPoller,
+    screenshot::{ScreenshotMetadata, ScreenshotRequest},
     video::{SlaveVideoInit, SlaveVideoModel, SlaveVideoOutput},
+    video_ext::RecordMetadata,
 };
 
 #[tracker::track]
@@ -42,24 +64,40 @@ pub struct SlaveModel {
     #[no_eq]
     preferences: PreferencesModel,
     sync_recording: bool,
+    #[do_not_track]
+    sync_record_pending: bool,
     slave_info_displayed: bool,
     config_presented: bool,
+    #[no_eq]
+    toast_message: Option<String>,
+    compact: bool,
+    #[no_eq]
+    grid_child: Option<Widget>,
+    #[no_eq]
+    infos: HashMap<String, String>,
+    #[do_not_track]
+    info_poller: Option<InfoPoller>,
     index: DynamicIndex,
 }
 
 #[derive(Debug)]
 pub enum SlaveInput {
     ToggleRecord,
+    StartSyncRecord(PathBuf, DateTime),
+    StopSyncRecord,
     ToggleConnect,
     TogglePolling,
     PollingChanged(bool),
     RecordingChanged(bool),
     TakeScreenshot,
+    TakeSyncScreenshot(PathBuf, DateTime),
+    SetCompositeFeed(Option<gst_app::AppSrc>),
     //AddInputSource(InputSource),
     //RemoveInputSource(InputSource),
     //SetSlaveStatus(SlaveStatusClass, i16),
     UpdateInputSources,
     ToggleDisplayInfo,
+    SetVideoStatsDisplayed(bool),
     //InputReceived(InputSourceEvent),
     OpenFirmwareUpater,
     OpenParameterTuner,
@@ -69,30 +107,99 @@ pub enum SlaveInput {
     //ConnectionChanged(Option<async_std::sync::Arc<RpcClient>>),
     ShowToastMessage(String),
     //CommunicationMessage(SlaveCommunicationMsg),
-    //InformationsReceived(HashMap<String, String>),
+    InformationsReceived(HashMap<String, String>),
     SetConfigPresented(bool),
+    SetCompact(bool),
+    Promote,
 
     UpdataPreferences(PreferencesModel),
     UpdateConfig(SlaveConfigModel),
 }
 
+#[derive(Debug)]
+pub struct SlaveInit {
+    pub preferences: PreferencesModel,
+    pub session: Option<SlaveSession>, // 恢复上次会话时的机位状态
+}
+
 #[derive(Debug)]
 pub enum SlaveOutput {
     DestroySlave(usize),
+    Promote(usize),
 }
 
 impl Position<GridPosition, DynamicIndex> for SlaveModel {
+    // 新加入的机位先按网格排列，其余布局由 AppModel 统一调整
     fn position(&self, index: &DynamicIndex) -> GridPosition {
-        let index = index.current_index() as i32;
-        let row = index / 3;
-        let column = index % 3;
-        GridPosition {
-            column,
-            row,
-            width: 1,
-            height: 1,
+        let index = index.current_index();
+        let preferences = &self.preferences;
+        preferences
+            .layout_mode
+            .positions(index + 1, 0, preferences.custom_layout_columns as usize)
+            .remove(index)
+    }
+}
+
+impl SlaveModel {
+    // 仅正在拉流且未在录制的机位参与同步录制
+    pub fn can_sync_record(&self) -> bool {
+        self.polling == Some(true) && self.recording == Some(false)
+    }
+
+    // 机位在父级 Grid 中的控件，用于调整布局
+    pub fn grid_child(&self) -> Option<&Widget> {
+        self.grid_child.as_ref()
+    }
+
+    fn record_metadata(&self, start_time: DateTime) -> RecordMetadata {
+        let config = self.config_model.model();
+        RecordMetadata {
+            title: format!("机位 {}", self.index.current_index() + 1),
+            video_url: url_ext::without_credentials(config.get_video_url()).to_string(),
+            start_time,
+            notes: config.get_recording_notes().clone(),
         }
     }
+
+    fn screenshot_metadata(&self) -> ScreenshotMetadata {
+        ScreenshotMetadata {
+            slave: format!("机位 {}", self.index.current_index() + 1),
+            timestamp: String::new(),
+            video_url: url_ext::without_credentials(self.config_model.model().get_video_url())
+                .to_string(),
+            infos: self.infos.clone().into_iter().collect(),
+        }
+    }
+
+    fn save_screenshot(&self, pathbuf: PathBuf) {
+        self.video_model
+            .emit(SlaveVideoInput::SaveScreenshot(ScreenshotRequest::new(
+                pathbuf,
+                *self.preferences.get_image_save_format(),
+                self.screenshot_metadata(),
+                *self.preferences.get_screenshot_burst_count(),
+            )));
+    }
+
+    pub fn session(&self) -> SlaveSession {
+        SlaveSession {
+            config: self.config_model.model().clone(),
+            polling: self.polling == Some(true),
+        }
+    }
+
+    // 停止状态信息轮询，并清除已不再更新的状态信息
+    fn disconnect(&mut self) {
+        self.info_poller = None;
+        self.infos.clear();
+        self.set_connected(Some(false));
+        self.config_model.emit(SlaveConfigInput::SetConnected(Some(false)));
+        self.video_model.emit(SlaveVideoInput::SetHudData(self.hud_data()));
+    }
+
+    fn hud_data(&self) -> HudData {
+        HudData::from_infos(&self.infos, self.recording == Some(true))
+    }
 }
 
 #[relm4::factory(pub)]
@@ -100,11 +207,15 @@ impl FactoryComponent for SlaveModel {
     view! {
         #[root]
         ToastOverlay {
+            #[track = "self.changed(SlaveModel::toast_message())"]
+            add_toast?: self.toast_message.as_ref().map(|message| Toast::new(message)),
             #[wrap(Some)]
             set_child = &GtkBox {
                 set_orientation: Orientation::Vertical,
                 append = &CenterBox {
                     set_css_classes: &["toolbar"],
+                    #[track = "self.changed(SlaveModel::compact())"]
+                    set_visible: !self.compact,
                     set_orientation: Orientation::Horizontal,
                     #[wrap(Some)]
                     set_start_widget = &GtkBox {
@@ -139,7 +250,7 @@ impl FactoryComponent for SlaveModel {
                         append = &GtkButton {
                             set_icon_name: "camera-photo-symbolic",
                             #[watch]
-                            set_sensitive: self.video_model.model().get_pixbuf().is_some(),
+                            set_sensitive: self.video_model.model().has_frame(),
                             set_css_classes: &["circular"],
                             set_tooltip_text: Some("画面截图"),
                             connect_clicked[sender] => move |_| {
@@ -228,6 +339,14 @@ impl FactoryComponent for SlaveModel {
                             },
                         },
                         append = &Separator {},
+                        append = &ToggleButton {
+                            set_icon_name: "utilities-system-monitor-symbolic",
+                            set_css_classes: &["circular"],
+                            set_tooltip_text: Some("视频统计信息"),
+                            connect_active_notify[sender] => move |button| {
+                                sender.input(SlaveInput::SetVideoStatsDisplayed(button.is_active()));
+                            },
+                        },
                         append = &ToggleButton {
                             set_icon_name: "emblem-system-symbolic",
                             set_css_classes: &["circular"],
@@ -260,8 +379,16 @@ impl FactoryComponent for SlaveModel {
                     set_separator = &Separator {},
                     #[wrap(Some)]
                     set_content = &Overlay {
-                        set_width_request: 640,
+                        #[track = "self.changed(SlaveModel::compact())"]
+                        set_width_request: if self.compact { 160 } else { 640 },
                         set_child: Some(self.video_model.widget()),
+                        add_controller = &GestureClick {
+                            connect_pressed[sender] => move |_, n_press, _, _| {
+                                if n_press == 2 {
+                                    sender.input(SlaveInput::Promote);
+                                }
+                            }
+                        },
                         add_overlay = &GtkBox {
                             set_valign: Align::Start,
                             set_halign: Align::End,
@@ -431,22 +558,29 @@ impl FactoryComponent for SlaveModel {
         }
     }
 
-    type Init = PreferencesModel;
+    type Init = SlaveInit;
     type Input = SlaveInput;
     type Output = SlaveOutput;
     type CommandOutput = ();
     type ParentInput = AppMsg;
     type ParentWidget = Grid;
 
-    fn init_model(
-        preferences: Self::Init,
-        index: &DynamicIndex,
-        sender: FactorySender<Self>,
-    ) -> Self {
+    fn init_model(init: Self::Init, index: &DynamicIndex, sender: FactorySender<Self>) -> Self {
+        let preferences = init.preferences;
+        let (config, polling) = init.session.map_or((None, false), |session| {
+            (Some(session.config), session.polling)
+        });
         let config_model = SlaveConfigModel::builder()
-            .launch(preferences.clone())
+            .launch(SlaveConfigInit {
+                preferences: preferences.clone(),
+                index: index.current_index(),
+                config,
+            })
             .forward(sender.input_sender(), |msg| match msg {
                 SlaveConfigOutput::UpdateConfig(config) => SlaveInput::UpdateConfig(config),
+                SlaveConfigOutput::ShowToastMessage(message) => {
+                    SlaveInput::ShowToastMessage(message)
+                }
             });
         let video_model_init = SlaveVideoInit {
             preferences: preferences.clone(),
@@ -461,14 +595,23 @@ impl FactoryComponent for SlaveModel {
                 SlaveVideoOutput::ShowToastMessage(str) => SlaveInput::ShowToastMessage(str),
             },
         );
+        if polling {
+            sender.input(SlaveInput::TogglePolling);
+        }
         Self {
             preferences,
             connected: Some(false),
             recording: Some(false),
             polling: Some(false),
             sync_recording: false,
+            sync_record_pending: false,
             slave_info_displayed: false,
             config_presented: false,
+            toast_message: None,
+            compact: false,
+            grid_child: None,
+            infos: HashMap::new(),
+            info_poller: None,
             video_model,
             config_model,
             index: index.clone(),
@@ -476,6 +619,18 @@ impl FactoryComponent for SlaveModel {
         }
     }
 
+    fn init_widgets(
+        &mut self,
+        _index: &DynamicIndex,
+        root: &Self::Root,
+        returned_widget: &Widget,
+        sender: FactorySender<Self>,
+    ) -> Self::Widgets {
+        let widgets = view_output!();
+        self.grid_child = Some(returned_widget.clone());
+        widgets
+    }
+
     fn update(&mut self, message: Self::Input, sender: FactorySender<Self>) {
         self.reset();
 
@@ -484,57 +639,78 @@ impl FactoryComponent for SlaveModel {
             ToggleRecord => {
                 let video = &self.video_model;
                 if video.model().get_record_handle().is_none() {
+                    let start_time = DateTime::now_local().unwrap();
                     let mut pathbuf = self.preferences.get_video_save_path().clone();
                     pathbuf.push(format!(
-                        "{}.mkv",
-                        DateTime::now_local()
-                            .unwrap()
-                            .format_iso8601()
-                            .unwrap()
-                            .replace(":", "-")
+                        "{}.{}",
+                        start_time.format_iso8601().unwrap().replace(":", "-"),
+                        self.config_model.model().get_video_container().extension()
+                    ));
+                    video.emit(SlaveVideoInput::StartRecord(
+                        pathbuf,
+                        self.record_metadata(start_time),
                     ));
-                    video.emit(SlaveVideoInput::StartRecord(pathbuf));
                 } else {
                     video.emit(SlaveVideoInput::StopRecord(None));
                 }
                 self.set_recording(None);
             }
+            StartSyncRecord(directory, start_time) => {
+                if self.can_sync_record() {
+                    let mut pathbuf = directory;
+                    pathbuf.push(format!(
+                        "{}_{}.{}",
+                        start_time.format_iso8601().unwrap().replace(":", "-"),
+                        self.index.current_index() + 1,
+                        self.config_model.model().get_video_container().extension()
+                    ));
+                    self.video_model.emit(SlaveVideoInput::StartRecord(
+                        pathbuf,
+                        self.record_metadata(start_time),
+                    ));
+                    self.sync_record_pending = true;
+                    self.set_recording(None);
+                }
+            }
+            StopSyncRecord => {
+                // 同步录制仍在启动中时同样需要停止，否则录制开始后将无人停止
+                let sync_record_pending = std::mem::take(&mut self.sync_record_pending);
+                if self.sync_recording || sync_record_pending {
+                    self.video_model.emit(SlaveVideoInput::StopRecord(None));
+                    self.set_recording(None);
+                }
+            }
             ToggleConnect => {
                 match self.get_connected() {
                     Some(true) => {
                         // 断开连接
-                        self.set_connected(None);
-                        self.config_model.emit(SlaveConfigInput::SetConnected(None));
-                        // let sender = self.get_communication_msg_sender().clone().unwrap();
-                        // task::spawn(async move {
-                        //     sender.send(SlaveCommunicationMsg::Disconnect).await.expect("Communication main loop should be running");
-                        // });
+                        self.disconnect();
                     }
-                    Some(false) => { // 连接
-                         // let url = self.config_model.model().get_slave_url().clone();
-                         // if let ("http", url_str) = (url.scheme(), url.as_str()) {
-                         //     if let Ok(rpc_client) = RpcClientBuilder::default().build(url_str) {
-                         //         let (comm_sender, comm_receiver) = async_std::channel::bounded::<SlaveCommunicationMsg>(128);
-                         //         self.set_communication_msg_sender(Some(comm_sender.clone()));
-                         //         let sender = sender.clone();
-                         //         let control_sending_rate = *self.preferences.borrow().get_default_input_sending_rate();
-                         //         self.set_connected(None);
-                         //         self.config.send(SlaveConfigMsg::SetConnected(None)).unwrap();
-                         //         let status_info_update_interval = *self.preferences.borrow().get_default_status_info_update_interval();
-                         //         async_std::task::spawn(async move {
-                         //             communication_main_loop(control_sending_rate,
-                         //                                     Arc::new(rpc_client),
-                         //                                     comm_sender,
-                         //                                     comm_receiver,
-                         //                                     sender.clone(),
-                         //                                     status_info_update_interval as u64).await.unwrap_or_default();
-                         //         });
-                         //     } else {
-                         //         error_message("错误", "无法创建 RPC 客户端。", app_window.upgrade().as_ref());
-                         //     }
-                         // } else {
-                         //     error_message("错误", "连接 URL 有误，请检查并修改后重试 。", app_window.upgrade().as_ref());
-                         // }
+                    Some(false) => {
+                        // 连接，收到第一份状态信息后视为已连接
+                        let url = self.config_model.model().get_slave_url().clone();
+                        if url.scheme() == "http" {
+                            let (info_sender, info_receiver) =
+                                MainContext::channel(glib::PRIORITY_DEFAULT);
+                            let input_sender = sender.input_sender().clone();
+                            info_receiver.attach(None, move |result| {
+                                let msg = match result {
+                                    Ok(infos) => SlaveInput::InformationsReceived(infos),
+                                    Err(err) => SlaveInput::CommunicationError(err),
+                                };
+                                Continue(input_sender.send(msg).is_ok())
+                            });
+                            let interval = Duration::from_millis(
+                                *self.preferences.get_default_status_info_update_interval() as u64,
+                            );
+                            self.info_poller = Some(InfoPoller::spawn(url, interval, info_sender));
+                            self.set_connected(None);
+                            self.config_model.emit(SlaveConfigInput::SetConnected(None));
+                        } else {
+                            self.set_toast_message(Some(String::from(
+                                "连接 URL 有误，请检查并修改后重试。",
+                            )));
+                        }
                     }
                     None => (),
                 }
@@ -558,18 +734,21 @@ impl FactoryComponent for SlaveModel {
                     .emit(SlaveConfigInput::SetPolling(Some(val)));
             }
             RecordingChanged(val) => {
+                // 由同步录制发起的录制开始后才视为处于同步录制中
+                let sync_record_pending = std::mem::take(&mut self.sync_record_pending);
                 if val {
-                    if *self.get_recording() == Some(false) {
+                    if sync_record_pending {
                         self.set_sync_recording(true);
                     }
                 } else {
                     self.set_sync_recording(false);
                 }
                 self.set_recording(Some(val));
+                self.video_model
+                    .emit(SlaveVideoInput::SetHudData(self.hud_data()));
             }
             TakeScreenshot => {
                 let mut pathbuf = self.preferences.get_image_save_path().clone();
-                let format = self.preferences.get_image_save_format().clone();
                 pathbuf.push(format!(
                     "{}.{}",
                     DateTime::now_local()
@@ -577,16 +756,34 @@ impl FactoryComponent for SlaveModel {
                         .format_iso8601()
                         .unwrap()
                         .replace(":", "-"),
-                    format.extension()
+                    self.preferences.get_image_save_format().extension()
                 ));
-                self.video_model
-                    .emit(SlaveVideoInput::SaveScreenshot(pathbuf));
+                self.save_screenshot(pathbuf);
+            }
+            TakeSyncScreenshot(directory, timestamp) => {
+                // 没有画面的机位直接跳过
+                if self.video_model.model().has_frame() {
+                    let mut pathbuf = directory;
+                    pathbuf.push(format!(
+                        "{}_{}.{}",
+                        timestamp.format_iso8601().unwrap().replace(":", "-"),
+                        self.index.current_index() + 1,
+                        self.preferences.get_image_save_format().extension()
+                    ));
+                    self.save_screenshot(pathbuf);
+                }
             }
+            SetCompositeFeed(appsrc) => self
+                .video_model
+                .emit(SlaveVideoInput::SetCompositeFeed(appsrc)),
             //AddInputSource(InputSource) => {}
             //RemoveInputSource(InputSource) => {}
             //SetSlaveStatus(SlaveStatusClass, i16) => {}
             UpdateInputSources => {}
             ToggleDisplayInfo => self.set_slave_info_displayed(!self.get_slave_info_displayed()),
+            SetVideoStatsDisplayed(displayed) => self
+                .video_model
+                .emit(SlaveVideoInput::SetStatsDisplayed(displayed)),
             //InputReceived(InputSourceEvent) => {}
             OpenFirmwareUpater => {}
             OpenParameterTuner => {}
@@ -606,14 +803,35 @@ impl FactoryComponent for SlaveModel {
             ErrorMessage(str) => {
                 println!("错误: {}", str);
                 // error_message("错误", &msg, app_window.upgrade().as_ref());
+                self.set_toast_message(Some(str));
+            }
+            CommunicationError(str) => {
+                if self.info_poller.is_some() {
+                    self.disconnect();
+                    self.set_toast_message(Some(format!("与机位通信失败：{}", str)));
+                }
             }
-            CommunicationError(_str) => {}
             //ConnectionChanged(Option<async_std::sync::Arc<RpcClient>>) => {}
-            ShowToastMessage(_str) => {}
+            ShowToastMessage(str) => self.set_toast_message(Some(str)),
             //CommunicationMessage(SlaveCommunicationMsg) => {}
-            //InformationsReceived(HashMap<String, String>) => {}
+            InformationsReceived(infos) => {
+                // 断开连接后仍在途中的状态信息直接丢弃
+                if self.info_poller.is_some() {
+                    if self.connected.is_none() {
+                        self.set_connected(Some(true));
+                        self.config_model
+                            .emit(SlaveConfigInput::SetConnected(Some(true)));
+                    }
+                    self.infos.extend(infos);
+                    self.video_model
+                        .emit(SlaveVideoInput::SetHudData(self.hud_data()));
+                }
+            }
             SetConfigPresented(val) => self.set_config_presented(val),
+            SetCompact(val) => self.set_compact(val),
+            Promote => sender.output(SlaveOutput::Promote(self.index.current_index())),
             UpdataPreferences(preferences) => {
+                self.set_preferences(preferences.clone());
                 self.config_model
                     .emit(SlaveConfigInput::UpdatePreferences(preferences.clone()));
                 self.video_model
@@ -629,6 +847,7 @@ impl FactoryComponent for SlaveModel {
         use SlaveOutput::*;
         match output {
             DestroySlave(index) => Some(AppMsg::DestroySlave(index)),
+            Promote(index) => Some(AppMsg::PromoteSlave(index)),
         }
     }
 }
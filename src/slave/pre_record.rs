@@ -0,0 +1,123 @@
+use std::{collections::VecDeque, sync::Mutex, time::Duration};
+
+use gst::{prelude::*, ClockTime};
+
+use super::video_error::VideoError;
+
+#[derive(Debug)]
+struct PreRecordTarget {
+    appsrc: gst_app::AppSrc,
+    base_time: ClockTime,
+}
+
+// 预录缓冲区，保存最近一段时间的压缩视频流，开始录制时从关键帧起写入文件
+#[derive(Debug)]
+pub struct PreRecordBuffer {
+    duration: ClockTime,
+    samples: Mutex<VecDeque<gst::Sample>>,
+    target: Mutex<Option<PreRecordTarget>>,
+}
+
+fn sample_time(sample: &gst::Sample) -> Option<ClockTime> {
+    sample
+        .buffer()
+        .and_then(|buffer| buffer.dts().or_else(|| buffer.pts()))
+}
+
+fn is_keyframe(sample: &gst::Sample) -> bool {
+    sample.buffer().map_or(false, |buffer| {
+        !buffer.flags().contains(gst::BufferFlags::DELTA_UNIT)
+    })
+}
+
+impl PreRecordBuffer {
+    pub fn new(duration: Duration) -> Self {
+        Self {
+            duration: ClockTime::from_nseconds(duration.as_nanos() as u64),
+            samples: Mutex::new(VecDeque::new()),
+            target: Mutex::new(None),
+        }
+    }
+
+    pub fn push(&self, sample: gst::Sample) {
+        let mut samples = self.samples.lock().unwrap();
+        let mut target = self.target.lock().unwrap();
+        // 录制管道已结束或出错时停止转发，不影响主管道
+        if let Some(Err(_)) = target.as_ref().map(|target| push_rebased(target, &sample)) {
+            target.take();
+        }
+        drop(target);
+        if let Some(latest) = sample_time(&sample) {
+            self.trim(&mut samples, latest);
+        }
+        samples.push_back(sample);
+    }
+
+    // 以画面组为单位丢弃超出时长的数据，保证缓冲区始终从关键帧开始
+    fn trim(&self, samples: &mut VecDeque<gst::Sample>, latest: ClockTime) {
+        let limit = latest.saturating_sub(self.duration);
+        let cut = samples
+            .iter()
+            .enumerate()
+            .skip(1)
+            .take_while(|(_, sample)| sample_time(sample).map_or(true, |time| time <= limit))
+            .filter(|(_, sample)| is_keyframe(sample))
+            .map(|(index, _)| index)
+            .last();
+        if let Some(cut) = cut {
+            samples.drain(..cut);
+        }
+        // 长时间没有关键帧时也不能无限增长
+        let hard_limit = limit.saturating_sub(self.duration);
+        while samples
+            .front()
+            .and_then(sample_time)
+            .map_or(false, |time| time < hard_limit)
+        {
+            samples.pop_front();
+        }
+    }
+
+    // 先写入缓冲区中从首个关键帧开始的数据，之后的新数据直接转发给录制管道
+    pub fn start_recording(&self, appsrc: &gst_app::AppSrc) -> Result<(), VideoError> {
+        let samples = self.samples.lock().unwrap();
+        let mut buffered = samples.iter().skip_while(|sample| !is_keyframe(sample));
+        let base_time = buffered
+            .clone()
+            .next()
+            .and_then(sample_time)
+            .unwrap_or(ClockTime::ZERO);
+        let target = PreRecordTarget {
+            appsrc: appsrc.clone(),
+            base_time,
+        };
+        buffered.try_for_each(|sample| push_rebased(&target, sample))?;
+        *self.target.lock().unwrap() = Some(target);
+        Ok(())
+    }
+
+    pub fn stop_recording(&self) {
+        self.target.lock().unwrap().take();
+    }
+}
+
+// 将时间戳平移到从 0 开始，使录制文件的起点对齐到首个关键帧
+fn push_rebased(target: &PreRecordTarget, sample: &gst::Sample) -> Result<(), VideoError> {
+    let caps = sample.caps().map(|caps| caps.to_owned());
+    if caps.is_some() && target.appsrc.caps() != caps {
+        target.appsrc.set_caps(caps.as_ref());
+    }
+    let mut buffer = sample.buffer_owned().ok_or(VideoError::PushBuffer)?;
+    {
+        let buffer = buffer.make_mut();
+        let pts = buffer.pts().map(|pts| pts.saturating_sub(target.base_time));
+        let dts = buffer.dts().map(|dts| dts.saturating_sub(target.base_time));
+        buffer.set_pts(pts);
+        buffer.set_dts(dts);
+    }
+    target
+        .appsrc
+        .push_buffer(buffer)
+        .map_err(|_| VideoError::PushBuffer)?;
+    Ok(())
+}
@@ -0,0 +1,128 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use relm4::gtk::{gdk_pixbuf::Pixbuf, glib};
+
+use super::{exif, video_ext::ImageFormat};
+
+// 随截图一同保存的信息
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScreenshotMetadata {
+    pub slave: String,
+    pub timestamp: String,
+    pub video_url: String,
+    pub infos: BTreeMap<String, String>,
+}
+
+// 一次截图请求，连拍时每一张沿用同一请求并递增序号
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScreenshotRequest {
+    pub path: PathBuf,
+    pub format: ImageFormat,
+    pub metadata: ScreenshotMetadata,
+    pub burst_count: u32,
+    pub sequence: u32,
+}
+
+impl ScreenshotRequest {
+    pub fn new(
+        path: PathBuf,
+        format: ImageFormat,
+        metadata: ScreenshotMetadata,
+        burst_count: u32,
+    ) -> Self {
+        Self {
+            path,
+            format,
+            metadata,
+            burst_count: burst_count.max(1),
+            sequence: 0,
+        }
+    }
+
+    pub fn is_last(&self) -> bool {
+        self.sequence + 1 >= self.burst_count
+    }
+
+    pub fn next(&self) -> Option<Self> {
+        (!self.is_last()).then(|| Self {
+            sequence: self.sequence + 1,
+            ..self.clone()
+        })
+    }
+
+    // 连拍时在文件名后追加序号，原始画面追加 _raw
+    pub fn frame_path(&self, raw: bool) -> PathBuf {
+        let stem = self
+            .path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let mut name = stem;
+        if self.burst_count > 1 {
+            name.push_str(&format!("_{:02}", self.sequence + 1));
+        }
+        if raw {
+            name.push_str("_raw");
+        }
+        self.path
+            .with_file_name(format!("{}.{}", name, self.format.extension()))
+    }
+}
+
+// PNG 将信息写入文本块，JPEG 与 TIFF 写入 EXIF；BMP 格式没有可存放元数据的位置
+pub fn save_screenshot(
+    pixbuf: &Pixbuf,
+    path: &Path,
+    format: ImageFormat,
+    jpeg_quality: u8,
+    metadata: &ScreenshotMetadata,
+) -> Result<(), glib::Error> {
+    let type_ = format.to_string().to_lowercase();
+    match format {
+        ImageFormat::PNG => {
+            let mut texts = vec![
+                ("tEXt::Source".to_string(), metadata.slave.clone()),
+                (
+                    "tEXt::Creation Time".to_string(),
+                    metadata.timestamp.clone(),
+                ),
+                ("tEXt::URL".to_string(), metadata.video_url.clone()),
+            ];
+            texts.extend(
+                metadata
+                    .infos
+                    .iter()
+                    .map(|(key, value)| (format!("tEXt::{}", key), value.clone())),
+            );
+            let options: Vec<(&str, &str)> = texts
+                .iter()
+                .map(|(key, value)| (key.as_str(), value.as_str()))
+                .collect();
+            pixbuf.savev(path, &type_, &options)
+        }
+        ImageFormat::JPEG => {
+            let quality = jpeg_quality.clamp(1, 100).to_string();
+            let jpeg = pixbuf.save_to_bufferv(&type_, &[("quality", &quality)])?;
+            let jpeg = exif::add_to_jpeg(&jpeg, metadata).ok_or_else(exif_error)?;
+            write_file(path, jpeg)
+        }
+        ImageFormat::TIFF => {
+            let mut tiff = pixbuf.save_to_bufferv(&type_, &[])?;
+            exif::add_to_tiff(&mut tiff, metadata).ok_or_else(exif_error)?;
+            write_file(path, tiff)
+        }
+        ImageFormat::BMP => pixbuf.savev(path, &type_, &[]),
+    }
+}
+
+fn exif_error() -> glib::Error {
+    glib::Error::new(glib::FileError::Failed, "无法写入 EXIF 信息")
+}
+
+fn write_file(path: &Path, data: Vec<u8>) -> Result<(), glib::Error> {
+    fs::write(path, data).map_err(|err| glib::Error::new(glib::FileError::Failed, &err.to_string()))
+}
@@ -0,0 +1,118 @@
+use std::net::{IpAddr, Ipv4Addr};
+
+use url::{Host, Url};
+
+// 去掉 URL 中的用户名与密码，用于界面提示和写入文件的元数据
+pub fn without_credentials(url: &Url) -> Url {
+    let mut url = url.clone();
+    url.set_username("").ok();
+    url.set_password(None).ok();
+    url
+}
+
+// 将 URL 中的 IPv4 地址累加 offset，主机不是 IPv4 地址或地址溢出时返回 None
+pub fn increment_ipv4_host(url: &Url, offset: u32) -> Option<Url> {
+    let address = match url.host()? {
+        Host::Ipv4(address) => address,
+        _ => return None,
+    };
+    let address = Ipv4Addr::from(u32::from(address).checked_add(offset)?);
+    let mut url = url.clone();
+    url.set_ip_host(IpAddr::V4(address)).ok()?;
+    Some(url)
+}
+
+// 将 URL 中的端口累加 offset，未指定端口或端口溢出时返回 None
+pub fn increment_port(url: &Url, offset: u32) -> Option<Url> {
+    let port = u16::try_from(u32::from(url.port_or_known_default()?).checked_add(offset)?).ok()?;
+    let mut url = url.clone();
+    url.set_port(Some(port)).ok()?;
+    Some(url)
+}
+
+// 第 index 个机位（从 0 开始）使用的 URL，无法按间隔累加时返回 None，由调用方提示用户
+pub fn slave_url(default: &Url, index: usize, stride: u32) -> Option<Url> {
+    let offset = u32::try_from(index).ok()?.checked_mul(stride)?;
+    if offset == 0 {
+        return Some(default.clone());
+    }
+    increment_ipv4_host(default, offset)
+}
+
+pub fn video_url(default: &Url, index: usize, stride: u32) -> Option<Url> {
+    let offset = u32::try_from(index).ok()?.checked_mul(stride)?;
+    if offset == 0 {
+        return Some(default.clone());
+    }
+    increment_port(default, offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn url(url: &str) -> Url {
+        Url::from_str(url).unwrap()
+    }
+
+    #[test]
+    fn increments_ipv4_host_and_port() {
+        let default = url("tcp://192.168.1.100:8888");
+        assert_eq!(
+            slave_url(&default, 2, 1),
+            Some(url("tcp://192.168.1.102:8888"))
+        );
+        assert_eq!(
+            video_url(&url("rtp://0.0.0.0:5600"), 3, 2),
+            Some(url("rtp://0.0.0.0:5606"))
+        );
+    }
+
+    #[test]
+    fn ipv4_overflow() {
+        let default = url("tcp://255.255.255.255:8888");
+        assert_eq!(slave_url(&default, 0, 1), Some(default.clone()));
+        assert_eq!(slave_url(&default, 1, 1), None);
+        assert_eq!(increment_ipv4_host(&default, 1), None);
+    }
+
+    #[test]
+    fn domain_host() {
+        let default = url("tcp://rov.local:8888");
+        assert_eq!(slave_url(&default, 0, 1), Some(default.clone()));
+        assert_eq!(slave_url(&default, 1, 1), None);
+    }
+
+    #[test]
+    fn ipv6_host() {
+        let default = url("tcp://[fe80::1]:8888");
+        assert_eq!(slave_url(&default, 1, 1), None);
+    }
+
+    #[test]
+    fn port_overflow() {
+        let default = url("rtp://0.0.0.0:65535");
+        assert_eq!(video_url(&default, 0, 1), Some(default.clone()));
+        assert_eq!(video_url(&default, 1, 1), None);
+        assert_eq!(increment_port(&default, 1), None);
+    }
+
+    #[test]
+    fn missing_port() {
+        let default = url("udp://192.168.1.100");
+        assert_eq!(video_url(&default, 0, 1), Some(default.clone()));
+        assert_eq!(video_url(&default, 1, 1), None);
+    }
+
+    #[test]
+    fn zero_stride_keeps_default() {
+        let slave = url("tcp://192.168.1.100:8888");
+        let video = url("rtp://0.0.0.0:5600");
+        for index in 0..4 {
+            assert_eq!(slave_url(&slave, index, 0), Some(slave.clone()));
+            assert_eq!(video_url(&video, index, 0), Some(video.clone()));
+        }
+    }
+}
@@ -1,4 +1,8 @@
-use std::{path::PathBuf, sync::Mutex};
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 use adw::{gtk::ContentFit, prelude::*, StatusPage};
 use gst::{
@@ -9,10 +13,11 @@ use gst::{
 };
 use relm4::{
     gtk::{
+        gdk,
         gdk_pixbuf::Pixbuf,
         glib,
-        glib::{clone, prelude::*},
-        Box as GtkBox, Picture, Stack,
+        glib::{clone, prelude::*, DateTime, SourceId},
+        Align, Box as GtkBox, DrawingArea, Image, Label, Orientation, Overlay, Picture, Stack,
     },
     prelude::*,
     ComponentParts,
@@ -24,21 +29,73 @@ use crate::{
 };
 
 use super::video_ext::*;
-use super::{async_glib::Promise, config::SlaveConfigModel};
+use super::{
+    async_glib::Promise,
+    config::SlaveConfigModel,
+    frame_error::FrameError,
+    frame_mailbox::FrameMailbox,
+    frame_worker::FrameWorker,
+    hud::{burn_hud, contain_rect, current_clock, draw_hud, HudData},
+    pre_record::PreRecordBuffer,
+    screenshot::{save_screenshot, ScreenshotRequest},
+    video_error::VideoError,
+    video_processor::FrameProcessorChain,
+    video_stats::VideoStatsCounter,
+};
 
 pub struct SlaveVideoInit {
     pub preferences: PreferencesModel,
     pub config: SlaveConfigModel,
 }
 
+#[derive(Debug)]
+pub enum RecordHandle {
+    Tee((gst::Element, gst::Pad), Vec<gst::Element>), // 从主管道的 tee 分出的录制分支
+    Processed(Pipeline, gst_app::AppSrc),             // 录制增强后画面的独立管道
+    PreRecorded(Pipeline, gst_app::AppSrc),           // 包含预录画面的独立管道
+}
+
 #[tracker::track]
 #[derive(Debug)]
 pub struct SlaveVideoModel {
     #[no_eq]
-    pub record_handle: Option<((gst::Element, gst::Pad), Vec<gst::Element>)>,
+    pub record_handle: Option<RecordHandle>,
     #[no_eq]
     pub pixbuf: Option<Pixbuf>,
+    #[no_eq]
+    pub paintable: Option<gdk::Paintable>,
     pipeline: Option<Pipeline>,
+    #[do_not_track]
+    frame_mailbox: Option<Arc<FrameMailbox<opencv::core::Mat>>>,
+    #[do_not_track]
+    frame_worker: Option<FrameWorker>,
+    #[do_not_track]
+    pre_record_buffer: Option<Arc<PreRecordBuffer>>,
+    #[do_not_track]
+    stats_counter: Option<Arc<VideoStatsCounter>>,
+    #[do_not_track]
+    stats_timer: Option<SourceId>,
+    #[do_not_track]
+    bus_watch: Option<SourceId>,
+    #[do_not_track]
+    restart_timer: Option<SourceId>,
+    #[do_not_track]
+    watchdog_timer: Option<SourceId>,
+    #[do_not_track]
+    restart_attempts: u32,
+    #[do_not_track]
+    pending_restart: Option<Duration>,
+    #[do_not_track]
+    stop_generation: u64,
+    buffering: Option<i32>,
+    signal_lost: bool,
+    hud_data: HudData,
+    #[do_not_track]
+    hud_shared: Arc<Mutex<HudData>>,
+    #[do_not_track]
+    composite_feed: Arc<Mutex<Option<gst_app::AppSrc>>>,
+    stats_displayed: bool,
+    stats_text: String,
     #[no_eq]
     slave_config: SlaveConfigModel,
     preferences: PreferencesModel,
@@ -49,13 +106,24 @@ pub enum SlaveVideoInput {
     StartPipeline,
     StopPipeline,
     EnsurePipelineStopped,
+    PipelineStopped(u64, Option<Duration>),
     SetPixbuf(Option<Pixbuf>),
-    StartRecord(PathBuf),
+    StartRecord(PathBuf, RecordMetadata),
     StopRecord(Option<Promise<()>>),
     UpdatePreferences(PreferencesModel),
     UpdateConfig(SlaveConfigModel),
-    SaveScreenshot(PathBuf),
+    SaveScreenshot(ScreenshotRequest),
     RequestFrame,
+    SetStatsDisplayed(bool),
+    RefreshStats,
+    PipelineError(String),
+    PipelineWarning(String),
+    PipelineEos,
+    SetBuffering(i32),
+    RestartPipeline,
+    CheckStall,
+    SetHudData(HudData),
+    SetCompositeFeed(Option<gst_app::AppSrc>),
 }
 
 #[derive(Debug)]
@@ -70,26 +138,71 @@ pub enum SlaveVideoOutput {
 impl SimpleComponent for SlaveVideoModel {
     view! {
         frame = GtkBox {
-            append = &Stack {
-                set_vexpand: true,
-                set_hexpand: true,
-                add_child = &StatusPage {
-                    set_icon_name: Some("face-uncertain-symbolic"),
-                    set_title: "无信号",
-                    set_description: Some("请点击上方按钮启动视频拉流"),
-                    #[track = "model.changed(SlaveVideoModel::pixbuf())"]
-                    set_visible: model.pixbuf == None,
-                },
-                add_child = &Picture {
-                    set_hexpand: true,
+            append = &Overlay {
+                #[wrap(Some)]
+                set_child = &Stack {
                     set_vexpand: true,
-                    set_can_shrink: true,
+                    set_hexpand: true,
+                    add_child = &StatusPage {
+                        set_icon_name: Some("face-uncertain-symbolic"),
+                        set_title: "无信号",
+                        set_description: Some("请点击上方按钮启动视频拉流"),
+                        #[track = "model.changed(SlaveVideoModel::pixbuf()) || model.changed(SlaveVideoModel::paintable())"]
+                        set_visible: !model.has_frame(),
+                    },
+                    add_child: picture = &Picture {
+                        set_hexpand: true,
+                        set_vexpand: true,
+                        set_can_shrink: true,
+                        #[track = "model.changed(SlaveVideoModel::slave_config())"]
+                        set_content_fit: if *model.slave_config.get_keep_video_display_ratio() {ContentFit::Contain} else {ContentFit::Fill},
+                        #[track = "model.changed(SlaveVideoModel::pixbuf()) || model.changed(SlaveVideoModel::paintable())"]
+                        set_paintable: model.current_paintable().as_ref(),
+                    },
+                },
+                add_overlay: hud_area = &DrawingArea {
+                    set_can_target: false,
                     #[track = "model.changed(SlaveVideoModel::slave_config())"]
-                    set_content_fit: if *model.slave_config.get_keep_video_display_ratio() {ContentFit::Contain} else {ContentFit::Fill},
-                    #[track = "model.changed(SlaveVideoModel::pixbuf())"]
-                    set_pixbuf: match &model.pixbuf {
-                        Some(pixbuf) => Some(&pixbuf),
-                        None => None,
+                    set_visible: *model.slave_config.get_hud_displayed(),
+                },
+                add_overlay = &Label {
+                    set_halign: Align::Start,
+                    set_valign: Align::End,
+                    set_margin_all: 10,
+                    set_css_classes: &["osd", "monospace"],
+                    #[track = "model.changed(SlaveVideoModel::stats_displayed()) || model.changed(SlaveVideoModel::stats_text())"]
+                    set_visible: model.stats_displayed && !model.stats_text.is_empty(),
+                    #[track = "model.changed(SlaveVideoModel::stats_text())"]
+                    set_label: &model.stats_text,
+                },
+                add_overlay = &Label {
+                    set_halign: Align::Center,
+                    set_valign: Align::Start,
+                    set_margin_all: 10,
+                    set_css_classes: &["osd"],
+                    #[track = "model.changed(SlaveVideoModel::buffering())"]
+                    set_visible: model.buffering.is_some(),
+                    #[track = "model.changed(SlaveVideoModel::buffering())"]
+                    set_label: &model.buffering.map_or_else(String::new, |percent| format!("缓冲中 {}%", percent)),
+                },
+                add_overlay = &GtkBox {
+                    set_orientation: Orientation::Vertical,
+                    set_halign: Align::Center,
+                    set_valign: Align::Center,
+                    set_spacing: 5,
+                    set_css_classes: &["osd"],
+                    #[track = "model.changed(SlaveVideoModel::signal_lost())"]
+                    set_visible: model.signal_lost,
+                    append = &Image {
+                        set_icon_name: Some("network-error-symbolic"),
+                        set_pixel_size: 48,
+                        set_margin_top: 10,
+                    },
+                    append = &Label {
+                        set_label: "信号丢失，正在尝试重新拉流",
+                        set_margin_start: 10,
+                        set_margin_end: 10,
+                        set_margin_bottom: 10,
                     },
                 },
             },
@@ -109,14 +222,65 @@ impl SimpleComponent for SlaveVideoModel {
             preferences: init.preferences,
             slave_config: init.config,
             pixbuf: None,
+            paintable: None,
             pipeline: None,
+            frame_mailbox: None,
+            frame_worker: None,
+            pre_record_buffer: None,
+            stats_counter: None,
+            stats_timer: None,
+            bus_watch: None,
+            restart_timer: None,
+            watchdog_timer: None,
+            restart_attempts: 0,
+            pending_restart: None,
+            stop_generation: 0,
+            buffering: None,
+            signal_lost: false,
+            hud_data: HudData::default(),
+            hud_shared: Arc::new(Mutex::new(HudData::default())),
+            composite_feed: Arc::new(Mutex::new(None)),
+            stats_displayed: false,
+            stats_text: String::new(),
             record_handle: None,
             tracker: 0,
         };
         let widgets = view_output!();
+        let hud = model.hud_shared.clone();
+        widgets.hud_area.set_draw_func(
+            clone!(@weak widgets.picture as picture => move |_area, cr, width, height| {
+                let (width, height) = (width as f64, height as f64);
+                // 保持比例显示时画面四周留有黑边，HUD 只绘制在画面区域内
+                let (x, y, width, height) = match picture.paintable() {
+                    Some(paintable) if picture.content_fit() == ContentFit::Contain => {
+                        contain_rect(width, height, paintable.intrinsic_aspect_ratio())
+                    }
+                    _ => (0.0, 0.0, width, height),
+                };
+                let mut cr = cr.clone();
+                cr.translate(x, y);
+                draw_hud(&mut cr, width, height, &hud.lock().unwrap(), &current_clock()).ok();
+            }),
+        );
+        // 每秒刷新一次以更新时钟
+        glib::timeout_add_seconds_local(
+            1,
+            clone!(@weak widgets.hud_area as hud_area => @default-return Continue(false), move || {
+                if hud_area.is_visible() {
+                    hud_area.queue_draw();
+                }
+                Continue(true)
+            }),
+        );
         ComponentParts { model, widgets }
     }
 
+    fn post_view() {
+        if model.changed(SlaveVideoModel::hud_data()) {
+            hud_area.queue_draw();
+        }
+    }
+
     fn update(&mut self, message: Self::Input, sender: ComponentSender<Self>) {
         self.reset();
 
@@ -124,52 +288,175 @@ impl SimpleComponent for SlaveVideoModel {
         match message {
             StartPipeline => {
                 assert!(self.pipeline == None);
+                if let Some(timer) = self.restart_timer.take() {
+                    timer.remove();
+                }
                 let config = self.get_slave_config();
                 let video_url = config.get_video_url();
                 if let Some(video_source) = VideoSource::from_url(video_url) {
                     let video_decoder = config.get_video_decoder().clone();
-                    let colorspace_conversion = config.get_colorspace_conversion().clone();
+                    let colorspace_conversion = available_colorspace_conversion(
+                        *config.get_colorspace_conversion(),
+                        &sender,
+                    );
+                    let display_backend = available_display_backend(config, &sender);
                     let use_decodebin = config.get_use_decodebin().clone();
                     let appsink_leaky_enabled = config.get_appsink_queue_leaky_enabled().clone();
                     let latency = config.get_video_latency().clone();
 
                     match if use_decodebin {
-                        super::video::create_decodebin_pipeline(video_source, appsink_leaky_enabled)
+                        super::video::create_decodebin_pipeline(
+                            video_source,
+                            colorspace_conversion,
+                            display_backend,
+                            appsink_leaky_enabled,
+                        )
                     } else {
                         super::video::create_pipeline(
                             video_source,
                             latency,
                             colorspace_conversion,
                             video_decoder,
+                            display_backend,
                             appsink_leaky_enabled,
                         )
                     } {
                         Ok(pipeline) => {
                             let sender = sender.clone();
-                            let (mat_sender, mat_receiver) =
-                                MainContext::channel(glib::PRIORITY_DEFAULT);
-                            super::video::attach_pipeline_callback(
-                                &pipeline,
-                                mat_sender,
-                                self.get_slave_config(),
-                            )
-                            .unwrap();
-                            {
-                                let sender = sender.clone();
-                                mat_receiver.attach(None, move |mat| {
-                                    sender.input(SlaveVideoInput::SetPixbuf(Some(mat.as_pixbuf())));
-                                    Continue(true)
-                                });
+                            match display_backend {
+                                VideoDisplayBackend::Pixbuf => {
+                                    let mailbox = Arc::new(FrameMailbox::new());
+                                    let (waker, wake_receiver) =
+                                        MainContext::channel(glib::PRIORITY_DEFAULT);
+                                    let (error_sender, error_receiver) =
+                                        MainContext::channel::<FrameError>(glib::PRIORITY_DEFAULT);
+                                    let worker = FrameWorker::spawn(
+                                        FrameProcessorChain::new(
+                                            &self.get_slave_config().video_algorithms,
+                                        ),
+                                        mailbox.clone(),
+                                        waker,
+                                        error_sender,
+                                    );
+                                    worker.set_hud(self.burned_hud());
+                                    worker
+                                        .set_keep_raw(*self.preferences.get_screenshot_save_raw());
+                                    let output_sender = sender.output_sender().clone();
+                                    error_receiver.attach(None, move |error| {
+                                        output_sender
+                                            .send(SlaveVideoOutput::ErrorMessage(error.to_string()))
+                                            .unwrap();
+                                        Continue(true)
+                                    });
+                                    if let Err(msg) = super::video::attach_pipeline_callback(
+                                        &pipeline,
+                                        worker.input(),
+                                        self.get_slave_config(),
+                                    ) {
+                                        sender
+                                            .output(SlaveVideoOutput::ErrorMessage(msg.to_string()))
+                                            .unwrap();
+                                    }
+                                    let sender = sender.clone();
+                                    let mut last_error: Option<FrameError> = None;
+                                    wake_receiver.attach(
+                                        None,
+                                        clone!(@strong mailbox => move |_| {
+                                            match mailbox.take().map(|mat| mat.as_pixbuf()) {
+                                                Some(Ok(pixbuf)) => {
+                                                    last_error = None;
+                                                    sender.input(SlaveVideoInput::SetPixbuf(Some(pixbuf)));
+                                                }
+                                                Some(Err(error)) if last_error.as_ref() != Some(&error) => {
+                                                    sender.output(SlaveVideoOutput::ErrorMessage(error.to_string())).unwrap();
+                                                    last_error = Some(error);
+                                                }
+                                                _ => (),
+                                            }
+                                            Continue(true)
+                                        }),
+                                    );
+                                    self.frame_mailbox = Some(mailbox);
+                                    self.frame_worker = Some(worker);
+                                }
+                                VideoDisplayBackend::Paintable => {
+                                    let paintable = pipeline
+                                        .by_name("display")
+                                        .unwrap()
+                                        .property::<gdk::Paintable>("paintable");
+                                    self.set_pixbuf(None);
+                                    self.set_paintable(Some(paintable));
+                                }
+                            }
+                            let pre_record_duration = *self.slave_config.get_pre_record_duration();
+                            if pre_record_duration > 0 && !use_decodebin {
+                                let buffer = Arc::new(PreRecordBuffer::new(Duration::from_secs(
+                                    pre_record_duration as u64,
+                                )));
+                                match super::video::attach_pre_record_buffer(
+                                    &pipeline,
+                                    buffer.clone(),
+                                ) {
+                                    Ok(_) => self.pre_record_buffer = Some(buffer),
+                                    Err(err) => sender
+                                        .output(SlaveVideoOutput::ErrorMessage(err.to_string()))
+                                        .unwrap(),
+                                }
+                            }
+                            if self.composite_feed.lock().unwrap().is_some() {
+                                if let Err(err) = super::video::attach_composite_feed(
+                                    &pipeline,
+                                    colorspace_conversion,
+                                    self.composite_feed.clone(),
+                                ) {
+                                    sender
+                                        .output(SlaveVideoOutput::ErrorMessage(err.to_string()))
+                                        .unwrap();
+                                }
                             }
+                            let stats_counter = Arc::new(VideoStatsCounter::new());
+                            stats_counter.attach(&pipeline);
+                            self.stats_counter = Some(stats_counter);
                             match pipeline.set_state(gst::State::Playing) {
                                 Ok(_) => {
+                                    self.bus_watch = watch_pipeline_bus(
+                                        &pipeline,
+                                        sender.input_sender().clone(),
+                                    );
                                     self.set_pipeline(Some(pipeline));
+                                    let input_sender = sender.input_sender().clone();
+                                    self.stats_timer = Some(glib::timeout_add_local(
+                                        Duration::from_secs(1),
+                                        move || {
+                                            input_sender
+                                                .send(SlaveVideoInput::RefreshStats)
+                                                .unwrap();
+                                            Continue(true)
+                                        },
+                                    ));
+                                    if *self.slave_config.get_stall_timeout() > 0 {
+                                        let input_sender = sender.input_sender().clone();
+                                        self.watchdog_timer = Some(glib::timeout_add_local(
+                                            Duration::from_millis(500),
+                                            move || {
+                                                input_sender
+                                                    .send(SlaveVideoInput::CheckStall)
+                                                    .unwrap();
+                                                Continue(true)
+                                            },
+                                        ));
+                                    }
                                     sender
                                         .output(SlaveVideoOutput::PollingChanged(true))
                                         .unwrap();
                                 }
                                 Err(_) => {
-                                    sender.output(SlaveVideoOutput::ErrorMessage(String::from("无法启动管道，这可能是由于管道使用的资源不存在或被占用导致的，请检查相关资源是否可用。"))).unwrap();
+                                    sender
+                                        .output(SlaveVideoOutput::ErrorMessage(
+                                            VideoError::StateChange(gst::State::Playing)
+                                                .to_string(),
+                                        ))
+                                        .unwrap();
                                     sender
                                         .output(SlaveVideoOutput::PollingChanged(false))
                                         .unwrap();
@@ -178,7 +465,7 @@ impl SimpleComponent for SlaveVideoModel {
                         }
                         Err(msg) => {
                             sender
-                                .output(SlaveVideoOutput::ErrorMessage(String::from(msg)))
+                                .output(SlaveVideoOutput::ErrorMessage(msg.to_string()))
                                 .unwrap();
                             sender
                                 .output(SlaveVideoOutput::PollingChanged(false))
@@ -187,31 +474,46 @@ impl SimpleComponent for SlaveVideoModel {
                     }
                 } else {
                     sender
-                        .output(SlaveVideoOutput::ErrorMessage(String::from(
-                            "拉流 URL 有误，请检查并修改后重试。",
-                        )))
+                        .output(SlaveVideoOutput::ErrorMessage(
+                            VideoError::invalid_url(video_url, "仅支持 rtp、udp 与 rtsp 协议")
+                                .to_string(),
+                        ))
                         .unwrap();
                     sender
                         .output(SlaveVideoOutput::PollingChanged(false))
                         .unwrap();
                 }
+                // 信号丢失后重新拉流失败时继续按退避间隔重试
+                if self.pipeline.is_none() && self.signal_lost {
+                    let delay = self.next_restart_delay();
+                    self.schedule_restart(&sender, delay);
+                }
             }
             StopPipeline => {
                 assert!(self.pipeline != None);
+                if let Some(timer) = self.restart_timer.take() {
+                    timer.remove();
+                }
+                // 由看门狗发起的停止在旧管道释放后再重新拉流，手动停止则放弃重试
+                if self.pending_restart.is_none() {
+                    self.restart_attempts = 0;
+                    self.set_signal_lost(false);
+                }
                 let recording = self.is_recording();
                 if recording {
                     let promise = Promise::new();
                     let future = promise.future();
                     sender.input(SlaveVideoInput::StopRecord(Some(promise)));
                     let input_sender = sender.input_sender().clone();
-                    let(event_sender, event_receiver) = glib::MainContext::channel::<()>(glib::PRIORITY_DEFAULT);
+                    let (event_sender, event_receiver) =
+                        glib::MainContext::channel::<()>(glib::PRIORITY_DEFAULT);
                     event_receiver.attach(None, move |_| {
-                        input_sender.send(SlaveVideoInput::EnsurePipelineStopped).unwrap();
+                        input_sender
+                            .send(SlaveVideoInput::EnsurePipelineStopped)
+                            .unwrap();
                         Continue(false)
                     });
-                    future.for_each(move |_| {
-                        event_sender.send(()).unwrap()
-                    });
+                    future.for_each(move |_| event_sender.send(()).unwrap());
                 } else {
                     sender.input(SlaveVideoInput::EnsurePipelineStopped);
                 }
@@ -220,7 +522,11 @@ impl SimpleComponent for SlaveVideoModel {
                 let promise = Promise::new();
                 let future = promise.future();
                 let promise = Mutex::new(Some(promise));
-                let recording = self.is_recording();
+                self.release_pipeline_resources();
+                // 停止过程中可能已启动新管道，旧管道的回调通过序号识别并忽略
+                self.stop_generation += 1;
+                let generation = self.stop_generation;
+                let input_sender = sender.input_sender().clone();
                 if let Some(pipeline) = self.pipeline.take() {
                     let sinkpad = pipeline
                         .by_name("display")
@@ -243,44 +549,63 @@ impl SimpleComponent for SlaveVideoModel {
                     if pipeline.current_state() == gst::State::Playing
                         && pipeline.send_event(gst::event::Eos::new())
                     {
-                        let sender = sender.output_sender().clone();
                         future.for_each(
-                            clone!(@weak pipeline, @strong sender => move |_| {
-                                sender.send(SlaveVideoOutput::PollingChanged(false)).unwrap();
-                                pipeline.set_state(gst::State::Null).unwrap();
+                            clone!(@weak pipeline, @strong input_sender => move |_| {
+                                pipeline.set_state(gst::State::Null).ok();
+                                input_sender.send(SlaveVideoInput::PipelineStopped(generation, None)).unwrap();
                             }),
                         );
+                        // 超时回调持有管道，保证管道在收到 EOS 或超时之前不会被释放
+                        let timeout = *self.preferences.get_pipeline_timeout();
                         glib::timeout_add_local_once(
-                            self.preferences.get_pipeline_timeout().clone(),
-                            clone!(@weak pipeline,@strong sender => move || {
-                                sender.send(SlaveVideoOutput::PollingChanged(false)).unwrap();
-                                if recording {
-                                    sender.send(SlaveVideoOutput::RecordingChanged(false)).unwrap();
-                                }
-                                sender.send(SlaveVideoOutput::ShowToastMessage(String::from("等待管道响应超时，已将其强制终止。"))).unwrap();
-                                pipeline.set_state(gst::State::Null).unwrap();
+                            timeout,
+                            clone!(@strong pipeline, @strong input_sender => move || {
+                                pipeline.set_state(gst::State::Null).ok();
+                                input_sender.send(SlaveVideoInput::PipelineStopped(generation, Some(timeout))).unwrap();
                             }),
                         );
                     } else {
-                        sender
-                            .output(SlaveVideoOutput::PollingChanged(false))
-                            .unwrap();
-                        sender
-                            .output(SlaveVideoOutput::RecordingChanged(false))
-                            .unwrap();
-                        pipeline.set_state(gst::State::Null).unwrap();
+                        pipeline.set_state(gst::State::Null).ok();
+                        sender.input(SlaveVideoInput::PipelineStopped(generation, None));
                     }
                 }
             }
+            PipelineStopped(generation, timeout) => {
+                // 收到 EOS 后超时回调仍会触发，只处理每次停止的第一个结果
+                if generation != self.stop_generation {
+                    return;
+                }
+                self.stop_generation += 1;
+                if let Some(timeout) = timeout {
+                    sender
+                        .output(SlaveVideoOutput::ShowToastMessage(
+                            VideoError::Timeout(timeout).to_string(),
+                        ))
+                        .unwrap();
+                }
+                // 新管道已经启动时不再更新拉流与录制状态
+                if self.pipeline.is_none() {
+                    sender
+                        .output(SlaveVideoOutput::PollingChanged(false))
+                        .unwrap();
+                    sender
+                        .output(SlaveVideoOutput::RecordingChanged(false))
+                        .unwrap();
+                }
+                if let Some(delay) = self.pending_restart.take() {
+                    self.schedule_restart(&sender, delay);
+                }
+            }
             SetPixbuf(pixbuf) => {
                 if self.get_pixbuf().is_none() {
                     sender
                         .output(SlaveVideoOutput::PollingChanged(true))
                         .unwrap(); // 主要是更新截图按钮的状态
                 }
+                self.set_paintable(None);
                 self.set_pixbuf(pixbuf)
             }
-            StartRecord(pathbuf) => {
+            StartRecord(pathbuf, metadata) => {
                 let config = self.get_slave_config();
                 if let Some(pipeline) = &self.pipeline {
                     let encoder = if *config.get_reencode_recording_video() {
@@ -288,12 +613,42 @@ impl SimpleComponent for SlaveVideoModel {
                     } else {
                         None
                     };
-                    let colorspace_conversion = config.get_colorspace_conversion().clone();
-                    let record_handle = match encoder {
-                        Some(encoder) => {
+                    let colorspace_conversion = available_colorspace_conversion(
+                        *config.get_colorspace_conversion(),
+                        &sender,
+                    );
+                    let options = RecordOptions {
+                        container: *config.get_video_container(),
+                        segmentation: self.preferences.record_segmentation(),
+                        metadata,
+                    };
+                    let record_processed =
+                        encoder.is_some() && *config.get_record_processed_video();
+                    if record_processed && self.frame_worker.is_none() {
+                        sender
+                            .output(SlaveVideoOutput::ShowToastMessage(String::from(
+                                "当前显示方式不经过增强处理，将录制原始画面。",
+                            )))
+                            .unwrap();
+                    }
+                    let record_handle = match (encoder, &self.frame_worker) {
+                        (Some(encoder), Some(worker)) if record_processed => {
+                            super::video::create_processed_record_pipeline(
+                                encoder,
+                                colorspace_conversion,
+                                &pathbuf.to_str().unwrap(),
+                                &options,
+                            )
+                            .map(|(record_pipeline, appsrc)| {
+                                worker.set_recorder(Some(appsrc.clone()));
+                                RecordHandle::Processed(record_pipeline, appsrc)
+                            })
+                        }
+                        (Some(encoder), _) => {
                             let elements = encoder.gst_record_elements(
                                 colorspace_conversion,
                                 &pathbuf.to_str().unwrap(),
+                                &options,
                             );
                             elements.and_then(|elements| {
                                 super::video::connect_elements_to_pipeline(
@@ -301,26 +656,38 @@ impl SimpleComponent for SlaveVideoModel {
                                     "tee_decoded",
                                     &elements,
                                 )
-                                .map(|pad| (elements, pad))
+                                .map(|pad| RecordHandle::Tee(pad, elements))
+                            })
+                        }
+                        (None, _) if self.pre_record_buffer.is_some() => {
+                            let buffer = self.pre_record_buffer.as_ref().unwrap();
+                            super::video::create_pre_record_pipeline(
+                                &config.video_decoder,
+                                &pathbuf.to_str().unwrap(),
+                                &options,
+                                buffer,
+                            )
+                            .map(|(record_pipeline, appsrc)| {
+                                RecordHandle::PreRecorded(record_pipeline, appsrc)
                             })
                         }
-                        None => {
+                        (None, _) => {
                             let elements = config
                                 .video_decoder
-                                .gst_record_elements(&pathbuf.to_str().unwrap());
+                                .gst_record_elements(&pathbuf.to_str().unwrap(), &options);
                             elements.and_then(|elements| {
                                 super::video::connect_elements_to_pipeline(
                                     pipeline,
                                     "tee_source",
                                     &elements,
                                 )
-                                .map(|pad| (elements, pad))
+                                .map(|pad| RecordHandle::Tee(pad, elements))
                             })
                         }
                     };
                     match record_handle {
-                        Ok((elements, pad)) => {
-                            self.record_handle = Some((pad, Vec::from(elements)));
+                        Ok(record_handle) => {
+                            self.record_handle = Some(record_handle);
                             sender
                                 .output(SlaveVideoOutput::RecordingChanged(true))
                                 .unwrap();
@@ -338,59 +705,301 @@ impl SimpleComponent for SlaveVideoModel {
             }
             StopRecord(promise) => {
                 if let Some(pipeline) = &self.pipeline {
-                    if let Some((teepad, elements)) = &self.record_handle {
+                    if let Some(record_handle) = &self.record_handle {
                         let sender = sender.output_sender().clone();
-                        super::video::disconnect_elements_to_pipeline(pipeline, teepad, elements)
-                            .unwrap()
-                            .for_each(move |_| {
+                        let stopped = match record_handle {
+                            RecordHandle::Tee(teepad, elements) => {
+                                super::video::disconnect_elements_to_pipeline(
+                                    pipeline, teepad, elements,
+                                )
+                            }
+                            RecordHandle::Processed(record_pipeline, appsrc) => {
+                                if let Some(worker) = &self.frame_worker {
+                                    worker.set_recorder(None);
+                                }
+                                super::video::stop_processed_record_pipeline(
+                                    record_pipeline,
+                                    appsrc,
+                                )
+                            }
+                            RecordHandle::PreRecorded(record_pipeline, appsrc) => {
+                                if let Some(buffer) = &self.pre_record_buffer {
+                                    buffer.stop_recording();
+                                }
+                                super::video::stop_processed_record_pipeline(
+                                    record_pipeline,
+                                    appsrc,
+                                )
+                            }
+                        };
+                        match stopped {
+                            Ok(future) => future.for_each(move |_| {
                                 sender
                                     .send(SlaveVideoOutput::RecordingChanged(false))
                                     .unwrap();
                                 if let Some(promise) = promise {
                                     promise.success(());
                                 }
-                            });
+                            }),
+                            Err(err) => {
+                                sender
+                                    .send(SlaveVideoOutput::ErrorMessage(err.to_string()))
+                                    .unwrap();
+                                sender
+                                    .send(SlaveVideoOutput::RecordingChanged(false))
+                                    .unwrap();
+                                if let Some(promise) = promise {
+                                    promise.success(());
+                                }
+                            }
+                        }
                     }
                     self.set_record_handle(None);
                 }
             }
-            UpdateConfig(config) => self.set_slave_config(config),
-            UpdatePreferences(preferences) => self.set_preferences(preferences),
-            SaveScreenshot(pathbuf) => {
-                assert!(self.pixbuf != None);
-                if let Some(pixbuf) = &self.pixbuf {
-                    let format = pathbuf
-                        .extension()
-                        .unwrap()
-                        .to_str()
-                        .and_then(ImageFormat::from_extension)
-                        .unwrap();
-                    match pixbuf.savev(&pathbuf, &format.to_string().to_lowercase(), &[]) {
-                        Ok(_) => sender
+            UpdateConfig(config) => {
+                if config.video_algorithms != self.slave_config.video_algorithms {
+                    if let Some(worker) = &self.frame_worker {
+                        worker.set_processor(FrameProcessorChain::new(&config.video_algorithms));
+                    }
+                }
+                // 显示方式由增强算法决定，运行中的管道与新设置不符时需要重建
+                let running_backend = if self.frame_worker.is_some() {
+                    VideoDisplayBackend::Pixbuf
+                } else {
+                    VideoDisplayBackend::Paintable
+                };
+                let backend_changed = self.pipeline.is_some()
+                    && preferred_display_backend(&config) != running_backend;
+                self.set_slave_config(config);
+                self.sync_worker_hud();
+                if backend_changed && self.pending_restart.is_none() {
+                    if self.is_recording() {
+                        sender
+                            .output(SlaveVideoOutput::ShowToastMessage(String::from(
+                                "显示方式需要随增强算法切换，请在停止录制后重启管道以应用。",
+                            )))
+                            .unwrap();
+                    } else {
+                        sender
+                            .output(SlaveVideoOutput::ShowToastMessage(String::from(
+                                "显示方式已随增强算法切换，正在重启管道。",
+                            )))
+                            .unwrap();
+                        self.pending_restart = Some(Duration::ZERO);
+                        sender.input(SlaveVideoInput::StopPipeline);
+                    }
+                }
+            }
+            SetHudData(hud_data) => {
+                *self.hud_shared.lock().unwrap() = hud_data.clone();
+                self.set_hud_data(hud_data);
+                self.sync_worker_hud();
+            }
+            SetCompositeFeed(appsrc) => {
+                let attach = appsrc.is_some();
+                *self.composite_feed.lock().unwrap() = appsrc;
+                // 管道运行中开始拼接录制时补充分支，之后重启管道时会自动添加
+                if let Some(pipeline) = &self.pipeline {
+                    if attach && pipeline.by_name("composite_feed").is_none() {
+                        let colorspace_conversion = available_colorspace_conversion(
+                            *self.slave_config.get_colorspace_conversion(),
+                            &sender,
+                        );
+                        if let Err(err) = super::video::attach_composite_feed(
+                            pipeline,
+                            colorspace_conversion,
+                            self.composite_feed.clone(),
+                        ) {
+                            sender
+                                .output(SlaveVideoOutput::ErrorMessage(err.to_string()))
+                                .unwrap();
+                        }
+                    }
+                }
+            }
+            UpdatePreferences(preferences) => {
+                if let Some(worker) = &self.frame_worker {
+                    worker.set_keep_raw(*preferences.get_screenshot_save_raw());
+                }
+                self.set_preferences(preferences);
+            }
+            SaveScreenshot(mut request) => {
+                // 连拍过程中画面中断时静默结束
+                let frame = match self.current_frame_pixbuf() {
+                    Some(frame) => frame,
+                    None => return,
+                };
+                let pixbuf = match self.burned_hud() {
+                    Some(hud) => burn_hud_into_pixbuf(&frame, &hud).unwrap_or_else(|err| {
+                        sender
                             .output(SlaveVideoOutput::ShowToastMessage(format!(
-                                "截图保存成功：{}",
-                                pathbuf.to_str().unwrap()
+                                "无法在截图上叠加 HUD：{}",
+                                err
                             )))
-                            .unwrap(),
-                        Err(err) => sender
+                            .unwrap();
+                        frame.clone()
+                    }),
+                    None => frame.clone(),
+                };
+                request.metadata.timestamp = DateTime::now_local()
+                    .unwrap()
+                    .format_iso8601()
+                    .unwrap()
+                    .to_string();
+                let jpeg_quality = *self.preferences.get_image_jpeg_quality();
+                let pathbuf = request.frame_path(false);
+                let mut result = save_screenshot(
+                    &pixbuf,
+                    &pathbuf,
+                    request.format,
+                    jpeg_quality,
+                    &request.metadata,
+                );
+                if *self.preferences.get_screenshot_save_raw() {
+                    let raw = self
+                        .frame_worker
+                        .as_ref()
+                        .and_then(FrameWorker::raw_frame)
+                        .map(|mat| mat.as_pixbuf());
+                    match raw {
+                        Some(Ok(raw)) => {
+                            result = result.and(save_screenshot(
+                                &raw,
+                                &request.frame_path(true),
+                                request.format,
+                                jpeg_quality,
+                                &request.metadata,
+                            ))
+                        }
+                        Some(Err(err)) => sender
                             .output(SlaveVideoOutput::ShowToastMessage(format!(
-                                "截图保存失败：{}",
-                                err.to_string()
+                                "无法保存原始画面：{}",
+                                err
                             )))
                             .unwrap(),
-                    };
+                        // 未经处理的画面与显示画面相同，无需重复保存
+                        None => (),
+                    }
                 }
+                match result {
+                    Ok(_) => match request.next() {
+                        Some(next) => {
+                            let interval = Duration::from_millis(
+                                *self.preferences.get_screenshot_burst_interval() as u64,
+                            );
+                            let sender = sender.clone();
+                            glib::timeout_add_local_once(interval, move || {
+                                sender.input(SlaveVideoInput::SaveScreenshot(next));
+                            });
+                        }
+                        None if request.burst_count > 1 => sender
+                            .output(SlaveVideoOutput::ShowToastMessage(format!(
+                                "连拍保存成功：共 {} 张，{}",
+                                request.burst_count,
+                                pathbuf.parent().unwrap().to_str().unwrap()
+                            )))
+                            .unwrap(),
+                        None => sender
+                            .output(SlaveVideoOutput::ShowToastMessage(format!(
+                                "截图保存成功：{}",
+                                pathbuf.to_str().unwrap()
+                            )))
+                            .unwrap(),
+                    },
+                    Err(err) => sender
+                        .output(SlaveVideoOutput::ShowToastMessage(format!(
+                            "截图保存失败：{}",
+                            err.to_string()
+                        )))
+                        .unwrap(),
+                };
             }
             RequestFrame => {
                 if let Some(pipeline) = &self.pipeline {
-                    pipeline
-                        .by_name("display")
-                        .unwrap()
-                        .dynamic_cast::<gst_app::AppSink>()
-                        .unwrap()
-                        .send_event(gst::event::CustomDownstream::new(
+                    pipeline.by_name("display").unwrap().send_event(
+                        gst::event::CustomDownstream::new(
                             gst::Structure::builder("resend").build(),
-                        ));
+                        ),
+                    );
+                }
+            }
+            PipelineError(message) => {
+                if self.pipeline.is_some() {
+                    sender
+                        .output(SlaveVideoOutput::ErrorMessage(
+                            VideoError::Runtime(message).to_string(),
+                        ))
+                        .unwrap();
+                    self.abort_pipeline(&sender);
+                }
+            }
+            PipelineWarning(message) => sender
+                .output(SlaveVideoOutput::ShowToastMessage(format!(
+                    "管道警告：{}",
+                    message
+                )))
+                .unwrap(),
+            PipelineEos => {
+                // 正常停止时管道已被取走，此时收到的 EOS 说明视频流意外结束
+                if self.pipeline.is_some() {
+                    sender
+                        .output(SlaveVideoOutput::ShowToastMessage(String::from(
+                            "视频流已结束。",
+                        )))
+                        .unwrap();
+                    self.abort_pipeline(&sender);
+                }
+            }
+            SetBuffering(percent) => {
+                self.set_buffering(if percent < 100 { Some(percent) } else { None })
+            }
+            RestartPipeline => {
+                self.restart_timer = None;
+                if self.pipeline.is_none() {
+                    sender.input(SlaveVideoInput::StartPipeline);
+                }
+            }
+            CheckStall => {
+                let timeout = Duration::from_millis(*self.slave_config.get_stall_timeout() as u64);
+                if let Some(counter) = &self.stats_counter {
+                    let stalled = counter.since_last_frame() > timeout;
+                    if !stalled && counter.has_frames() && self.signal_lost {
+                        self.restart_attempts = 0;
+                        self.set_signal_lost(false);
+                        sender
+                            .output(SlaveVideoOutput::ShowToastMessage(String::from(
+                                "视频信号已恢复。",
+                            )))
+                            .unwrap();
+                    } else if stalled && self.pipeline.is_some() && self.pending_restart.is_none() {
+                        let delay = self.next_restart_delay();
+                        self.set_signal_lost(true);
+                        self.pending_restart = Some(delay);
+                        sender
+                            .output(SlaveVideoOutput::ShowToastMessage(format!(
+                                "超过 {} 毫秒未收到画面，将在 {} 秒后重新拉流。",
+                                timeout.as_millis(),
+                                delay.as_secs()
+                            )))
+                            .unwrap();
+                        sender.input(SlaveVideoInput::StopPipeline);
+                    }
+                }
+            }
+            SetStatsDisplayed(displayed) => self.set_stats_displayed(displayed),
+            RefreshStats => {
+                if let (Some(pipeline), Some(counter)) = (&self.pipeline, &self.stats_counter) {
+                    let stats = counter.snapshot(
+                        pipeline,
+                        self.dropped_frames(),
+                        self.frame_worker
+                            .as_ref()
+                            .and_then(FrameWorker::processing_time),
+                    );
+                    if self.stats_displayed {
+                        self.set_stats_text(stats.to_string());
+                    }
                 }
             }
         }
@@ -398,6 +1007,72 @@ impl SimpleComponent for SlaveVideoModel {
 }
 
 impl SlaveVideoModel {
+    fn release_pipeline_resources(&mut self) {
+        if let Some(timer) = self.stats_timer.take() {
+            timer.remove();
+        }
+        if let Some(watch) = self.bus_watch.take() {
+            watch.remove();
+        }
+        if let Some(timer) = self.watchdog_timer.take() {
+            timer.remove();
+        }
+        self.stats_counter = None;
+        self.frame_worker = None;
+        self.pre_record_buffer = None;
+        self.set_stats_text(String::new());
+        self.set_buffering(None);
+    }
+
+    // 出错的管道无法再通过 EOS 正常结束，直接将其终止
+    fn abort_pipeline(&mut self, sender: &ComponentSender<Self>) {
+        if let Some(
+            RecordHandle::Processed(record_pipeline, appsrc)
+            | RecordHandle::PreRecorded(record_pipeline, appsrc),
+        ) = self.get_mut_record_handle().take()
+        {
+            super::video::stop_processed_record_pipeline(&record_pipeline, &appsrc).ok();
+        }
+        self.release_pipeline_resources();
+        if let Some(pipeline) = self.pipeline.take() {
+            pipeline.set_state(gst::State::Null).ok();
+        }
+        sender
+            .output(SlaveVideoOutput::RecordingChanged(false))
+            .unwrap();
+        sender
+            .output(SlaveVideoOutput::PollingChanged(false))
+            .unwrap();
+        if *self.slave_config.get_auto_restart_pipeline() {
+            const RESTART_DELAY: Duration = Duration::from_secs(3);
+            sender
+                .output(SlaveVideoOutput::ShowToastMessage(format!(
+                    "将在 {} 秒后尝试重新启动管道。",
+                    RESTART_DELAY.as_secs()
+                )))
+                .unwrap();
+            self.schedule_restart(sender, RESTART_DELAY);
+        }
+    }
+
+    fn schedule_restart(&mut self, sender: &ComponentSender<Self>, delay: Duration) {
+        if let Some(timer) = self.restart_timer.take() {
+            timer.remove();
+        }
+        let input_sender = sender.input_sender().clone();
+        self.restart_timer = Some(glib::timeout_add_local_once(delay, move || {
+            input_sender.send(SlaveVideoInput::RestartPipeline).unwrap();
+        }));
+    }
+
+    // 信号丢失后的重试间隔从 1 秒开始逐次翻倍，最长 30 秒
+    fn next_restart_delay(&mut self) -> Duration {
+        const MAX_RESTART_DELAY: Duration = Duration::from_secs(30);
+        let delay = Duration::from_secs(1 << self.restart_attempts.min(5)).min(MAX_RESTART_DELAY);
+        self.restart_attempts += 1;
+        delay
+    }
+
     pub fn is_running(&self) -> bool {
         self.pipeline.is_some()
     }
@@ -405,4 +1080,129 @@ impl SlaveVideoModel {
     pub fn is_recording(&self) -> bool {
         self.record_handle.is_some()
     }
+
+    // 包括处理线程来不及处理和界面来不及显示而丢弃的帧
+    pub fn dropped_frames(&self) -> u64 {
+        self.frame_mailbox
+            .as_ref()
+            .map_or(0, |mailbox| mailbox.dropped())
+            + self.frame_worker.as_ref().map_or(0, FrameWorker::dropped)
+    }
+
+    pub fn has_frame(&self) -> bool {
+        self.pixbuf.is_some() || self.paintable.is_some()
+    }
+
+    fn current_paintable(&self) -> Option<gdk::Paintable> {
+        match (&self.pixbuf, &self.paintable) {
+            (Some(pixbuf), _) => Some(gdk::Texture::for_pixbuf(pixbuf).upcast()),
+            (None, paintable) => paintable.clone(),
+        }
+    }
+
+    fn burned_hud(&self) -> Option<HudData> {
+        self.slave_config
+            .get_burn_hud()
+            .then(|| self.hud_data.clone())
+    }
+
+    fn sync_worker_hud(&self) {
+        if let Some(worker) = &self.frame_worker {
+            worker.set_hud(self.burned_hud());
+        }
+    }
+
+    fn current_frame_pixbuf(&self) -> Option<Pixbuf> {
+        match (&self.pixbuf, &self.paintable) {
+            (Some(pixbuf), _) => Some(pixbuf.clone()),
+            (None, Some(paintable)) => paintable
+                .current_image()
+                .downcast::<gdk::Texture>()
+                .ok()
+                .and_then(|texture| gdk::pixbuf_get_from_texture(&texture)),
+            (None, None) => None,
+        }
+    }
+}
+
+fn burn_hud_into_pixbuf(pixbuf: &Pixbuf, hud: &HudData) -> Result<Pixbuf, FrameError> {
+    let mut mat = pixbuf.as_mat()?;
+    burn_hud(&mut mat, hud, &current_clock())?;
+    mat.as_pixbuf()
+}
+
+fn available_colorspace_conversion(
+    colorspace_conversion: ColorspaceConversion,
+    sender: &ComponentSender<SlaveVideoModel>,
+) -> ColorspaceConversion {
+    if colorspace_conversion.is_available() {
+        colorspace_conversion
+    } else {
+        sender
+            .output(SlaveVideoOutput::ShowToastMessage(format!(
+                "当前系统缺少 {} 色彩空间转换所需的元素，已回退至 CPU 进行转换。",
+                colorspace_conversion
+            )))
+            .unwrap();
+        ColorspaceConversion::CPU
+    }
+}
+
+// 启用增强算法时必须经过 OpenCV 处理，此时只能使用 Pixbuf 方式显示
+fn preferred_display_backend(config: &SlaveConfigModel) -> VideoDisplayBackend {
+    match config.get_video_display_backend() {
+        VideoDisplayBackend::Paintable
+            if !config.get_video_algorithms().is_empty()
+                || !VideoDisplayBackend::Paintable.is_available() =>
+        {
+            VideoDisplayBackend::Pixbuf
+        }
+        backend => *backend,
+    }
+}
+
+fn available_display_backend(
+    config: &SlaveConfigModel,
+    sender: &ComponentSender<SlaveVideoModel>,
+) -> VideoDisplayBackend {
+    if *config.get_video_display_backend() == VideoDisplayBackend::Paintable
+        && !VideoDisplayBackend::Paintable.is_available()
+    {
+        sender
+            .output(SlaveVideoOutput::ShowToastMessage(String::from(
+                "当前系统缺少 gtk4paintablesink 元素，已回退至 OpenCV 显示方式。",
+            )))
+            .unwrap();
+    }
+    preferred_display_backend(config)
+}
+
+fn watch_pipeline_bus(
+    pipeline: &Pipeline,
+    sender: relm4::Sender<SlaveVideoInput>,
+) -> Option<SourceId> {
+    let bus = pipeline.bus()?;
+    bus.add_watch_local(
+        clone!(@weak pipeline => @default-return Continue(false), move |_bus, message| {
+            use gst::MessageView;
+            match message.view() {
+                MessageView::Error(err) => {
+                    sender.send(SlaveVideoInput::PipelineError(err.error().to_string())).unwrap();
+                }
+                MessageView::Warning(warning) => {
+                    sender.send(SlaveVideoInput::PipelineWarning(warning.error().to_string())).unwrap();
+                }
+                MessageView::Eos(_) => sender.send(SlaveVideoInput::PipelineEos).unwrap(),
+                MessageView::Buffering(buffering) => {
+                    sender.send(SlaveVideoInput::SetBuffering(buffering.percent())).unwrap();
+                }
+                MessageView::Latency(_) => {
+                    pipeline.recalculate_latency().ok();
+                }
+                _ => (),
+            }
+            Continue(true)
+        }),
+    )
+    .ok()
 }
@@ -0,0 +1,123 @@
+use std::{fmt::Display, time::Duration};
+
+use super::{frame_error::FrameError, url_ext};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VideoError {
+    MissingElement(String),
+    ElementNotFound(String),
+    AddElement(String),
+    Link { src: String, sink: String },
+    RequestPad(String),
+    InvalidUrl { url: String, reason: String },
+    StateChange(gst::State),
+    Timeout(Duration),
+    Caps(String),
+    UnsupportedCodec { container: String, codec: String },
+    PushBuffer,
+    Runtime(String),
+    Frame(FrameError),
+}
+
+impl VideoError {
+    pub fn link(src: &impl AsRef<str>, sink: &impl AsRef<str>) -> Self {
+        VideoError::Link {
+            src: src.as_ref().to_string(),
+            sink: sink.as_ref().to_string(),
+        }
+    }
+
+    // 错误提示会显示在界面上，不能带出 RTSP 的用户名与密码
+    pub fn invalid_url(url: &url::Url, reason: &str) -> Self {
+        VideoError::InvalidUrl {
+            url: url_ext::without_credentials(url).to_string(),
+            reason: reason.to_string(),
+        }
+    }
+}
+
+// 根据元素名推测提供该元素的 GStreamer 插件包
+fn plugin_package(element: &str) -> Option<&'static str> {
+    let package = match element {
+        "queue" | "tee" | "filesink" | "capsfilter" => "gstreamer",
+        "videoconvert" | "videoscale" | "compositor" | "appsink" | "appsrc" | "uridecodebin"
+        | "uridecodebin3" => "gst-plugins-base",
+        "udpsrc" | "rtspsrc" | "rtpjitterbuffer" | "matroskamux" | "mp4mux" | "splitmuxsink"
+        | "v4l2convert" | "vp8enc" | "vp8dec" | "vp9enc" | "vp9dec" => "gst-plugins-good",
+        "x264enc" => "gst-plugins-ugly",
+        "mpegtsmux" => "gst-plugins-bad",
+        "gtk4paintablesink" => "gst-plugin-gtk4",
+        name if name.starts_with("rtp") && name.ends_with("depay") => "gst-plugins-good",
+        name if name.starts_with("avdec_") || name.starts_with("avenc_") => "gst-libav",
+        name if name.starts_with("vaapi") => "gstreamer-vaapi",
+        name if name.ends_with("parse")
+            || name.starts_with("nv")
+            || name.starts_with("cuda")
+            || name.starts_with("d3d11")
+            || name.starts_with("va")
+            || name.starts_with("x265")
+            || name.starts_with("av1") =>
+        {
+            "gst-plugins-bad"
+        }
+        _ => return None,
+    };
+    Some(package)
+}
+
+impl Display for VideoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VideoError::MissingElement(element) => match plugin_package(element) {
+                Some(package) => write!(
+                    f,
+                    "当前系统缺少 GStreamer 元素 {}，请安装 {} 插件包后重试。",
+                    element, package
+                ),
+                None => write!(
+                    f,
+                    "当前系统缺少 GStreamer 元素 {}，请安装提供该元素的插件后重试。",
+                    element
+                ),
+            },
+            VideoError::ElementNotFound(name) => write!(f, "管道中找不到元素 {}。", name),
+            VideoError::AddElement(name) => write!(f, "无法将元素 {} 添加到管道。", name),
+            VideoError::Link { src, sink } => write!(
+                f,
+                "无法连接 {} 与 {}，请检查编解码器与色彩空间转换设置是否匹配。",
+                src, sink
+            ),
+            VideoError::RequestPad(element) => write!(f, "无法从元素 {} 申请新的输出端。", element),
+            VideoError::InvalidUrl { url, reason } => {
+                write!(f, "拉流 URL {} 有误（{}），请检查并修改后重试。", url, reason)
+            }
+            VideoError::StateChange(state) => write!(
+                f,
+                "无法将管道切换到 {:?} 状态，这可能是由于管道使用的资源不存在或被占用导致的，请检查相关资源是否可用。",
+                state
+            ),
+            VideoError::Timeout(timeout) => write!(
+                f,
+                "等待管道响应超时（{} 秒），已将其强制终止。",
+                timeout.as_secs_f32()
+            ),
+            VideoError::Caps(caps) => write!(f, "无法创建媒体格式 {}。", caps),
+            VideoError::UnsupportedCodec { container, codec } => write!(
+                f,
+                "{} 封装格式不支持 {} 编码的视频，请更换录制格式或录制编码器。",
+                container, codec
+            ),
+            VideoError::PushBuffer => write!(f, "无法将画面送入录制管道。"),
+            VideoError::Runtime(message) => write!(f, "管道运行出错：{}", message),
+            VideoError::Frame(error) => error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for VideoError {}
+
+impl From<FrameError> for VideoError {
+    fn from(error: FrameError) -> Self {
+        VideoError::Frame(error)
+    }
+}
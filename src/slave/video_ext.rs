@@ -1,29 +1,50 @@
 use std::{
+    collections::HashMap,
     ffi::c_void,
+    path::Path,
     str::FromStr,
-    sync::{Arc, Mutex},
+    sync::{Arc, Mutex, OnceLock},
+    time::Duration,
 };
 
-use cv::{core::Size, imgproc, prelude::*, Result};
-use cv::{core::VecN, types::VectorOfMat};
+use cv::{prelude::*, Result};
 use gst::{
     element_error, prelude::*, Element, EventView, Pad, PadProbeData, PadProbeReturn, PadProbeType,
     Pipeline,
 };
 use opencv as cv;
-use relm4::gtk::{
-    gdk_pixbuf::{Colorspace, Pixbuf},
-    glib::{clone, EnumClass, Sender},
+use relm4::{
+    factory::positions::GridPosition,
+    gtk::{
+        gdk_pixbuf::{Colorspace, Pixbuf},
+        glib::{self, clone, EnumClass},
+    },
 };
 use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
 use strum_macros::{Display as EnumToString, EnumIter};
 use url::Url;
 
 use super::{
     async_glib::{Future, Promise},
     config::SlaveConfigModel,
+    frame_error::FrameError,
+    frame_mailbox::FrameMailbox,
+    pre_record::PreRecordBuffer,
+    video_error::VideoError,
 };
 
+// 插件在运行期间不会增减，缓存查询结果以免每次绘制下拉框都查询 GStreamer 注册表
+fn element_available(name: &str) -> bool {
+    static CACHE: OnceLock<Mutex<HashMap<String, bool>>> = OnceLock::new();
+    *CACHE
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap()
+        .entry(name.to_string())
+        .or_insert_with(|| gst::ElementFactory::find(name).is_some())
+}
+
 #[derive(EnumIter, EnumToString, PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum ImageFormat {
     JPEG,
@@ -72,21 +93,34 @@ impl VideoSource {
         &self,
         latency: u32,
         video_decoder: &VideoDecoder,
-    ) -> Result<Vec<Element>, String> {
+    ) -> Result<Vec<Element>, VideoError> {
         let mut elements = Vec::new();
         match self {
             VideoSource::UDP(url) | VideoSource::RTP(url) => {
                 let udpsrc = gst::ElementFactory::make("udpsrc")
                     .name("source")
-                    .property("address", url.host_str().ok_or("url-host error")?)
-                    .property("port", url.port().ok_or("port error")? as i32)
+                    .property(
+                        "address",
+                        url.host_str()
+                            .ok_or_else(|| VideoError::invalid_url(url, "缺少主机地址"))?,
+                    )
+                    .property(
+                        "port",
+                        url.port()
+                            .ok_or_else(|| VideoError::invalid_url(url, "缺少端口"))?
+                            as i32,
+                    )
                     .build()
-                    .map_err(|_| "create udpsrc error")?;
+                    .map_err(|_| VideoError::MissingElement(String::from("udpsrc")))?;
 
                 if let VideoSource::RTP(_) = self {
                     let caps_src =
                         gst::caps::Caps::from_str("application/x-rtp, media=(string)video")
-                            .map_err(|_| "Cannot create capability for udpsrc")?;
+                            .map_err(|_| {
+                                VideoError::Caps(String::from(
+                                    "application/x-rtp, media=(string)video",
+                                ))
+                            })?;
                     udpsrc.set_property("caps", caps_src);
                 }
                 elements.push(udpsrc);
@@ -95,7 +129,7 @@ impl VideoSource {
                     let rtpjitterbuffer = gst::ElementFactory::make("rtpjitterbuffer")
                         .property("latency", latency)
                         .build()
-                        .map_err(|_| "Missing element: rtpjitterbuffer")?;
+                        .map_err(|_| VideoError::MissingElement(String::from("rtpjitterbuffer")))?;
                     elements.push(rtpjitterbuffer);
                 }
             }
@@ -104,10 +138,16 @@ impl VideoSource {
                     .name("source")
                     .property("location", url.to_string())
                     .property("user-id", url.username())
-                    .property("user-pw", url.password().ok_or("password error")?)
+                    .property(
+                        "user-pw",
+                        url.password()
+                            .ok_or_else(|| VideoError::invalid_url(url, "缺少密码"))?,
+                    )
                     .property("latency", latency)
+                    // 附带发送端报告中的 NTP 时间，用于统计端到端延迟
+                    .property("add-reference-timestamp-meta", true)
                     .build()
-                    .map_err(|_| "Missing element: rtspsrc")?;
+                    .map_err(|_| VideoError::MissingElement(String::from("rtspsrc")))?;
                 elements.push(rtspsrc);
             }
         }
@@ -116,7 +156,7 @@ impl VideoSource {
                 let depay = gst::ElementFactory::make(&video_decoder.0.depay_name())
                     .name("rtpdepay")
                     .build()
-                    .map_err(|_| format!("Missing element: {}", video_decoder.0.depay_name()))?;
+                    .map_err(|_| VideoError::MissingElement(video_decoder.0.depay_name()))?;
                 elements.push(depay);
             }
             _ => (),
@@ -125,11 +165,6 @@ impl VideoSource {
     }
 }
 
-#[derive(EnumIter, EnumToString, PartialEq, Clone, Debug)]
-pub enum VideoAlgorithm {
-    CLAHE,
-}
-
 #[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
 pub struct VideoEncoder(pub VideoCodec, pub VideoCodecProvider);
 
@@ -171,7 +206,7 @@ impl VideoCodec {
     }
 }
 
-#[derive(EnumIter, PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[derive(EnumIter, PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum VideoCodecProvider {
     Native,
     AVCodec,
@@ -208,103 +243,337 @@ impl VideoCodecProvider {
             },
         }
     }
+
+    pub fn is_available(&self, codec: VideoCodec, encode: bool) -> bool {
+        element_available(&self.format_codec(codec, encode))
+    }
+
+    // 硬件接口优先，其次为软件接口
+    pub fn best_available(codec: VideoCodec, encode: bool) -> Option<VideoCodecProvider> {
+        use VideoCodecProvider::*;
+        let priority: &[VideoCodecProvider] = if encode {
+            &[NVCodec, D3D11, VAAPI, Native, AVCodec]
+        } else {
+            &[NVCodec, D3D11, VAAPI, AVCodec, Native]
+        };
+        priority
+            .iter()
+            .copied()
+            .find(|provider| provider.is_available(codec, encode))
+    }
+
+    // 至少支持一种编码格式的接口
+    pub fn available(encode: bool) -> Vec<VideoCodecProvider> {
+        VideoCodecProvider::iter()
+            .filter(|provider| VideoCodec::iter().any(|codec| provider.is_available(codec, encode)))
+            .collect()
+    }
+}
+
+impl VideoCodec {
+    // 至少有一种接口支持的编码格式
+    pub fn available(encode: bool) -> Vec<VideoCodec> {
+        VideoCodec::iter()
+            .filter(|codec| {
+                VideoCodecProvider::iter().any(|provider| provider.is_available(*codec, encode))
+            })
+            .collect()
+    }
 }
 
 impl VideoEncoder {
+    pub fn missing_element(&self) -> Option<String> {
+        let encoder_name = self.1.format_codec(self.0, true);
+        (!element_available(&encoder_name)).then_some(encoder_name)
+    }
+
     pub fn gst_record_elements(
         &self,
         colorspace_conversion: ColorspaceConversion,
         filename: &str,
-    ) -> Result<Vec<Element>, String> {
+        options: &RecordOptions,
+    ) -> Result<Vec<Element>, VideoError> {
         let mut elements = Vec::new();
         let queue_to_file = gst::ElementFactory::make("queue")
             .build()
-            .map_err(|_| "Missing element: queue")?;
+            .map_err(|_| VideoError::MissingElement(String::from("queue")))?;
         elements.push(queue_to_file);
         elements.extend_from_slice(&colorspace_conversion.gst_elements()?);
         let encoder_name = self.1.format_codec(self.0, true);
         let encoder = gst::ElementFactory::make(&encoder_name)
             .build()
-            .map_err(|_| format!("Missing element: {}", &encoder_name))?;
+            .map_err(|_| VideoError::MissingElement(encoder_name.clone()))?;
         elements.push(encoder);
         match self.0 {
             VideoCodec::H264 => {
                 let h264parse = gst::ElementFactory::make("h264parse")
                     .build()
-                    .map_err(|_| "Missing element: h264parse")?;
+                    .map_err(|_| VideoError::MissingElement(String::from("h264parse")))?;
                 elements.push(h264parse);
             }
             VideoCodec::H265 => {
                 let h265parse = gst::ElementFactory::make("h265parse")
                     .build()
-                    .map_err(|_| "Missing element: h265parse")?;
+                    .map_err(|_| VideoError::MissingElement(String::from("h265parse")))?;
                 elements.push(h265parse);
             }
             _ => (),
         };
-        let matroskamux = gst::ElementFactory::make("matroskamux")
-            .build()
-            .map_err(|_| "Missing muxer: matroskamux")?;
-        elements.push(matroskamux);
-        let filesink = gst::ElementFactory::make("filesink")
-            .build()
-            .map_err(|_| "Missing element: filesink")?;
-        filesink.set_property("location", filename);
-        elements.push(filesink);
+        elements.extend(gst_file_sink_elements(filename, options, self.0, true)?);
         Ok(elements)
     }
 }
 
+#[derive(EnumIter, PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum VideoContainer {
+    FragmentedMP4,
+    Matroska,
+    MPEGTS,
+}
+
+impl ToString for VideoContainer {
+    fn to_string(&self) -> String {
+        match self {
+            VideoContainer::FragmentedMP4 => "MP4（分片，异常退出后仍可播放）",
+            VideoContainer::Matroska => "Matroska (MKV)",
+            VideoContainer::MPEGTS => "MPEG-TS",
+        }
+        .to_string()
+    }
+}
+
+impl VideoContainer {
+    fn element_name(&self) -> &'static str {
+        match self {
+            VideoContainer::FragmentedMP4 => "mp4mux",
+            VideoContainer::Matroska => "matroskamux",
+            VideoContainer::MPEGTS => "mpegtsmux",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            VideoContainer::FragmentedMP4 => "mp4",
+            VideoContainer::Matroska => "mkv",
+            VideoContainer::MPEGTS => "ts",
+        }
+    }
+
+    pub fn is_available(&self) -> bool {
+        element_available(self.element_name())
+    }
+
+    pub fn available() -> Vec<VideoContainer> {
+        VideoContainer::iter().filter(Self::is_available).collect()
+    }
+
+    // mp4mux 不支持 VP8，mpegtsmux 仅支持 H.264 与 H.265
+    pub fn supports(&self, codec: VideoCodec) -> bool {
+        match self {
+            VideoContainer::FragmentedMP4 => codec != VideoCodec::VP8,
+            VideoContainer::Matroska => true,
+            VideoContainer::MPEGTS => matches!(codec, VideoCodec::H264 | VideoCodec::H265),
+        }
+    }
+
+    pub fn short_name(&self) -> String {
+        self.extension().to_uppercase()
+    }
+
+    fn gst_muxer_element(&self) -> Result<Element, VideoError> {
+        let muxer = gst::ElementFactory::make(self.element_name())
+            .build()
+            .map_err(|_| VideoError::MissingElement(self.element_name().to_string()))?;
+        if let VideoContainer::FragmentedMP4 = self {
+            // 每秒写出一个分片，文件未正常收尾时已写入的分片仍可播放
+            muxer.set_property("fragment-duration", 1000u32);
+        }
+        Ok(muxer)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordOptions {
+    pub container: VideoContainer,
+    pub segmentation: Option<RecordSegmentation>,
+    pub metadata: RecordMetadata,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordMetadata {
+    pub title: String,
+    pub video_url: String,
+    pub start_time: glib::DateTime,
+    pub notes: String,
+}
+
+// 分段录制的切分条件，为 0 的项表示不限制
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RecordSegmentation {
+    pub max_duration: Duration,
+    pub max_size_bytes: u64,
+}
+
+// 分段录制时将文件名 a.mkv 转换为 a_00000.mkv、a_00001.mkv……
+fn segment_location(filename: &str, fragment_id: u32) -> String {
+    let path = Path::new(filename);
+    let stem = path
+        .file_stem()
+        .map_or_else(String::new, |stem| stem.to_string_lossy().to_string());
+    let name = match path.extension() {
+        Some(extension) => format!(
+            "{}_{:05}.{}",
+            stem,
+            fragment_id,
+            extension.to_string_lossy()
+        ),
+        None => format!("{}_{:05}", stem, fragment_id),
+    };
+    path.with_file_name(name).to_string_lossy().to_string()
+}
+
+fn record_tags(metadata: &RecordMetadata, codec: VideoCodec) -> gst::TagList {
+    let mode = gst::TagMergeMode::Replace;
+    let mut tags = gst::TagList::new();
+    {
+        let tags = tags.get_mut().unwrap();
+        tags.add::<gst::tags::Title>(&metadata.title.as_str(), mode);
+        if !metadata.video_url.is_empty() {
+            tags.add::<gst::tags::Location>(&metadata.video_url.as_str(), mode);
+        }
+        // 同步录制的各路视频使用同一开始时间，便于事后对齐
+        tags.add::<gst::tags::DateTime>(
+            &gst::DateTime::from_g_date_time(metadata.start_time.clone()),
+            mode,
+        );
+        tags.add::<gst::tags::VideoCodec>(&codec.to_string().as_str(), mode);
+        tags.add::<gst::tags::ApplicationName>(
+            &concat!(env!("CARGO_PKG_NAME"), " ", env!("CARGO_PKG_VERSION")),
+            mode,
+        );
+        if !metadata.notes.is_empty() {
+            tags.add::<gst::tags::Comment>(&metadata.notes.as_str(), mode);
+        }
+    }
+    tags
+}
+
+// 将录制信息写入封装格式的标签，mpegtsmux 不支持标签，MPEG-TS 文件中不包含这些信息
+fn apply_record_tags(muxer: &Element, tags: &gst::TagList) {
+    if let Some(tag_setter) = muxer.dynamic_cast_ref::<gst::TagSetter>() {
+        tag_setter.merge_tags(tags, gst::TagMergeMode::Replace);
+    }
+}
+
+fn gst_file_sink_elements(
+    filename: &str,
+    options: &RecordOptions,
+    codec: VideoCodec,
+    request_keyframes: bool,
+) -> Result<Vec<Element>, VideoError> {
+    let filesink = gst::ElementFactory::make("filesink")
+        .build()
+        .map_err(|_| VideoError::MissingElement(String::from("filesink")))?;
+    if !options.container.supports(codec) {
+        return Err(VideoError::UnsupportedCodec {
+            container: options.container.short_name(),
+            codec: codec.to_string(),
+        });
+    }
+    let muxer = options.container.gst_muxer_element()?;
+    let tags = record_tags(&options.metadata, codec);
+    apply_record_tags(&muxer, &tags);
+    match options.segmentation {
+        Some(segmentation) => {
+            // 每个分段写完后都会单独收尾，程序异常退出时仅丢失最后一段
+            let splitmuxsink = gst::ElementFactory::make("splitmuxsink")
+                .build()
+                .map_err(|_| VideoError::MissingElement(String::from("splitmuxsink")))?;
+            // 封装器在切换分段时会被重置并清空标签，需要在每个分段开始时重新写入
+            let filename = filename.to_string();
+            splitmuxsink.connect("format-location-full", false, {
+                let muxer = muxer.clone();
+                move |args| {
+                    let fragment_id = args.get(1)?.get::<u32>().ok()?;
+                    apply_record_tags(&muxer, &tags);
+                    Some(segment_location(&filename, fragment_id).to_value())
+                }
+            });
+            splitmuxsink.set_property("muxer", &muxer);
+            splitmuxsink.set_property("sink", &filesink);
+            splitmuxsink.set_property("max-size-time", segmentation.max_duration.as_nanos() as u64);
+            splitmuxsink.set_property("max-size-bytes", segmentation.max_size_bytes);
+            // 重新编码时主动请求关键帧，使分段长度贴近设定值
+            splitmuxsink.set_property("send-keyframe-requests", request_keyframes);
+            Ok(vec![splitmuxsink])
+        }
+        None => {
+            filesink.set_property("location", filename);
+            Ok(vec![muxer, filesink])
+        }
+    }
+}
+
+// 分段录制时文件由 splitmuxsink 内部的 filesink 写入，需要在其上等待 EOS
+fn file_sink_pad(sink: &Element) -> Option<Pad> {
+    if sink.has_property("sink", None) {
+        sink.property::<Option<Element>>("sink")?.static_pad("sink")
+    } else {
+        sink.static_pad("sink")
+    }
+}
+
 #[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
 pub struct VideoDecoder(pub VideoCodec, pub VideoCodecProvider);
 
 impl VideoDecoder {
-    pub fn gst_record_elements(&self, filename: &str) -> Result<Vec<Element>, String> {
+    pub fn missing_element(&self) -> Option<String> {
+        let decoder_name = self.1.format_codec(self.0, false);
+        (!element_available(&decoder_name)).then_some(decoder_name)
+    }
+
+    pub fn gst_record_elements(
+        &self,
+        filename: &str,
+        options: &RecordOptions,
+    ) -> Result<Vec<Element>, VideoError> {
         let mut elements = Vec::new();
         let queue_to_file = gst::ElementFactory::make("queue")
             .build()
-            .map_err(|_| "Missing element: queue")?;
+            .map_err(|_| VideoError::MissingElement(String::from("queue")))?;
         elements.push(queue_to_file);
         match self.0 {
             VideoCodec::H264 => {
                 let parse = gst::ElementFactory::make("h264parse")
                     .build()
-                    .map_err(|_| "Missing element: h264parse")?;
+                    .map_err(|_| VideoError::MissingElement(String::from("h264parse")))?;
                 elements.push(parse);
             }
             VideoCodec::H265 => {
                 let parse = gst::ElementFactory::make("h265parse")
                     .build()
-                    .map_err(|_| "Missing element: h265parse")?;
+                    .map_err(|_| VideoError::MissingElement(String::from("h265parse")))?;
                 elements.push(parse);
             }
             _ => (),
         }
-        let matroskamux = gst::ElementFactory::make("matroskamux")
-            .build()
-            .map_err(|_| "Missing muxer: matroskamux")?;
-        elements.push(matroskamux);
-        let filesink = gst::ElementFactory::make("filesink")
-            .build()
-            .map_err(|_| "Missing element: filesink")?;
-        filesink.set_property("location", filename);
-        elements.push(filesink);
+        elements.extend(gst_file_sink_elements(filename, options, self.0, false)?);
         Ok(elements)
     }
 
-    pub fn gst_main_elements(&self) -> Result<Vec<Element>, String> {
+    pub fn gst_main_elements(&self) -> Result<Vec<Element>, VideoError> {
         let mut elements = Vec::new();
         match self.0 {
             VideoCodec::H264 => {
                 let parse = gst::ElementFactory::make("h264parse")
                     .build()
-                    .map_err(|_| "Missing element: h264parse")?;
+                    .map_err(|_| VideoError::MissingElement(String::from("h264parse")))?;
                 elements.push(parse);
             }
             VideoCodec::H265 => {
                 let parse = gst::ElementFactory::make("h265parse")
                     .build()
-                    .map_err(|_| "Missing element: h265parse")?;
+                    .map_err(|_| VideoError::MissingElement(String::from("h265parse")))?;
                 elements.push(parse);
             }
             _ => (),
@@ -313,7 +582,7 @@ impl VideoDecoder {
         let decoder = gst::ElementFactory::make(&decoder_name)
             .name("video_decoder")
             .build()
-            .map_err(|_| format!("Missing element: {}", &decoder_name))?;
+            .map_err(|_| VideoError::MissingElement(decoder_name.clone()))?;
         elements.push(decoder);
         Ok(elements)
     }
@@ -324,48 +593,144 @@ pub enum ColorspaceConversion {
     CPU,
     CUDA,
     D3D11,
+    VAAPI,
+    V4L2,
 }
 
 impl ColorspaceConversion {
-    fn gst_elements(&self) -> Result<Vec<Element>, String> {
+    fn element_names(&self) -> &'static [&'static str] {
         match self {
-            ColorspaceConversion::CPU => Ok(vec![gst::ElementFactory::make("videoconvert")
-                .build()
-                .map_err(|_| "Missing element: videoconvert")?]),
-            ColorspaceConversion::CUDA => Ok(vec![
-                gst::ElementFactory::make("cudaupload")
-                    .build()
-                    .map_err(|_| "Missing element: cudaupload")?,
-                gst::ElementFactory::make("cudaconvert")
-                    .build()
-                    .map_err(|_| "Missing element: cudaconvert")?,
-                gst::ElementFactory::make("cudadownload")
-                    .build()
-                    .map_err(|_| "Missing element: cudadownload")?,
-            ]),
-            ColorspaceConversion::D3D11 => Ok(vec![
-                gst::ElementFactory::make("d3d11upload")
-                    .build()
-                    .map_err(|_| "Missing element: d3d11upload")?,
-                gst::ElementFactory::make("d3d11convert")
+            ColorspaceConversion::CPU => &["videoconvert"],
+            ColorspaceConversion::CUDA => &["cudaupload", "cudaconvert", "cudadownload"],
+            ColorspaceConversion::D3D11 => &["d3d11upload", "d3d11convert", "d3d11download"],
+            ColorspaceConversion::VAAPI => &["vapostproc"],
+            ColorspaceConversion::V4L2 => &["v4l2convert"],
+        }
+    }
+
+    pub fn is_available(&self) -> bool {
+        self.element_names()
+            .iter()
+            .all(|name| element_available(name))
+    }
+
+    pub fn available() -> Vec<ColorspaceConversion> {
+        ColorspaceConversion::iter()
+            .filter(Self::is_available)
+            .collect()
+    }
+
+    fn gst_elements(&self) -> Result<Vec<Element>, VideoError> {
+        self.element_names()
+            .iter()
+            .map(|name| {
+                gst::ElementFactory::make(name)
                     .build()
-                    .map_err(|_| "Missing element: d3d11convert")?,
-                gst::ElementFactory::make("d3d11download")
+                    .map_err(|_| VideoError::MissingElement(name.to_string()))
+            })
+            .collect()
+    }
+
+    // 硬件转换通常不支持 appsink 要求的 RGB 格式，需要再经过 videoconvert 进行打包
+    fn gst_display_elements(&self) -> Result<Vec<Element>, VideoError> {
+        let mut elements = self.gst_elements()?;
+        if *self != ColorspaceConversion::CPU {
+            elements.push(
+                gst::ElementFactory::make("videoconvert")
                     .build()
-                    .map_err(|_| "Missing element: d3d11download")?,
-            ]),
+                    .map_err(|_| VideoError::MissingElement(String::from("videoconvert")))?,
+            );
+        }
+        Ok(elements)
+    }
+}
+#[derive(EnumIter, PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum VideoDisplayBackend {
+    Pixbuf,
+    Paintable,
+}
+
+impl ToString for VideoDisplayBackend {
+    fn to_string(&self) -> String {
+        match self {
+            VideoDisplayBackend::Pixbuf => "OpenCV (支持画面增强)",
+            VideoDisplayBackend::Paintable => "GTK Paintable (零拷贝)",
+        }
+        .to_string()
+    }
+}
+
+impl VideoDisplayBackend {
+    fn element_name(&self) -> &'static str {
+        match self {
+            VideoDisplayBackend::Pixbuf => "appsink",
+            VideoDisplayBackend::Paintable => "gtk4paintablesink",
+        }
+    }
+
+    pub fn is_available(&self) -> bool {
+        element_available(self.element_name())
+    }
+
+    pub fn available() -> Vec<VideoDisplayBackend> {
+        VideoDisplayBackend::iter()
+            .filter(Self::is_available)
+            .collect()
+    }
+
+    fn gst_sink_element(&self) -> Result<Element, VideoError> {
+        let sink = gst::ElementFactory::make(self.element_name())
+            .name("display")
+            .build()
+            .map_err(|_| VideoError::MissingElement(self.element_name().to_string()))?;
+        if let VideoDisplayBackend::Pixbuf = self {
+            let caps_app = gst::caps::Caps::from_str("video/x-raw, format=RGB")
+                .map_err(|_| VideoError::Caps(String::from("video/x-raw, format=RGB")))?;
+            sink.set_property("caps", caps_app);
+        }
+        Ok(sink)
+    }
+
+    fn gst_conversion_elements(
+        &self,
+        colorspace_conversion: ColorspaceConversion,
+    ) -> Result<Vec<Element>, VideoError> {
+        match self {
+            VideoDisplayBackend::Pixbuf => colorspace_conversion.gst_display_elements(),
+            VideoDisplayBackend::Paintable => colorspace_conversion.gst_elements(),
         }
     }
 }
+
+impl Default for VideoContainer {
+    fn default() -> Self {
+        Self::FragmentedMP4
+    }
+}
+
+impl Default for VideoDisplayBackend {
+    fn default() -> Self {
+        Self::Pixbuf
+    }
+}
+
 impl Default for VideoEncoder {
     fn default() -> Self {
-        Self(VideoCodec::H264, VideoCodecProvider::Native)
+        Self(
+            VideoCodec::H264,
+            VideoCodecProvider::best_available(VideoCodec::H264, true)
+                .unwrap_or(VideoCodecProvider::Native),
+        )
     }
 }
 
 impl Default for VideoDecoder {
     fn default() -> Self {
-        Self(VideoCodec::H264, VideoCodecProvider::AVCodec)
+        Self(
+            VideoCodec::H264,
+            VideoCodecProvider::best_available(VideoCodec::H264, false)
+                .unwrap_or(VideoCodecProvider::AVCodec),
+        )
     }
 }
 
@@ -379,31 +744,34 @@ pub fn connect_elements_to_pipeline(
     pipeline: &Pipeline,
     tee_name: &str,
     elements: &[Element],
-) -> Result<(Element, Pad), String> {
-    let output_tee = pipeline.by_name(tee_name).ok_or("Cannot find output_tee")?;
-    if let Some(element) = elements.first() {
-        pipeline
-            .add(element)
-            .map_err(|_| "Cannot add the first element to pipeline")?; // 必须先添加，再连接
-    }
+) -> Result<(Element, Pad), VideoError> {
+    let output_tee = pipeline
+        .by_name(tee_name)
+        .ok_or_else(|| VideoError::ElementNotFound(tee_name.to_string()))?;
+    let first = elements
+        .first()
+        .ok_or_else(|| VideoError::ElementNotFound(String::from("record elements")))?;
+    add_elements(pipeline, &[first])?; // 必须先添加，再连接
     let teepad = output_tee
         .request_pad_simple("src_%u")
-        .ok_or("Cannot request pad")?;
+        .ok_or_else(|| VideoError::RequestPad(tee_name.to_string()))?;
     for elements in elements.windows(2) {
         if let [a, b] = elements {
-            pipeline
-                .add(b)
-                .map_err(|_| "Cannot add elements to pipeline")?;
-            a.link(b).map_err(|_| "Cannot link elements")?;
+            add_elements(pipeline, &[b])?;
+            a.link(b)
+                .map_err(|_| VideoError::link(&a.name(), &b.name()))?;
         }
     }
-    let sinkpad = elements.first().unwrap().static_pad("sink").unwrap();
+    let sinkpad = first
+        .static_pad("sink")
+        .ok_or_else(|| VideoError::link(&tee_name, &first.name()))?;
     teepad
         .link(&sinkpad)
-        .map_err(|_| "Cannot link the pad of output tee to the pad of first element")?;
-    output_tee.sync_state_with_parent().unwrap();
-    for element in elements {
-        element.sync_state_with_parent().unwrap();
+        .map_err(|_| VideoError::link(&tee_name, &first.name()))?;
+    for element in [&output_tee].into_iter().chain(elements) {
+        element
+            .sync_state_with_parent()
+            .map_err(|_| VideoError::StateChange(pipeline.current_state()))?;
     }
     Ok((output_tee, teepad))
 }
@@ -412,21 +780,23 @@ pub fn disconnect_elements_to_pipeline(
     pipeline: &Pipeline,
     (output_tee, teepad): &(Element, Pad),
     elements: &[Element],
-) -> Result<Future<()>, String> {
-    let first_sinkpad = elements.first().unwrap().static_pad("sink").unwrap();
+) -> Result<Future<()>, VideoError> {
+    let first = elements
+        .first()
+        .ok_or_else(|| VideoError::ElementNotFound(String::from("record elements")))?;
+    let first_sinkpad = first
+        .static_pad("sink")
+        .ok_or_else(|| VideoError::link(&output_tee.name(), &first.name()))?;
     teepad
         .unlink(&first_sinkpad)
-        .map_err(|_| "Cannot unlink elements")?;
+        .map_err(|_| VideoError::link(&output_tee.name(), &teepad.name()))?;
     output_tee
         .remove_pad(teepad)
-        .map_err(|_| "Cannot remove pad from output tee")?;
+        .map_err(|_| VideoError::RequestPad(output_tee.name().to_string()))?;
     let last_sinkpad = elements
         .last()
-        .unwrap()
-        .sink_pads()
-        .into_iter()
-        .next()
-        .unwrap();
+        .and_then(file_sink_pad)
+        .ok_or_else(|| VideoError::ElementNotFound(String::from("filesink")))?;
     let elements = elements.to_vec();
     let promise = Promise::new();
     let future = promise.future();
@@ -446,52 +816,446 @@ pub fn disconnect_elements_to_pipeline(
     });
     first_sinkpad.send_event(gst::event::Eos::new());
     let future = future.map(clone!(@strong pipeline => move |_| {
-            pipeline.remove_many(&elements.iter().collect::<Vec<_>>()).map_err(|_| "Cannot remove elements from pipeline").unwrap();
-            for element in elements.iter() {
-                element.set_state(gst::State::Null).unwrap();
+        pipeline.remove_many(&elements.iter().collect::<Vec<_>>()).ok();
+        for element in elements.iter() {
+            element.set_state(gst::State::Null).ok();
+        }
+    }));
+    Ok(future)
+}
+
+fn create_appsrc_record_pipeline(
+    elements: &[Element],
+    do_timestamp: bool,
+) -> Result<(Pipeline, gst_app::AppSrc), VideoError> {
+    let pipeline = gst::Pipeline::new(None);
+    let appsrc = gst_app::AppSrc::builder()
+        .name("record_source")
+        .format(gst::Format::Time)
+        .is_live(true)
+        .do_timestamp(do_timestamp)
+        .build();
+    let appsrc_element: &Element = appsrc.upcast_ref();
+    add_elements(&pipeline, &[appsrc_element])?;
+    add_elements(&pipeline, &elements.iter().collect::<Vec<_>>())?;
+    link_elements(
+        &[appsrc_element]
+            .into_iter()
+            .chain(elements.iter())
+            .collect::<Vec<_>>(),
+    )?;
+    pipeline
+        .set_state(gst::State::Playing)
+        .map_err(|_| VideoError::StateChange(gst::State::Playing))?;
+    Ok((pipeline, appsrc))
+}
+
+// 录制增强后的画面时，处理后的帧通过 appsrc 送入独立的编码管道
+pub fn create_processed_record_pipeline(
+    encoder: &VideoEncoder,
+    colorspace_conversion: ColorspaceConversion,
+    filename: &str,
+    options: &RecordOptions,
+) -> Result<(Pipeline, gst_app::AppSrc), VideoError> {
+    let elements = encoder.gst_record_elements(colorspace_conversion, filename, options)?;
+    create_appsrc_record_pipeline(&elements, true)
+}
+
+// 带预录的录制同样使用独立管道，先写入缓冲区中的画面，再接续实时视频流
+pub fn create_pre_record_pipeline(
+    decoder: &VideoDecoder,
+    filename: &str,
+    options: &RecordOptions,
+    buffer: &PreRecordBuffer,
+) -> Result<(Pipeline, gst_app::AppSrc), VideoError> {
+    let elements = decoder.gst_record_elements(filename, options)?;
+    let (pipeline, appsrc) = create_appsrc_record_pipeline(&elements, false)?;
+    if let Err(err) = buffer.start_recording(&appsrc) {
+        pipeline.set_state(gst::State::Null).ok();
+        return Err(err);
+    }
+    Ok((pipeline, appsrc))
+}
+
+// 在 tee_source 上分出一路压缩视频流写入预录缓冲区
+pub fn attach_pre_record_buffer(
+    pipeline: &Pipeline,
+    buffer: Arc<PreRecordBuffer>,
+) -> Result<(), VideoError> {
+    let tee_source = pipeline
+        .by_name("tee_source")
+        .ok_or_else(|| VideoError::ElementNotFound(String::from("tee_source")))?;
+    let queue = gst::ElementFactory::make("queue")
+        .build()
+        .map_err(|_| VideoError::MissingElement(String::from("queue")))?;
+    let appsink = gst_app::AppSink::builder()
+        .name("pre_record")
+        .sync(false)
+        .build();
+    let appsink_element: &Element = appsink.upcast_ref();
+    add_elements(pipeline, &[&queue, appsink_element])?;
+    link_elements(&[&queue, appsink_element])?;
+    link_tee(&tee_source, &queue)?;
+    appsink.set_callbacks(
+        gst_app::AppSinkCallbacks::builder()
+            .new_sample(move |appsink| {
+                let sample = appsink.pull_sample().map_err(|_| gst::FlowError::Eos)?;
+                buffer.push(sample);
+                Ok(gst::FlowSuccess::Ok)
+            })
+            .build(),
+    );
+    Ok(())
+}
+
+// 拼接录制中网格单元的尺寸，各机位按界面中的网格位置与跨度排列
+pub const COMPOSITE_CELL_SIZE: (i32, i32) = (640, 360);
+const COMPOSITE_FRAMERATE: i32 = 25;
+
+fn composite_cell_caps() -> gst::Caps {
+    let (width, height) = COMPOSITE_CELL_SIZE;
+    gst::Caps::builder("video/x-raw")
+        .field("format", "I420")
+        .field("width", width)
+        .field("height", height)
+        .field("pixel-aspect-ratio", gst::Fraction::new(1, 1))
+        .build()
+}
+
+// 各机位的画面分别通过 appsrc 送入 compositor，合成后使用默认编码器写入单个文件
+pub fn create_composite_record_pipeline(
+    positions: &[GridPosition],
+    encoder: &VideoEncoder,
+    filename: &str,
+    options: &RecordOptions,
+) -> Result<(Pipeline, Vec<gst_app::AppSrc>), VideoError> {
+    let pipeline = gst::Pipeline::new(None);
+    let (width, height) = COMPOSITE_CELL_SIZE;
+    let columns = positions
+        .iter()
+        .map(|position| position.column + position.width)
+        .max()
+        .unwrap_or(1)
+        .max(1);
+    let rows = positions
+        .iter()
+        .map(|position| position.row + position.height)
+        .max()
+        .unwrap_or(1)
+        .max(1);
+    let compositor = gst::ElementFactory::make("compositor")
+        .name("compositor")
+        .build()
+        .map_err(|_| VideoError::MissingElement(String::from("compositor")))?;
+    // 没有画面的机位显示为黑色
+    compositor.set_property_from_value(
+        "background",
+        &EnumClass::new(compositor.property_type("background").unwrap())
+            .unwrap()
+            .to_value(1)
+            .unwrap(),
+    );
+    let capsfilter = gst::ElementFactory::make("capsfilter")
+        .property(
+            "caps",
+            gst::Caps::builder("video/x-raw")
+                .field("width", width * columns)
+                .field("height", height * rows)
+                .field("framerate", gst::Fraction::new(COMPOSITE_FRAMERATE, 1))
+                .build(),
+        )
+        .build()
+        .map_err(|_| VideoError::MissingElement(String::from("capsfilter")))?;
+    let elements = encoder.gst_record_elements(ColorspaceConversion::CPU, filename, options)?;
+    add_elements(&pipeline, &[&compositor, &capsfilter])?;
+    add_elements(&pipeline, &elements.iter().collect::<Vec<_>>())?;
+    link_elements(
+        &[&compositor, &capsfilter]
+            .into_iter()
+            .chain(elements.iter())
+            .collect::<Vec<_>>(),
+    )?;
+    let appsrcs = positions
+        .iter()
+        .enumerate()
+        .map(|(index, position)| {
+            let appsrc = gst_app::AppSrc::builder()
+                .name(format!("composite_{}", index))
+                .caps(&composite_cell_caps())
+                .format(gst::Format::Time)
+                .is_live(true)
+                .do_timestamp(true)
+                .build();
+            let queue = gst::ElementFactory::make("queue")
+                .build()
+                .map_err(|_| VideoError::MissingElement(String::from("queue")))?;
+            let appsrc_element: &Element = appsrc.upcast_ref();
+            add_elements(&pipeline, &[appsrc_element, &queue])?;
+            link_elements(&[appsrc_element, &queue])?;
+            let sinkpad = compositor
+                .request_pad_simple("sink_%u")
+                .ok_or_else(|| VideoError::RequestPad(compositor.name().to_string()))?;
+            sinkpad.set_property("xpos", position.column * width);
+            sinkpad.set_property("ypos", position.row * height);
+            sinkpad.set_property("width", position.width * width);
+            sinkpad.set_property("height", position.height * height);
+            // 画中画模式下缩略图叠放在主画面之上
+            let zorder = if position.width * position.height > 1 {
+                0u32
+            } else {
+                1
+            };
+            sinkpad.set_property("zorder", zorder);
+            sinkpad.set_property_from_value(
+                "sizing-policy",
+                &EnumClass::new(sinkpad.property_type("sizing-policy").unwrap())
+                    .unwrap()
+                    .to_value(1)
+                    .unwrap(),
+            );
+            queue
+                .static_pad("src")
+                .ok_or_else(|| VideoError::link(&queue.name(), &compositor.name()))?
+                .link(&sinkpad)
+                .map_err(|_| VideoError::link(&queue.name(), &compositor.name()))?;
+            Ok(appsrc)
+        })
+        .collect::<Result<Vec<_>, VideoError>>()?;
+    pipeline
+        .set_state(gst::State::Playing)
+        .map_err(|_| VideoError::StateChange(gst::State::Playing))?;
+    Ok((pipeline, appsrcs))
+}
+
+// 在 tee_decoded 上分出一路缩放后的画面，设置了拼接录制管道的 appsrc 时送入其中
+pub fn attach_composite_feed(
+    pipeline: &Pipeline,
+    colorspace_conversion: ColorspaceConversion,
+    target: Arc<Mutex<Option<gst_app::AppSrc>>>,
+) -> Result<(), VideoError> {
+    let tee_decoded = pipeline
+        .by_name("tee_decoded")
+        .ok_or_else(|| VideoError::ElementNotFound(String::from("tee_decoded")))?;
+    let queue = gst::ElementFactory::make("queue")
+        .build()
+        .map_err(|_| VideoError::MissingElement(String::from("queue")))?;
+    queue.set_property_from_value(
+        "leaky",
+        &EnumClass::new(queue.property_type("leaky").unwrap())
+            .unwrap()
+            .to_value(2)
+            .unwrap(),
+    );
+    let videoscale = gst::ElementFactory::make("videoscale")
+        .build()
+        .map_err(|_| VideoError::MissingElement(String::from("videoscale")))?;
+    let appsink = gst_app::AppSink::builder()
+        .name("composite_feed")
+        .caps(&composite_cell_caps())
+        .sync(false)
+        .max_buffers(1)
+        .drop(true)
+        .build();
+    let conversion_elements = colorspace_conversion.gst_display_elements()?;
+    let elements = [&queue]
+        .into_iter()
+        .chain(conversion_elements.iter())
+        .chain([&videoscale, appsink.upcast_ref()])
+        .collect::<Vec<_>>();
+    add_elements(pipeline, &elements)?;
+    link_elements(&elements)?;
+    appsink.set_callbacks(
+        gst_app::AppSinkCallbacks::builder()
+            .new_sample(move |appsink| {
+                let sample = appsink.pull_sample().map_err(|_| gst::FlowError::Eos)?;
+                if let Some(appsrc) = target.lock().unwrap().as_ref() {
+                    if let Some(mut buffer) = sample.buffer_owned() {
+                        // 时间戳由拼接录制管道重新生成
+                        {
+                            let buffer = buffer.make_mut();
+                            buffer.set_pts(None);
+                            buffer.set_dts(None);
+                            buffer.set_duration(None);
+                        }
+                        appsrc.push_buffer(buffer).ok();
+                    }
+                }
+                Ok(gst::FlowSuccess::Ok)
+            })
+            .build(),
+    );
+    link_tee(&tee_decoded, &queue)?;
+    for element in elements {
+        element.sync_state_with_parent().ok();
+    }
+    Ok(())
+}
+
+pub fn push_mat_to_appsrc(appsrc: &gst_app::AppSrc, mat: &Mat) -> Result<(), VideoError> {
+    if mat.typ() != cv::core::CV_8UC3 {
+        return Err(VideoError::Frame(FrameError::UnsupportedFormat(format!(
+            "OpenCV type {}",
+            mat.typ()
+        ))));
+    }
+    let (width, height) = (mat.cols(), mat.rows());
+    let caps_changed = appsrc.caps().map_or(true, |caps| {
+        caps.structure(0).map_or(true, |structure| {
+            structure.get::<i32>("width").ok() != Some(width)
+                || structure.get::<i32>("height").ok() != Some(height)
+        })
+    });
+    if caps_changed {
+        appsrc.set_caps(Some(
+            &gst::Caps::builder("video/x-raw")
+                .field("format", "RGB")
+                .field("width", width)
+                .field("height", height)
+                .field("framerate", gst::Fraction::new(0, 1))
+                .build(),
+        ));
+    }
+    let row_size = (width * 3) as usize;
+    let stride = default_row_stride(width, 3);
+    let mut data = vec![0; stride * height as usize];
+    for row in 0..height {
+        let source = mat.ptr(row).map_err(FrameError::from)?;
+        let offset = row as usize * stride;
+        data[offset..offset + row_size]
+            .copy_from_slice(unsafe { std::slice::from_raw_parts(source, row_size) });
+    }
+    appsrc
+        .push_buffer(gst::Buffer::from_slice(data))
+        .map_err(|_| VideoError::PushBuffer)?;
+    Ok(())
+}
+
+pub fn stop_processed_record_pipeline(
+    pipeline: &Pipeline,
+    appsrc: &gst_app::AppSrc,
+) -> Result<Future<()>, VideoError> {
+    stop_appsrc_record_pipeline(pipeline, std::slice::from_ref(appsrc))
+}
+
+pub fn stop_composite_record_pipeline(
+    pipeline: &Pipeline,
+    appsrcs: &[gst_app::AppSrc],
+) -> Result<Future<()>, VideoError> {
+    stop_appsrc_record_pipeline(pipeline, appsrcs)
+}
+
+// 向所有 appsrc 发送 EOS，文件写入完成后关闭管道
+fn stop_appsrc_record_pipeline(
+    pipeline: &Pipeline,
+    appsrcs: &[gst_app::AppSrc],
+) -> Result<Future<()>, VideoError> {
+    let filesink_pad = pipeline
+        .iterate_sinks()
+        .into_iter()
+        .flatten()
+        .next()
+        .and_then(|sink| file_sink_pad(&sink))
+        .ok_or_else(|| VideoError::ElementNotFound(String::from("filesink")))?;
+    let promise = Promise::new();
+    let future = promise.future();
+    let promise = Mutex::new(Some(promise));
+    filesink_pad.add_probe(PadProbeType::EVENT_BOTH, move |_pad, info| {
+        match &info.data {
+            Some(PadProbeData::Event(event)) => {
+                if let EventView::Eos(_) = event.view() {
+                    promise.lock().unwrap().take().unwrap().success(());
+                    PadProbeReturn::Remove
+                } else {
+                    PadProbeReturn::Pass
+                }
             }
-        }));
+            _ => PadProbeReturn::Pass,
+        }
+    });
+    for appsrc in appsrcs {
+        appsrc.end_of_stream().map_err(|_| VideoError::PushBuffer)?;
+    }
+    let future = future.map(clone!(@strong pipeline => move |_| {
+        pipeline.set_state(gst::State::Null).ok();
+    }));
     Ok(future)
 }
 
+fn add_elements(pipeline: &Pipeline, elements: &[&Element]) -> Result<(), VideoError> {
+    for element in elements {
+        pipeline
+            .add(*element)
+            .map_err(|_| VideoError::AddElement(element.name().to_string()))?;
+    }
+    Ok(())
+}
+
+fn link_elements(elements: &[&Element]) -> Result<(), VideoError> {
+    for pair in elements.windows(2) {
+        if let [a, b] = pair {
+            a.link(*b)
+                .map_err(|_| VideoError::link(&a.name(), &b.name()))?;
+        }
+    }
+    Ok(())
+}
+
+// 动态添加的输出端中带有 media 字段的 caps 可用于区分音视频
+fn pad_media(pad: &Pad) -> Option<String> {
+    pad.current_caps()?
+        .iter()
+        .find_map(|structure| structure.get::<String>("media").ok())
+}
+
+// pad-added 回调中无法返回错误，改为向总线发送错误消息，由管道监听转为错误提示
+fn report_link_error(element: &Element, error: VideoError) {
+    element_error!(element, gst::CoreError::Negotiation, ("{}", error));
+}
+
+fn link_tee(tee: &Element, element: &Element) -> Result<(), VideoError> {
+    let sinkpad = element
+        .static_pad("sink")
+        .ok_or_else(|| VideoError::link(&tee.name(), &element.name()))?;
+    tee.request_pad_simple("src_%u")
+        .ok_or_else(|| VideoError::RequestPad(tee.name().to_string()))?
+        .link(&sinkpad)
+        .map_err(|_| VideoError::link(&tee.name(), &element.name()))?;
+    Ok(())
+}
+
 pub fn create_decodebin_pipeline(
     source: VideoSource,
+    colorspace_conversion: ColorspaceConversion,
+    display_backend: VideoDisplayBackend,
     appsink_queue_leaky_enabled: bool,
-) -> Result<gst::Pipeline, String> {
+) -> Result<gst::Pipeline, VideoError> {
     let pipeline = gst::Pipeline::new(None);
     let uridecodebin = gst::ElementFactory::make("uridecodebin3")
         .build()
-        .map_err(|_| "Missing element: uridecodebin3")
+        .map_err(|_| VideoError::MissingElement(String::from("uridecodebin3")))
         .and(
             gst::ElementFactory::make("uridecodebin")
                 .build()
-                .map_err(|_| "Missing element: uridecodebin"),
+                .map_err(|_| VideoError::MissingElement(String::from("uridecodebin"))),
         )?;
-    let appsink = gst::ElementFactory::make("appsink")
-        .name("display")
-        .build()
-        .map_err(|_| "Missing element: appsink")?;
-    let caps_app = gst::caps::Caps::from_str("video/x-raw, format=RGB")
-        .map_err(|_| "Cannot create capability for appsink")?;
+    let appsink = display_backend.gst_sink_element()?;
     let tee_decoded = gst::ElementFactory::make("tee")
         .name("tee_decoded")
         .build()
-        .map_err(|_| "Missing element: tee")?;
+        .map_err(|_| VideoError::MissingElement(String::from("tee")))?;
     let queue_to_app = gst::ElementFactory::make("queue")
         .build()
-        .map_err(|_| "Missing element: queue")?;
-    let videoconvert = gst::ElementFactory::make("videoconvert")
-        .build()
-        .map_err(|_| "Missing element: videoconvert")?;
-    pipeline
-        .add_many(&[
-            &uridecodebin,
-            &appsink,
-            &tee_decoded,
-            &queue_to_app,
-            &videoconvert,
-        ])
-        .map_err(|_| "Cannot create pipeline")?;
+        .map_err(|_| VideoError::MissingElement(String::from("queue")))?;
+    let colorspace_conversion_elements =
+        display_backend.gst_conversion_elements(colorspace_conversion)?;
+    add_elements(
+        &pipeline,
+        &[&uridecodebin, &appsink, &tee_decoded, &queue_to_app],
+    )?;
+    add_elements(
+        &pipeline,
+        &colorspace_conversion_elements.iter().collect::<Vec<_>>(),
+    )?;
     if appsink_queue_leaky_enabled {
         queue_to_app.set_property_from_value(
             "leaky",
@@ -501,52 +1265,44 @@ pub fn create_decodebin_pipeline(
                 .unwrap(),
         );
     }
-    appsink.set_property("caps", caps_app);
-    videoconvert
-        .link(&appsink)
-        .map_err(|_| "Cannot link videoconvert to the appsink")?;
-    queue_to_app
-        .link(&videoconvert)
-        .map_err(|_| "Cannot link appsink queue to the videoconvert")?;
-    tee_decoded
-        .request_pad_simple("src_%u")
-        .unwrap()
-        .link(&queue_to_app.static_pad("sink").unwrap())
-        .map_err(|_| "Cannot link tee to appsink queue")?;
+    link_elements(
+        &[&queue_to_app]
+            .into_iter()
+            .chain(colorspace_conversion_elements.iter())
+            .chain([&appsink])
+            .collect::<Vec<_>>(),
+    )?;
+    link_tee(&tee_decoded, &queue_to_app)?;
     let url = match &source {
         VideoSource::RTP(url) | VideoSource::UDP(url) | VideoSource::RTSP(url) => url,
     };
     uridecodebin.set_property("uri", url.to_string());
     uridecodebin.connect("pad-added", true, move |args| {
-        if let [_element, pad] = args {
-            let pad = pad.get::<Pad>().unwrap();
-            let media =
-                pad.caps()
-                    .unwrap()
-                    .iter()
-                    .flat_map(|x| x.iter())
-                    .find_map(|(key, value)| {
-                        if key == "media" {
-                            Some(value.get::<String>().unwrap())
-                        } else {
-                            None
-                        }
-                    });
-            let video_sink_pad = tee_decoded.static_pad("sink").unwrap();
-            match media.as_deref() {
-                Some("video") => {
-                    pad.link(&video_sink_pad)
-                        .map_err(|_| "Cannot delay link uridecodebin to tee_decoded")
-                        .unwrap();
-                }
-                Some("audio") => {}
-                Some(_) | None => {
-                    if pad.can_link(&video_sink_pad) {
-                        pad.link(&video_sink_pad)
-                            .map_err(|_| "Cannot delay link uridecodebin to tee_decoded")
-                            .unwrap();
-                    }
+        if let [element, pad] = args {
+            let (element, pad) = match (element.get::<Element>(), pad.get::<Pad>()) {
+                (Ok(element), Ok(pad)) => (element, pad),
+                _ => return None,
+            };
+            let video_sink_pad = match tee_decoded.static_pad("sink") {
+                Some(pad) => pad,
+                None => {
+                    report_link_error(
+                        &element,
+                        VideoError::link(&element.name(), &tee_decoded.name()),
+                    );
+                    return None;
                 }
+            };
+            let linkable = match pad_media(&pad).as_deref() {
+                Some("video") => true,
+                Some("audio") => false,
+                Some(_) | None => pad.can_link(&video_sink_pad),
+            };
+            if linkable && pad.link(&video_sink_pad).is_err() {
+                report_link_error(
+                    &element,
+                    VideoError::link(&element.name(), &tee_decoded.name()),
+                );
             }
         }
         None
@@ -559,102 +1315,66 @@ pub fn create_pipeline(
     latency: u32,
     colorspace_conversion: ColorspaceConversion,
     decoder: VideoDecoder,
+    display_backend: VideoDisplayBackend,
     appsink_queue_leaky_enabled: bool,
-) -> Result<gst::Pipeline, String> {
+) -> Result<gst::Pipeline, VideoError> {
     let pipeline = gst::Pipeline::new(None);
     let src_elements = source.gst_src_elements(latency, &decoder)?;
     let (video_src, depay_elements) = src_elements
         .split_first()
-        .ok_or_else(|| "Source element is empty")?;
+        .ok_or_else(|| VideoError::ElementNotFound(String::from("source")))?;
     let video_src = video_src.clone();
-    let appsink = gst::ElementFactory::make("appsink")
-        .name("display")
-        .build()
-        .map_err(|_| "Missing element: appsink")?;
-    let caps_app = gst::caps::Caps::from_str("video/x-raw, format=RGB")
-        .map_err(|_| "Cannot create capability for appsink")?;
-    appsink.set_property("caps", caps_app);
+    let appsink = display_backend.gst_sink_element()?;
     let tee_source = gst::ElementFactory::make("tee")
         .name("tee_source")
         .build()
-        .map_err(|_| "Missing element: tee")?;
+        .map_err(|_| VideoError::MissingElement(String::from("tee")))?;
     let tee_decoded = gst::ElementFactory::make("tee")
         .name("tee_decoded")
         .build()
-        .map_err(|_| "Missing element: tee")?;
+        .map_err(|_| VideoError::MissingElement(String::from("tee")))?;
     let queue_to_decode = gst::ElementFactory::make("queue")
         .build()
-        .map_err(|_| "Missing element: queue")?;
+        .map_err(|_| VideoError::MissingElement(String::from("queue")))?;
     let queue_to_app = gst::ElementFactory::make("queue")
         .build()
-        .map_err(|_| "Missing element: queue")?;
-    let colorspace_conversion_elements = colorspace_conversion.gst_elements()?;
+        .map_err(|_| VideoError::MissingElement(String::from("queue")))?;
+    let colorspace_conversion_elements =
+        display_backend.gst_conversion_elements(colorspace_conversion)?;
     let decoder_elements = decoder.gst_main_elements()?;
 
-    pipeline
-        .add_many(&[
+    add_elements(
+        &pipeline,
+        &[
             &video_src,
             &appsink,
             &tee_decoded,
             &tee_source,
             &queue_to_app,
             &queue_to_decode,
-        ])
-        .map_err(|_| "Cannot create pipeline")?;
-    pipeline
-        .add_many(&colorspace_conversion_elements.iter().collect::<Vec<_>>())
-        .map_err(|_| "Cannot add colorspace conversion elements to pipeline")?;
-    for depay_element in depay_elements {
-        pipeline
-            .add(depay_element)
-            .map_err(|_| "Cannot add depay elements to pipeline")?;
-    }
-    for decoder_element in &decoder_elements {
-        pipeline
-            .add(decoder_element)
-            .map_err(|_| "Cannot add decoder elements element")?;
-    }
-    for element in depay_elements.windows(2) {
-        if let [a, b] = element {
-            a.link(b)
-                .map_err(|_| "Cannot link elements between depay elements")?;
-        }
-    }
-    for element in decoder_elements.windows(2) {
-        if let [a, b] = element {
-            a.link(b)
-                .map_err(|_| "Cannot link elements between decoder elements")?;
-        }
-    }
-    for element in colorspace_conversion_elements.windows(2) {
-        if let [a, b] = element {
-            a.link(b)
-                .map_err(|_| "Cannot link elements between colorspace conversion elements")?;
-        }
-    }
-    match (decoder_elements.first(), decoder_elements.last()) {
-        (Some(first), Some(last)) => {
-            queue_to_decode
-                .link(first)
-                .map_err(|_| "Cannot link queue to the first decoder element")?;
-            last.link(&tee_decoded)
-                .map_err(|_| "Cannot link last decode to tee")?;
-        }
-        _ => return Err("Missing decoder element".to_string()),
-    }
-    match (
-        colorspace_conversion_elements.first(),
-        colorspace_conversion_elements.last(),
-    ) {
-        (Some(first), Some(last)) => {
-            queue_to_app.link(first).map_err(|_| {
-                "Cannot link the last decoder element to first colorspace conversion element"
-            })?;
-            last.link(&appsink)
-                .map_err(|_| "Cannot link last colorspace conversion element to appsink")?;
-        }
-        _ => return Err("Missing decoder element".to_string()),
-    }
+        ],
+    )?;
+    add_elements(
+        &pipeline,
+        &colorspace_conversion_elements.iter().collect::<Vec<_>>(),
+    )?;
+    add_elements(&pipeline, &depay_elements.iter().collect::<Vec<_>>())?;
+    add_elements(&pipeline, &decoder_elements.iter().collect::<Vec<_>>())?;
+    link_elements(&depay_elements.iter().collect::<Vec<_>>())?;
+    link_elements(
+        &[&queue_to_decode]
+            .into_iter()
+            .chain(decoder_elements.iter())
+            .chain([&tee_decoded])
+            .collect::<Vec<_>>(),
+    )?;
+    link_elements(
+        &[&queue_to_app]
+            .into_iter()
+            .chain(colorspace_conversion_elements.iter())
+            .chain([&appsink])
+            .collect::<Vec<_>>(),
+    )?;
     if appsink_queue_leaky_enabled {
         queue_to_app.set_property_from_value(
             "leaky",
@@ -665,131 +1385,79 @@ pub fn create_pipeline(
         );
     }
     // appsink.set_property("sync", true);
-    tee_source
-        .request_pad_simple("src_%u")
-        .unwrap()
-        .link(&queue_to_decode.static_pad("sink").unwrap())
-        .map_err(|_| "Cannot link tee to decoder queue")?;
-    tee_decoded
-        .request_pad_simple("src_%u")
-        .unwrap()
-        .link(&queue_to_app.static_pad("sink").unwrap())
-        .map_err(|_| "Cannot link tee to appsink queue")?;
+    link_tee(&tee_source, &queue_to_decode)?;
+    link_tee(&tee_decoded, &queue_to_app)?;
     match (depay_elements.first(), depay_elements.last()) {
         (Some(first), Some(last)) => {
             let first = first.clone();
-            if let Some(src) = video_src.static_pad("src") {
-                src.link(&first.static_pad("sink").unwrap())
-                    .map_err(|_| "Cannot link video source element to the first depay element")
-                    .unwrap();
+            if video_src.static_pad("src").is_some() {
+                video_src
+                    .link(&first)
+                    .map_err(|_| VideoError::link(&video_src.name(), &first.name()))?;
             } else {
                 video_src.connect("pad-added", true, move |args| {
-                    if let [_element, pad] = args {
-                        let pad = pad.get::<Pad>().unwrap();
-                        let media = pad.caps().unwrap().iter().flat_map(|x| x.iter()).find_map(|(key, value)| {
-                            if key == "media" {
-                                Some(value.get::<String>().unwrap())
-                            } else {
-                                None
+                    if let [element, pad] = args {
+                        let (element, pad) = match (element.get::<Element>(), pad.get::<Pad>()) {
+                            (Ok(element), Ok(pad)) => (element, pad),
+                            _ => return None,
+                        };
+                        if pad_media(&pad).as_deref() == Some("video") {
+                            let linked = first
+                                .static_pad("sink")
+                                .map_or(false, |sinkpad| pad.link(&sinkpad).is_ok());
+                            if !linked {
+                                report_link_error(
+                                    &element,
+                                    VideoError::link(&element.name(), &first.name()),
+                                );
                             }
-                        });
-
-                        if media.map_or(false, |x| x.eq("video")) {
-                            pad.link(&first.static_pad("sink").unwrap()).map_err(|_| "Cannot delay link video source element to the first depay element").unwrap();
                         }
                     }
                     None
                 });
             }
             last.link(&tee_source)
-                .map_err(|_| "Cannot link the last depay element to tee")?;
+                .map_err(|_| VideoError::link(&last.name(), &tee_source.name()))?;
         }
         _ => video_src
             .link(&tee_source)
-            .map_err(|_| "Cannot link video source to tee")?,
+            .map_err(|_| VideoError::link(&video_src.name(), &tee_source.name()))?,
     }
     Ok(pipeline)
 }
 
-fn correct_underwater_color(src: Mat) -> Mat {
-    let mut image = Mat::default();
-    src.convert_to(&mut image, cv::core::CV_32FC3, 1.0, 0.0)
-        .expect("Cannot convert source image");
-    let image = (image / 255.0).into_result().unwrap();
-    let mut channels = cv::types::VectorOfMat::new();
-    cv::core::split(&image, &mut channels).expect("Cannot split image");
-    let [mut mean, mut std] = [cv::core::Scalar::default(); 2];
-    let image_original_size = image;
-    let mut image = Mat::default();
-    cv::imgproc::resize(
-        &image_original_size,
-        &mut image,
-        Size::new(128, 128),
-        0.0,
-        0.0,
-        imgproc::INTER_NEAREST,
-    )
-    .expect("Cannot resize image");
-    cv::core::mean_std_dev(&image, &mut mean, &mut std, &cv::core::no_array())
-        .expect("Cannot calculate mean and standard deviation for image");
-    const U: f64 = 3.0;
-    let min_max = mean
-        .iter()
-        .zip(std.iter())
-        .map(|(mean, std)| (mean - U * std, mean + U * std));
-    let channels = channels
-        .iter()
-        .zip(min_max)
-        .map(|(channel, (min, max))| (channel - VecN::from(min)) / (max - min) * 255.0)
-        .map(|x| x.into_result().and_then(|x| x.to_mat()).unwrap());
-    let channels = VectorOfMat::from_iter(channels);
-    let mut image = Mat::default();
-    cv::core::merge(&channels, &mut image).expect("Cannot merge result channels");
-    let mut result = Mat::default();
-    image
-        .convert_to(&mut result, cv::core::CV_8UC3, 1.0, 0.0)
-        .expect("Cannot convert result data type");
-    result
-}
-
-#[allow(dead_code)]
-fn apply_clahe(mut mat: Mat) -> Mat {
-    let mut channels = VectorOfMat::new();
-    cv::core::split(&mat, &mut channels).expect("Cannot split image");
-    if let Ok(mut clahe) = imgproc::create_clahe(2.0, Size::new(8, 8)) {
-        for mut channel in channels.iter() {
-            clahe
-                .apply(&channel.clone(), &mut channel)
-                .expect("Cannot apply CLAHE");
-        }
-    }
-    cv::core::merge(&channels, &mut mat).expect("Cannot merge result channels");
-    mat
+// appsink 收到的原始画面格式
+#[derive(Debug, Clone, PartialEq)]
+struct FrameFormat {
+    width: i32,
+    height: i32,
+    format: String,
 }
 
 pub fn attach_pipeline_callback(
     pipeline: &Pipeline,
-    sender: Sender<Mat>,
+    mailbox: Arc<FrameMailbox<Result<Mat, FrameError>>>,
     config: &SlaveConfigModel,
-) -> Result<(), String> {
-    let frame_size: Arc<Mutex<Option<(i32, i32)>>> = Arc::new(Mutex::new(None));
+) -> Result<(), VideoError> {
+    let frame_format: Arc<Mutex<Option<FrameFormat>>> = Arc::new(Mutex::new(None));
     let appsink = pipeline
         .by_name("display")
-        .unwrap()
+        .ok_or_else(|| VideoError::ElementNotFound(String::from("display")))?
         .dynamic_cast::<gst_app::AppSink>()
-        .unwrap();
-    let algorithm = config.video_algorithms.clone();
+        .map_err(|_| VideoError::MissingElement(String::from("appsink")))?;
+    appsink.set_max_buffers(config.appsink_max_buffers);
+    appsink.set_drop(config.appsink_drop);
     appsink.set_callbacks(
         gst_app::AppSinkCallbacks::builder()
-            .new_event(clone!(@strong frame_size => move |appsink| {
+            .new_event(clone!(@strong frame_format => move |appsink| {
                 if let Ok(miniobj) = appsink.pull_object() {
                     if let Ok(event) = miniobj.downcast::<gst::Event>() {
                         if let EventView::Caps(caps) = event.view() {
                             let caps = caps.caps();
                             if let Some(structure) = caps.structure(0) {
-                                match (structure.get("width"), structure.get("height")) {
-                                    (Ok(width), Ok(height)) => {
-                                        *frame_size.lock().unwrap() = Some((width, height));
+                                match (structure.get("width"), structure.get("height"), structure.get::<String>("format")) {
+                                    (Ok(width), Ok(height), Ok(format)) => {
+                                        *frame_format.lock().unwrap() = Some(FrameFormat { width, height, format });
                                     },
                                     _ => (),
                                 }
@@ -799,8 +1467,7 @@ pub fn attach_pipeline_callback(
                 }
                 true
             }))
-            .new_sample(clone!(@strong frame_size => move |appsink| {
-                let (width, height) = frame_size.lock().unwrap().ok_or(gst::FlowError::Flushing)?;
+            .new_sample(clone!(@strong frame_format => move |appsink| {
                 let sample = appsink.pull_sample().map_err(|_| gst::FlowError::Eos)?;
                 let buffer = sample.buffer().ok_or_else(|| {
                     element_error!(
@@ -818,37 +1485,122 @@ pub fn attach_pipeline_callback(
                     );
                     gst::FlowError::Error
                 })?;
-                let mat = unsafe {
-                    Mat::new_rows_cols_with_data(height, width, cv::core::CV_8UC3, map.as_ptr() as *mut c_void, cv::core::Mat_AUTO_STEP)
-                }.map_err(|_| gst::FlowError::CustomError)?.clone();
-                let mat = match algorithm.first() {
-                    Some(VideoAlgorithm::CLAHE) => {
-                        apply_clahe(correct_underwater_color(mat))
-                    },
-                    _ => mat,
+                // 解析失败的帧交给处理线程报告，不中断管道
+                let frame = match frame_format.lock().unwrap().as_ref() {
+                    Some(format) => mat_from_frame_data(map.as_slice(), format),
+                    None => Err(FrameError::MissingCaps),
                 };
-                sender.send(mat).unwrap();
+                mailbox.post(frame);
                 Ok(gst::FlowSuccess::Ok)
             }))
             .build());
     Ok(())
 }
 
+// GStreamer 的原始视频每行按 4 字节对齐
+fn default_row_stride(width: i32, bytes_per_pixel: i32) -> usize {
+    ((width * bytes_per_pixel + 3) & !3) as usize
+}
+
+fn mat_from_frame_data(data: &[u8], format: &FrameFormat) -> Result<Mat, FrameError> {
+    let (mat_type, bytes_per_pixel, conversion) = match format.format.as_str() {
+        "RGB" => (cv::core::CV_8UC3, 3, None),
+        "BGR" => (cv::core::CV_8UC3, 3, Some(cv::imgproc::COLOR_BGR2RGB)),
+        "RGBA" | "RGBx" => (cv::core::CV_8UC4, 4, Some(cv::imgproc::COLOR_RGBA2RGB)),
+        "BGRA" | "BGRx" => (cv::core::CV_8UC4, 4, Some(cv::imgproc::COLOR_BGRA2RGB)),
+        "GRAY8" => (cv::core::CV_8UC1, 1, Some(cv::imgproc::COLOR_GRAY2RGB)),
+        other => return Err(FrameError::UnsupportedFormat(other.to_string())),
+    };
+    let stride = default_row_stride(format.width, bytes_per_pixel);
+    let expected = stride * format.height as usize;
+    if data.len() < expected {
+        return Err(FrameError::InvalidBuffer {
+            expected,
+            actual: data.len(),
+        });
+    }
+    // 借用缓冲区的数据构造 Mat 后立即复制，之后缓冲区即可释放
+    let borrowed = unsafe {
+        Mat::new_rows_cols_with_data(
+            format.height,
+            format.width,
+            mat_type,
+            data.as_ptr() as *mut c_void,
+            stride,
+        )
+    }?;
+    match conversion {
+        Some(code) => {
+            let mut mat = Mat::default();
+            cv::imgproc::cvt_color(&borrowed, &mut mat, code, 0)?;
+            Ok(mat)
+        }
+        None => Ok(borrowed.try_clone()?),
+    }
+}
+
 pub trait MatExt {
-    fn as_pixbuf(&self) -> Pixbuf;
+    fn as_pixbuf(&self) -> Result<Pixbuf, FrameError>;
+}
+
+pub trait PixbufExt {
+    fn as_mat(&self) -> Result<Mat, FrameError>;
+}
+
+impl PixbufExt for Pixbuf {
+    fn as_mat(&self) -> Result<Mat, FrameError> {
+        let (mat_type, conversion) = match (self.n_channels(), self.bits_per_sample()) {
+            (3, 8) => (cv::core::CV_8UC3, None),
+            (4, 8) => (cv::core::CV_8UC4, Some(cv::imgproc::COLOR_RGBA2RGB)),
+            (channels, bits) => {
+                return Err(FrameError::UnsupportedFormat(format!(
+                    "Pixbuf {} 通道 {} 位",
+                    channels, bits
+                )))
+            }
+        };
+        let bytes = self.read_pixel_bytes();
+        let borrowed = unsafe {
+            Mat::new_rows_cols_with_data(
+                self.height(),
+                self.width(),
+                mat_type,
+                bytes.as_ptr() as *mut c_void,
+                self.rowstride() as usize,
+            )
+        }?;
+        match conversion {
+            Some(code) => {
+                let mut mat = Mat::default();
+                cv::imgproc::cvt_color(&borrowed, &mut mat, code, 0)?;
+                Ok(mat)
+            }
+            None => Ok(borrowed.try_clone()?),
+        }
+    }
 }
 
 impl MatExt for Mat {
-    fn as_pixbuf(&self) -> Pixbuf {
+    fn as_pixbuf(&self) -> Result<Pixbuf, FrameError> {
+        if self.typ() != cv::core::CV_8UC3 {
+            return Err(FrameError::UnsupportedFormat(format!(
+                "OpenCV type {}",
+                self.typ()
+            )));
+        }
         let width = self.cols();
         let height = self.rows();
-        // let bytes = glib::Bytes::from(self.data_bytes().unwrap());
-        // let pixbuf = Pixbuf::from_bytes(&bytes, Colorspace::Rgb, false, 8, width, height, 1);
-        let size = (width * height * 3) as usize;
-        let pixbuf = Pixbuf::new(Colorspace::Rgb, false, 8, width, height).unwrap();
-        unsafe {
-            pixbuf.pixels()[..size].copy_from_slice(self.data_bytes().unwrap());
-        }
-        pixbuf
+        let pixbuf = Pixbuf::new(Colorspace::Rgb, false, 8, width, height)
+            .ok_or(FrameError::PixbufAllocation(width, height))?;
+        let row_size = (width * 3) as usize;
+        let rowstride = pixbuf.rowstride() as usize;
+        let pixels = unsafe { pixbuf.pixels() };
+        // Pixbuf 的每行可能带有对齐填充，需要逐行复制
+        for row in 0..height {
+            let source = unsafe { std::slice::from_raw_parts(self.ptr(row)?, row_size) };
+            let offset = row as usize * rowstride;
+            pixels[offset..offset + row_size].copy_from_slice(source);
+        }
+        Ok(pixbuf)
     }
 }
@@ -0,0 +1,997 @@
+use cv::{
+    core::{no_array, Point, Scalar, Size, Vec3f, VecN, Vector, BORDER_CONSTANT, BORDER_DEFAULT},
+    imgproc,
+    prelude::*,
+    types::VectorOfMat,
+};
+use opencv as cv;
+use serde::{Deserialize, Serialize};
+use strum_macros::EnumIter;
+
+use super::frame_error::FrameError;
+
+pub trait FrameProcessor: Send {
+    fn process(&mut self, frame: Mat) -> Result<Mat, FrameError>;
+}
+
+pub struct AlgorithmParameter {
+    pub name: &'static str,
+    pub value: f64,
+    pub min: f64,
+    pub max: f64,
+    pub step: f64,
+    pub digits: u32,
+}
+
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub enum VideoAlgorithm {
+    UnderwaterColorCorrection {
+        range: f64,
+    },
+    CLAHE {
+        clip_limit: f64,
+        tile_grid_size: i32,
+    },
+    Dehaze {
+        strength: f64,
+        patch_size: i32,
+    },
+    GrayWorldWhiteBalance {
+        strength: f64,
+    },
+    WhitePatchWhiteBalance {
+        strength: f64,
+        percentile: f64,
+    },
+    UnsharpMask {
+        amount: f64,
+        sigma: f64,
+    },
+    FusionEnhance {
+        strength: f64,
+        levels: i32,
+    },
+}
+
+impl ToString for VideoAlgorithm {
+    fn to_string(&self) -> String {
+        match self {
+            VideoAlgorithm::UnderwaterColorCorrection { .. } => "水下色彩校正",
+            VideoAlgorithm::CLAHE { .. } => "CLAHE",
+            VideoAlgorithm::Dehaze { .. } => "暗通道去雾",
+            VideoAlgorithm::GrayWorldWhiteBalance { .. } => "灰度世界白平衡",
+            VideoAlgorithm::WhitePatchWhiteBalance { .. } => "白点白平衡",
+            VideoAlgorithm::UnsharpMask { .. } => "反锐化掩模",
+            VideoAlgorithm::FusionEnhance { .. } => "多尺度融合增强",
+        }
+        .to_string()
+    }
+}
+
+impl VideoAlgorithm {
+    pub fn all() -> impl Iterator<Item = VideoAlgorithm> {
+        [
+            VideoAlgorithm::UnderwaterColorCorrection { range: 3.0 },
+            VideoAlgorithm::CLAHE {
+                clip_limit: 2.0,
+                tile_grid_size: 8,
+            },
+            VideoAlgorithm::Dehaze {
+                strength: 0.85,
+                patch_size: 15,
+            },
+            VideoAlgorithm::GrayWorldWhiteBalance { strength: 1.0 },
+            VideoAlgorithm::WhitePatchWhiteBalance {
+                strength: 1.0,
+                percentile: 99.0,
+            },
+            VideoAlgorithm::UnsharpMask {
+                amount: 1.0,
+                sigma: 2.0,
+            },
+            VideoAlgorithm::FusionEnhance {
+                strength: 1.0,
+                levels: 5,
+            },
+        ]
+        .into_iter()
+    }
+
+    pub fn parameters(&self) -> Vec<AlgorithmParameter> {
+        match self {
+            VideoAlgorithm::UnderwaterColorCorrection { range } => vec![AlgorithmParameter {
+                name: "拉伸范围（标准差倍数）",
+                value: *range,
+                min: 0.5,
+                max: 10.0,
+                step: 0.1,
+                digits: 1,
+            }],
+            VideoAlgorithm::CLAHE {
+                clip_limit,
+                tile_grid_size,
+            } => vec![
+                AlgorithmParameter {
+                    name: "对比度限制",
+                    value: *clip_limit,
+                    min: 0.1,
+                    max: 40.0,
+                    step: 0.1,
+                    digits: 1,
+                },
+                AlgorithmParameter {
+                    name: "网格大小",
+                    value: *tile_grid_size as f64,
+                    min: 1.0,
+                    max: 64.0,
+                    step: 1.0,
+                    digits: 0,
+                },
+            ],
+            VideoAlgorithm::Dehaze {
+                strength,
+                patch_size,
+            } => vec![
+                strength_parameter("去雾强度", *strength),
+                AlgorithmParameter {
+                    name: "窗口大小",
+                    value: *patch_size as f64,
+                    min: 3.0,
+                    max: 61.0,
+                    step: 2.0,
+                    digits: 0,
+                },
+            ],
+            VideoAlgorithm::GrayWorldWhiteBalance { strength } => {
+                vec![strength_parameter("强度", *strength)]
+            }
+            VideoAlgorithm::WhitePatchWhiteBalance {
+                strength,
+                percentile,
+            } => vec![
+                strength_parameter("强度", *strength),
+                AlgorithmParameter {
+                    name: "亮度百分位",
+                    value: *percentile,
+                    min: 90.0,
+                    max: 100.0,
+                    step: 0.1,
+                    digits: 1,
+                },
+            ],
+            VideoAlgorithm::UnsharpMask { amount, sigma } => vec![
+                AlgorithmParameter {
+                    name: "锐化量",
+                    value: *amount,
+                    min: 0.0,
+                    max: 5.0,
+                    step: 0.1,
+                    digits: 1,
+                },
+                AlgorithmParameter {
+                    name: "模糊半径（σ）",
+                    value: *sigma,
+                    min: 0.1,
+                    max: 20.0,
+                    step: 0.1,
+                    digits: 1,
+                },
+            ],
+            VideoAlgorithm::FusionEnhance { strength, levels } => vec![
+                strength_parameter("融合强度", *strength),
+                AlgorithmParameter {
+                    name: "金字塔层数",
+                    value: *levels as f64,
+                    min: 1.0,
+                    max: 8.0,
+                    step: 1.0,
+                    digits: 0,
+                },
+            ],
+        }
+    }
+
+    pub fn set_parameter(&mut self, index: usize, value: f64) {
+        match (self, index) {
+            (VideoAlgorithm::UnderwaterColorCorrection { range }, 0) => *range = value,
+            (VideoAlgorithm::CLAHE { clip_limit, .. }, 0) => *clip_limit = value,
+            (VideoAlgorithm::CLAHE { tile_grid_size, .. }, 1) => *tile_grid_size = value as i32,
+            (VideoAlgorithm::Dehaze { strength, .. }, 0) => *strength = value,
+            (VideoAlgorithm::Dehaze { patch_size, .. }, 1) => *patch_size = value as i32,
+            (VideoAlgorithm::GrayWorldWhiteBalance { strength }, 0) => *strength = value,
+            (VideoAlgorithm::WhitePatchWhiteBalance { strength, .. }, 0) => *strength = value,
+            (VideoAlgorithm::WhitePatchWhiteBalance { percentile, .. }, 1) => *percentile = value,
+            (VideoAlgorithm::UnsharpMask { amount, .. }, 0) => *amount = value,
+            (VideoAlgorithm::UnsharpMask { sigma, .. }, 1) => *sigma = value,
+            (VideoAlgorithm::FusionEnhance { strength, .. }, 0) => *strength = value,
+            (VideoAlgorithm::FusionEnhance { levels, .. }, 1) => *levels = value as i32,
+            _ => (),
+        }
+    }
+
+    pub fn processor(&self) -> Box<dyn FrameProcessor> {
+        match self.clone() {
+            VideoAlgorithm::UnderwaterColorCorrection { range } => {
+                Box::new(UnderwaterColorCorrection { range })
+            }
+            VideoAlgorithm::CLAHE {
+                clip_limit,
+                tile_grid_size,
+            } => Box::new(Clahe {
+                clip_limit,
+                tile_grid_size,
+            }),
+            VideoAlgorithm::Dehaze {
+                strength,
+                patch_size,
+            } => Box::new(Dehaze {
+                strength,
+                patch_size,
+            }),
+            VideoAlgorithm::GrayWorldWhiteBalance { strength } => {
+                Box::new(GrayWorldWhiteBalance { strength })
+            }
+            VideoAlgorithm::WhitePatchWhiteBalance {
+                strength,
+                percentile,
+            } => Box::new(WhitePatchWhiteBalance {
+                strength,
+                percentile,
+            }),
+            VideoAlgorithm::UnsharpMask { amount, sigma } => {
+                Box::new(UnsharpMask { amount, sigma })
+            }
+            VideoAlgorithm::FusionEnhance { strength, levels } => {
+                Box::new(FusionEnhance { strength, levels })
+            }
+        }
+    }
+}
+
+fn strength_parameter(name: &'static str, value: f64) -> AlgorithmParameter {
+    AlgorithmParameter {
+        name,
+        value,
+        min: 0.0,
+        max: 1.0,
+        step: 0.05,
+        digits: 2,
+    }
+}
+
+#[derive(EnumIter, PartialEq, Clone, Copy, Debug)]
+pub enum VideoAlgorithmPreset {
+    UnderwaterCLAHE,
+    TurbidWater,
+}
+
+impl ToString for VideoAlgorithmPreset {
+    fn to_string(&self) -> String {
+        match self {
+            VideoAlgorithmPreset::UnderwaterCLAHE => "水下色彩校正 + CLAHE",
+            VideoAlgorithmPreset::TurbidWater => "浑浊水体（白平衡 + 去雾 + 锐化）",
+        }
+        .to_string()
+    }
+}
+
+impl VideoAlgorithmPreset {
+    pub fn algorithms(&self) -> Vec<VideoAlgorithm> {
+        match self {
+            VideoAlgorithmPreset::UnderwaterCLAHE => vec![
+                VideoAlgorithm::UnderwaterColorCorrection { range: 3.0 },
+                VideoAlgorithm::CLAHE {
+                    clip_limit: 2.0,
+                    tile_grid_size: 8,
+                },
+            ],
+            VideoAlgorithmPreset::TurbidWater => vec![
+                VideoAlgorithm::GrayWorldWhiteBalance { strength: 1.0 },
+                VideoAlgorithm::Dehaze {
+                    strength: 0.85,
+                    patch_size: 15,
+                },
+                VideoAlgorithm::UnsharpMask {
+                    amount: 1.0,
+                    sigma: 2.0,
+                },
+            ],
+        }
+    }
+}
+
+// 按顺序依次对画面应用各个算法
+#[derive(Default)]
+pub struct FrameProcessorChain {
+    processors: Vec<Box<dyn FrameProcessor>>,
+}
+
+impl std::fmt::Debug for FrameProcessorChain {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FrameProcessorChain")
+            .field("len", &self.processors.len())
+            .finish()
+    }
+}
+
+impl FrameProcessorChain {
+    pub fn new(algorithms: &[VideoAlgorithm]) -> Self {
+        Self {
+            processors: algorithms.iter().map(VideoAlgorithm::processor).collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.processors.is_empty()
+    }
+
+    pub fn process(&mut self, frame: Mat) -> Result<Mat, FrameError> {
+        self.processors
+            .iter_mut()
+            .try_fold(frame, |frame, processor| processor.process(frame))
+    }
+}
+
+struct UnderwaterColorCorrection {
+    range: f64,
+}
+
+impl FrameProcessor for UnderwaterColorCorrection {
+    fn process(&mut self, frame: Mat) -> Result<Mat, FrameError> {
+        Ok(correct_underwater_color(frame, self.range)?)
+    }
+}
+
+struct Clahe {
+    clip_limit: f64,
+    tile_grid_size: i32,
+}
+
+impl FrameProcessor for Clahe {
+    fn process(&mut self, frame: Mat) -> Result<Mat, FrameError> {
+        Ok(apply_clahe(frame, self.clip_limit, self.tile_grid_size)?)
+    }
+}
+
+struct Dehaze {
+    strength: f64,
+    patch_size: i32,
+}
+
+impl FrameProcessor for Dehaze {
+    fn process(&mut self, frame: Mat) -> Result<Mat, FrameError> {
+        Ok(dehaze(&frame, self.strength, self.patch_size)?)
+    }
+}
+
+struct GrayWorldWhiteBalance {
+    strength: f64,
+}
+
+impl FrameProcessor for GrayWorldWhiteBalance {
+    fn process(&mut self, frame: Mat) -> Result<Mat, FrameError> {
+        Ok(gray_world_white_balance(&frame, self.strength)?)
+    }
+}
+
+struct WhitePatchWhiteBalance {
+    strength: f64,
+    percentile: f64,
+}
+
+impl FrameProcessor for WhitePatchWhiteBalance {
+    fn process(&mut self, frame: Mat) -> Result<Mat, FrameError> {
+        Ok(white_patch_white_balance(
+            &frame,
+            self.strength,
+            self.percentile,
+        )?)
+    }
+}
+
+struct UnsharpMask {
+    amount: f64,
+    sigma: f64,
+}
+
+impl FrameProcessor for UnsharpMask {
+    fn process(&mut self, frame: Mat) -> Result<Mat, FrameError> {
+        Ok(unsharp_mask(&frame, self.amount, self.sigma)?)
+    }
+}
+
+struct FusionEnhance {
+    strength: f64,
+    levels: i32,
+}
+
+impl FrameProcessor for FusionEnhance {
+    fn process(&mut self, frame: Mat) -> Result<Mat, FrameError> {
+        Ok(fusion_enhance(&frame, self.strength, self.levels)?)
+    }
+}
+
+fn correct_underwater_color(src: Mat, range: f64) -> cv::Result<Mat> {
+    let mut image = Mat::default();
+    src.convert_to(&mut image, cv::core::CV_32FC3, 1.0 / 255.0, 0.0)?;
+    let mut channels = cv::types::VectorOfMat::new();
+    cv::core::split(&image, &mut channels)?;
+    let [mut mean, mut std] = [cv::core::Scalar::default(); 2];
+    let image_original_size = image;
+    let mut image = Mat::default();
+    cv::imgproc::resize(
+        &image_original_size,
+        &mut image,
+        Size::new(128, 128),
+        0.0,
+        0.0,
+        imgproc::INTER_NEAREST,
+    )?;
+    cv::core::mean_std_dev(&image, &mut mean, &mut std, &cv::core::no_array())?;
+    let min_max = mean
+        .iter()
+        .zip(std.iter())
+        .map(|(mean, std)| (mean - range * std, mean + range * std));
+    let channels = channels
+        .iter()
+        .zip(min_max)
+        .map(|(channel, (min, max))| {
+            ((channel - VecN::from(min)) / (max - min) * 255.0)
+                .into_result()
+                .and_then(|x| x.to_mat())
+        })
+        .collect::<cv::Result<VectorOfMat>>()?;
+    let mut image = Mat::default();
+    cv::core::merge(&channels, &mut image)?;
+    let mut result = Mat::default();
+    image.convert_to(&mut result, cv::core::CV_8UC3, 1.0, 0.0)?;
+    Ok(result)
+}
+
+fn apply_clahe(mut mat: Mat, clip_limit: f64, tile_grid_size: i32) -> cv::Result<Mat> {
+    let mut channels = VectorOfMat::new();
+    cv::core::split(&mat, &mut channels)?;
+    let mut clahe = imgproc::create_clahe(clip_limit, Size::new(tile_grid_size, tile_grid_size))?;
+    for mut channel in channels.iter() {
+        clahe.apply(&channel.clone(), &mut channel)?;
+    }
+    cv::core::merge(&channels, &mut mat)?;
+    Ok(mat)
+}
+
+// 暗通道先验去雾：J = (I - A) / max(t, t0) + A，其中 t = 1 - ω · dark(I / A)
+fn dehaze(src: &Mat, strength: f64, patch_size: i32) -> cv::Result<Mat> {
+    const MIN_TRANSMISSION: f64 = 0.1;
+    let mut image = Mat::default();
+    src.convert_to(&mut image, cv::core::CV_32FC3, 1.0 / 255.0, 0.0)?;
+    let kernel = imgproc::get_structuring_element(
+        imgproc::MORPH_RECT,
+        Size::new(patch_size, patch_size),
+        Point::new(-1, -1),
+    )?;
+    let mut channels = VectorOfMat::new();
+    cv::core::split(&image, &mut channels)?;
+    // 取暗通道中最亮的像素作为大气光
+    let mut max_loc = Point::default();
+    cv::core::min_max_loc(
+        &dark_channel(&channels, &kernel)?,
+        None,
+        None,
+        None,
+        Some(&mut max_loc),
+        &no_array(),
+    )?;
+    let atmosphere = *image.at_2d::<Vec3f>(max_loc.y, max_loc.x)?;
+    let normalized = channels
+        .iter()
+        .zip(atmosphere.iter())
+        .map(|(channel, light)| {
+            let mut normalized = Mat::default();
+            channel
+                .convert_to(&mut normalized, -1, 1.0 / (*light as f64).max(1e-3), 0.0)
+                .map(|_| normalized)
+        })
+        .collect::<cv::Result<VectorOfMat>>()?;
+    let mut transmission = Mat::default();
+    dark_channel(&normalized, &kernel)?.convert_to(&mut transmission, -1, -strength, 1.0)?;
+    // 平滑透射率图，减轻块状伪影
+    let mut smoothed = Mat::default();
+    imgproc::gaussian_blur(
+        &transmission,
+        &mut smoothed,
+        Size::default(),
+        patch_size as f64,
+        0.0,
+        BORDER_DEFAULT,
+    )?;
+    let lower_bound = Mat::new_size_with_default(
+        smoothed.size()?,
+        cv::core::CV_32FC1,
+        Scalar::all(MIN_TRANSMISSION),
+    )?;
+    let mut transmission = Mat::default();
+    cv::core::max(&smoothed, &lower_bound, &mut transmission)?;
+    let recovered = channels
+        .iter()
+        .zip(atmosphere.iter())
+        .map(|(channel, light)| {
+            let light = *light as f64;
+            let mut shifted = Mat::default();
+            channel.convert_to(&mut shifted, -1, 1.0, -light)?;
+            let mut divided = Mat::default();
+            cv::core::divide2(&shifted, &transmission, &mut divided, 1.0, -1)?;
+            let mut recovered = Mat::default();
+            divided.convert_to(&mut recovered, -1, 1.0, light)?;
+            Ok(recovered)
+        })
+        .collect::<cv::Result<VectorOfMat>>()?;
+    let mut image = Mat::default();
+    cv::core::merge(&recovered, &mut image)?;
+    let mut result = Mat::default();
+    image.convert_to(&mut result, cv::core::CV_8UC3, 255.0, 0.0)?;
+    Ok(result)
+}
+
+fn dark_channel(channels: &VectorOfMat, kernel: &Mat) -> cv::Result<Mat> {
+    let mut minimum = channels.get(0)?;
+    for channel in channels.iter().skip(1) {
+        let mut next = Mat::default();
+        cv::core::min(&minimum, &channel, &mut next)?;
+        minimum = next;
+    }
+    let mut dark = Mat::default();
+    imgproc::erode(
+        &minimum,
+        &mut dark,
+        kernel,
+        Point::new(-1, -1),
+        1,
+        BORDER_CONSTANT,
+        imgproc::morphology_default_border_value()?,
+    )?;
+    Ok(dark)
+}
+
+// 假设场景平均颜色为灰色，将各通道均值拉到同一水平
+fn gray_world_white_balance(src: &Mat, strength: f64) -> cv::Result<Mat> {
+    let mean = cv::core::mean(src, &no_array())?;
+    let gray = (mean[0] + mean[1] + mean[2]) / 3.0;
+    apply_channel_gains(src, [0, 1, 2].map(|i| gray / mean[i].max(1.0)), strength)
+}
+
+// 假设场景中最亮的部分为白色，按各通道的高百分位亮度拉伸到满幅
+fn white_patch_white_balance(src: &Mat, strength: f64, percentile: f64) -> cv::Result<Mat> {
+    let mut channels = VectorOfMat::new();
+    cv::core::split(src, &mut channels)?;
+    let mut gains = [1.0; 3];
+    for (gain, channel) in gains.iter_mut().zip(channels.iter()) {
+        *gain = 255.0 / channel_percentile(&channel, percentile)?.max(1.0);
+    }
+    apply_channel_gains(src, gains, strength)
+}
+
+fn channel_percentile(channel: &Mat, percentile: f64) -> cv::Result<f64> {
+    let mut histogram = Mat::default();
+    imgproc::calc_hist(
+        &VectorOfMat::from_iter([channel.clone()]),
+        &Vector::<i32>::from_slice(&[0]),
+        &no_array(),
+        &mut histogram,
+        &Vector::<i32>::from_slice(&[256]),
+        &Vector::<f32>::from_slice(&[0.0, 256.0]),
+        false,
+    )?;
+    let threshold = (channel.rows() * channel.cols()) as f64 * percentile / 100.0;
+    let mut count = 0.0;
+    for value in 0..256 {
+        count += *histogram.at::<f32>(value)? as f64;
+        if count >= threshold {
+            return Ok(value as f64);
+        }
+    }
+    Ok(255.0)
+}
+
+// 强度为 0 时保持原样，为 1 时完全应用增益
+fn apply_channel_gains(src: &Mat, gains: [f64; 3], strength: f64) -> cv::Result<Mat> {
+    let mut channels = VectorOfMat::new();
+    cv::core::split(src, &mut channels)?;
+    let channels = channels
+        .iter()
+        .zip(gains)
+        .map(|(channel, gain)| {
+            let mut balanced = Mat::default();
+            channel
+                .convert_to(&mut balanced, -1, 1.0 + strength * (gain - 1.0), 0.0)
+                .map(|_| balanced)
+        })
+        .collect::<cv::Result<VectorOfMat>>()?;
+    let mut result = Mat::default();
+    cv::core::merge(&channels, &mut result)?;
+    Ok(result)
+}
+
+fn unsharp_mask(src: &Mat, amount: f64, sigma: f64) -> cv::Result<Mat> {
+    let mut blurred = Mat::default();
+    imgproc::gaussian_blur(
+        src,
+        &mut blurred,
+        Size::default(),
+        sigma,
+        0.0,
+        BORDER_DEFAULT,
+    )?;
+    let mut result = Mat::default();
+    cv::core::add_weighted(src, 1.0 + amount, &blurred, -amount, 0.0, &mut result, -1)?;
+    Ok(result)
+}
+
+// 基于融合的水下图像增强：以白平衡结果及其对比度增强结果作为两路输入，
+// 按拉普拉斯对比度、显著性和饱和度计算权重，在拉普拉斯金字塔上逐层融合
+fn fusion_enhance(src: &Mat, strength: f64, levels: i32) -> cv::Result<Mat> {
+    let white_balanced = gray_world_white_balance(src, 1.0)?;
+    let contrasted = apply_clahe(white_balanced.clone(), 2.0, 8)?;
+    let inputs = [white_balanced, contrasted]
+        .iter()
+        .map(|input| {
+            let mut image = Mat::default();
+            input.convert_to(&mut image, cv::core::CV_32FC3, 1.0 / 255.0, 0.0)?;
+            let weight = fusion_weight(&image)?;
+            Ok((image, weight))
+        })
+        .collect::<cv::Result<Vec<_>>>()?;
+    let mut total_weight = Mat::default();
+    cv::core::add(
+        &inputs[0].1,
+        &inputs[1].1,
+        &mut total_weight,
+        &no_array(),
+        -1,
+    )?;
+    let mut fused_pyramid: Vec<Mat> = Vec::new();
+    for (image, weight) in inputs.iter() {
+        let mut normalized_weight = Mat::default();
+        cv::core::divide2(weight, &total_weight, &mut normalized_weight, 1.0, -1)?;
+        let mut weight = Mat::default();
+        cv::core::merge(
+            &VectorOfMat::from_iter([
+                normalized_weight.clone(),
+                normalized_weight.clone(),
+                normalized_weight,
+            ]),
+            &mut weight,
+        )?;
+        let weight_pyramid = gaussian_pyramid(&weight, levels)?;
+        let image_pyramid = laplacian_pyramid(image, levels)?;
+        for (level, (image, weight)) in image_pyramid.iter().zip(weight_pyramid.iter()).enumerate()
+        {
+            let mut weighted = Mat::default();
+            cv::core::multiply(image, weight, &mut weighted, 1.0, -1)?;
+            match fused_pyramid.get_mut(level) {
+                Some(fused) => {
+                    let mut sum = Mat::default();
+                    cv::core::add(fused, &weighted, &mut sum, &no_array(), -1)?;
+                    *fused = sum;
+                }
+                None => fused_pyramid.push(weighted),
+            }
+        }
+    }
+    let fused = collapse_laplacian_pyramid(fused_pyramid)?;
+    let mut original = Mat::default();
+    src.convert_to(&mut original, cv::core::CV_32FC3, 1.0 / 255.0, 0.0)?;
+    let mut blended = Mat::default();
+    cv::core::add_weighted(
+        &original,
+        1.0 - strength,
+        &fused,
+        strength,
+        0.0,
+        &mut blended,
+        -1,
+    )?;
+    let mut result = Mat::default();
+    blended.convert_to(&mut result, cv::core::CV_8UC3, 255.0, 0.0)?;
+    Ok(result)
+}
+
+fn fusion_weight(image: &Mat) -> cv::Result<Mat> {
+    let mut gray = Mat::default();
+    imgproc::cvt_color(image, &mut gray, imgproc::COLOR_RGB2GRAY, 0)?;
+    // 拉普拉斯对比度
+    let mut laplacian = Mat::default();
+    imgproc::laplacian(&gray, &mut laplacian, -1, 1, 1.0, 0.0, BORDER_DEFAULT)?;
+    let laplacian = cv::core::abs(&laplacian)?.to_mat()?;
+    // 显著性：平滑后的亮度与全局平均亮度之差
+    let mut blurred = Mat::default();
+    imgproc::gaussian_blur(
+        &gray,
+        &mut blurred,
+        Size::new(5, 5),
+        0.0,
+        0.0,
+        BORDER_DEFAULT,
+    )?;
+    let mean = cv::core::mean(&gray, &no_array())?[0];
+    let mut saliency = Mat::default();
+    blurred.convert_to(&mut saliency, -1, 1.0, -mean)?;
+    let saliency = cv::core::abs(&saliency)?.to_mat()?;
+    // 饱和度：各通道与亮度的标准差
+    let mut channels = VectorOfMat::new();
+    cv::core::split(image, &mut channels)?;
+    let mut variance = Mat::zeros_size(gray.size()?, cv::core::CV_32FC1)?.to_mat()?;
+    for channel in channels.iter() {
+        let mut difference = Mat::default();
+        cv::core::subtract(&channel, &gray, &mut difference, &no_array(), -1)?;
+        let mut squared = Mat::default();
+        cv::core::multiply(&difference, &difference, &mut squared, 1.0 / 3.0, -1)?;
+        let mut sum = Mat::default();
+        cv::core::add(&variance, &squared, &mut sum, &no_array(), -1)?;
+        variance = sum;
+    }
+    let mut saturation = Mat::default();
+    cv::core::sqrt(&variance, &mut saturation)?;
+    let mut weight = Mat::default();
+    cv::core::add(&laplacian, &saliency, &mut weight, &no_array(), -1)?;
+    let mut sum = Mat::default();
+    cv::core::add(&weight, &saturation, &mut sum, &no_array(), -1)?;
+    // 避免两路权重同时为 0 时除零
+    let mut weight = Mat::default();
+    sum.convert_to(&mut weight, -1, 1.0, 1e-3)?;
+    Ok(weight)
+}
+
+fn gaussian_pyramid(image: &Mat, levels: i32) -> cv::Result<Vec<Mat>> {
+    let mut pyramid = vec![image.clone()];
+    for level in 1..levels.max(1) as usize {
+        let mut down = Mat::default();
+        imgproc::pyr_down(
+            &pyramid[level - 1],
+            &mut down,
+            Size::default(),
+            BORDER_DEFAULT,
+        )?;
+        pyramid.push(down);
+    }
+    Ok(pyramid)
+}
+
+fn laplacian_pyramid(image: &Mat, levels: i32) -> cv::Result<Vec<Mat>> {
+    let gaussian = gaussian_pyramid(image, levels)?;
+    let mut pyramid = Vec::with_capacity(gaussian.len());
+    for pair in gaussian.windows(2) {
+        let mut up = Mat::default();
+        imgproc::pyr_up(&pair[1], &mut up, pair[0].size()?, BORDER_DEFAULT)?;
+        let mut difference = Mat::default();
+        cv::core::subtract(&pair[0], &up, &mut difference, &no_array(), -1)?;
+        pyramid.push(difference);
+    }
+    pyramid.push(gaussian[gaussian.len() - 1].clone());
+    Ok(pyramid)
+}
+
+fn collapse_laplacian_pyramid(mut pyramid: Vec<Mat>) -> cv::Result<Mat> {
+    let mut image = pyramid.pop().unwrap_or_default();
+    while let Some(level) = pyramid.pop() {
+        let mut up = Mat::default();
+        imgproc::pyr_up(&image, &mut up, level.size()?, BORDER_DEFAULT)?;
+        let mut sum = Mat::default();
+        cv::core::add(&level, &up, &mut sum, &no_array(), -1)?;
+        image = sum;
+    }
+    Ok(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use cv::core::{Vec3b, CV_8UC3, NORM_INF};
+
+    use super::*;
+
+    fn uniform(rows: i32, cols: i32, color: [f64; 3]) -> Mat {
+        Mat::new_rows_cols_with_default(
+            rows,
+            cols,
+            CV_8UC3,
+            Scalar::new(color[0], color[1], color[2], 0.0),
+        )
+        .unwrap()
+    }
+
+    // 带有横向渐变与纵向条纹的测试画面
+    fn pattern(rows: i32, cols: i32) -> Mat {
+        let mut mat = uniform(rows, cols, [0.0; 3]);
+        for row in 0..rows {
+            for col in 0..cols {
+                let value = (col * 255 / cols.max(1)) as u8;
+                let stripe = if row % 8 < 4 { 40 } else { 0 };
+                *mat.at_2d_mut::<Vec3b>(row, col).unwrap() =
+                    VecN([value, value.saturating_add(stripe), 255 - value]);
+            }
+        }
+        mat
+    }
+
+    fn max_difference(a: &Mat, b: &Mat) -> f64 {
+        cv::core::norm2(a, b, NORM_INF, &no_array()).unwrap()
+    }
+
+    fn channel(mat: &Mat, index: usize) -> Mat {
+        let mut channels = VectorOfMat::new();
+        cv::core::split(mat, &mut channels).unwrap();
+        channels.get(index).unwrap()
+    }
+
+    // 单个通道的最大值与最小值之差
+    fn channel_range(mat: &Mat, index: usize) -> f64 {
+        let (mut min, mut max) = (0.0, 0.0);
+        cv::core::min_max_loc(
+            &channel(mat, index),
+            Some(&mut min),
+            Some(&mut max),
+            None,
+            None,
+            &no_array(),
+        )
+        .unwrap();
+        max - min
+    }
+
+    fn all_algorithms(src: &Mat, strength: f64) -> Vec<(&'static str, cv::Result<Mat>)> {
+        vec![
+            ("dehaze", dehaze(src, strength, 15)),
+            ("gray_world", gray_world_white_balance(src, strength)),
+            (
+                "white_patch",
+                white_patch_white_balance(src, strength, 99.0),
+            ),
+            ("unsharp_mask", unsharp_mask(src, strength, 2.0)),
+            ("fusion", fusion_enhance(src, strength, 3)),
+        ]
+    }
+
+    #[test]
+    fn gray_world_neutralises_uniform_cast() {
+        let src = uniform(48, 64, [200.0, 100.0, 50.0]);
+        let result = gray_world_white_balance(&src, 1.0).unwrap();
+        let mean = cv::core::mean(&result, &no_array()).unwrap();
+        let gray = (200.0 + 100.0 + 50.0) / 3.0;
+        for channel in 0..3 {
+            assert!(
+                (mean[channel] - gray).abs() <= 1.0,
+                "channel {} mean {} is not neutral",
+                channel,
+                mean[channel]
+            );
+        }
+    }
+
+    #[test]
+    fn dehaze_raises_contrast_on_hazy_image() {
+        // 左右两块亮度接近的灰色，模拟被雾气压低的对比度
+        let mut src = uniform(64, 64, [200.0; 3]);
+        for row in 0..64 {
+            for col in 0..32 {
+                *src.at_2d_mut::<Vec3b>(row, col).unwrap() = VecN([170; 3]);
+            }
+        }
+        let result = dehaze(&src, 0.95, 15).unwrap();
+        let contrast = |mat: &Mat| {
+            let dark = mat.at_2d::<Vec3b>(32, 8).unwrap()[0] as i32;
+            let light = mat.at_2d::<Vec3b>(32, 56).unwrap()[0] as i32;
+            light - dark
+        };
+        assert!(contrast(&result) > contrast(&src));
+    }
+
+    #[test]
+    fn white_patch_stretches_brightest_percentile_to_full_scale() {
+        // 三个通道的最高亮度各不相同，都达不到满幅
+        let mut src = uniform(48, 64, [0.0; 3]);
+        for row in 0..48 {
+            for col in 0..64 {
+                let value = col as f64 / 63.0;
+                *src.at_2d_mut::<Vec3b>(row, col).unwrap() = VecN([
+                    (value * 200.0) as u8,
+                    (value * 150.0) as u8,
+                    (value * 100.0) as u8,
+                ]);
+            }
+        }
+        let result = white_patch_white_balance(&src, 1.0, 99.0).unwrap();
+        for index in 0..3 {
+            let before = channel_percentile(&channel(&src, index), 99.0).unwrap();
+            let after = channel_percentile(&channel(&result, index), 99.0).unwrap();
+            assert!(before < 250.0);
+            assert!(
+                after >= 250.0,
+                "channel {} percentile {} was not stretched to 255",
+                index,
+                after
+            );
+        }
+    }
+
+    #[test]
+    fn unsharp_mask_raises_edge_contrast() {
+        let mut src = uniform(32, 64, [150.0; 3]);
+        for row in 0..32 {
+            for col in 0..32 {
+                *src.at_2d_mut::<Vec3b>(row, col).unwrap() = VecN([100; 3]);
+            }
+        }
+        let result = unsharp_mask(&src, 1.0, 2.0).unwrap();
+        let dark = result.at_2d::<Vec3b>(16, 31).unwrap()[0];
+        let light = result.at_2d::<Vec3b>(16, 32).unwrap()[0];
+        assert!(dark < 100, "dark side of the edge is {}", dark);
+        assert!(light > 150, "light side of the edge is {}", light);
+    }
+
+    #[test]
+    fn fusion_raises_contrast_of_low_contrast_frame() {
+        // 亮度只在 100~140 之间变化的灰暗画面
+        let mut src = uniform(64, 64, [0.0; 3]);
+        for row in 0..64 {
+            for col in 0..64 {
+                let stripe = if row % 8 < 4 { 10 } else { 0 };
+                let value = (100 + col * 30 / 63 + stripe) as u8;
+                *src.at_2d_mut::<Vec3b>(row, col).unwrap() = VecN([value; 3]);
+            }
+        }
+        let result = fusion_enhance(&src, 1.0, 3).unwrap();
+        let mean = cv::core::mean(&result, &no_array()).unwrap();
+        for index in 0..3 {
+            assert!(
+                (30.0..=225.0).contains(&mean[index]),
+                "channel {} mean {} is out of range",
+                index,
+                mean[index]
+            );
+            assert!(
+                channel_range(&result, index) > channel_range(&src, index),
+                "channel {} contrast was not raised",
+                index
+            );
+        }
+    }
+
+    #[test]
+    fn dehaze_keeps_constant_image_finite() {
+        let src = uniform(32, 32, [180.0; 3]);
+        let result = dehaze(&src, 0.95, 15).unwrap();
+        assert_eq!(result.size().unwrap(), src.size().unwrap());
+        assert_eq!(result.typ(), CV_8UC3);
+    }
+
+    #[test]
+    fn zero_strength_returns_input() {
+        let src = pattern(48, 64);
+        for (name, result) in all_algorithms(&src, 0.0) {
+            let result = result.unwrap();
+            assert!(
+                max_difference(&src, &result) <= 1.0,
+                "{} changed the frame at strength 0",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn output_size_and_type_preserved() {
+        let src = pattern(48, 64);
+        for (name, result) in all_algorithms(&src, 0.8) {
+            let result = result.unwrap();
+            assert_eq!(result.size().unwrap(), src.size().unwrap(), "{}", name);
+            assert_eq!(result.typ(), src.typ(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn width_not_multiple_of_four() {
+        let src = pattern(37, 63);
+        for (name, result) in all_algorithms(&src, 0.8) {
+            let result = result.unwrap_or_else(|err| panic!("{} failed: {}", name, err));
+            assert_eq!(result.size().unwrap(), src.size().unwrap(), "{}", name);
+        }
+    }
+}
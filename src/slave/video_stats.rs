@@ -0,0 +1,261 @@
+use std::{
+    fmt::Display,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use gst::{
+    prelude::*, PadProbeData, PadProbeReturn, PadProbeType, Pipeline, ReferenceTimestampMeta,
+};
+
+// NTP 时间从 1900 年起算，比 Unix 时间早 70 年
+const NTP_UNIX_OFFSET: Duration = Duration::from_secs(2_208_988_800);
+
+#[derive(Debug)]
+pub struct VideoStatsCounter {
+    received_bytes: AtomicU64,
+    decoded_frames: AtomicU64,
+    displayed_frames: AtomicU64,
+    latency: Mutex<Option<gst::ClockTime>>,
+    end_to_end_latency: Mutex<Option<gst::ClockTime>>,
+    last_frame: Mutex<Instant>,
+    last_snapshot: Mutex<(Instant, u64, u64, u64)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct JitterBufferStats {
+    pub pushed: u64,
+    pub lost: u64,
+    pub late: u64,
+    pub duplicates: u64,
+    pub avg_jitter: gst::ClockTime,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct VideoStats {
+    pub bitrate: Option<f64>,
+    pub decoded_fps: f64,
+    pub displayed_fps: f64,
+    pub frame_size: Option<(i32, i32)>,
+    pub latency: Option<gst::ClockTime>,
+    pub end_to_end_latency: Option<gst::ClockTime>,
+    pub dropped_frames: u64,
+    pub processing_time: Option<Duration>,
+    pub jitter_buffer: Option<JitterBufferStats>,
+}
+
+impl VideoStatsCounter {
+    pub fn new() -> Self {
+        Self {
+            received_bytes: AtomicU64::new(0),
+            decoded_frames: AtomicU64::new(0),
+            displayed_frames: AtomicU64::new(0),
+            latency: Mutex::new(None),
+            end_to_end_latency: Mutex::new(None),
+            last_frame: Mutex::new(Instant::now()),
+            last_snapshot: Mutex::new((Instant::now(), 0, 0, 0)),
+        }
+    }
+
+    // 在压缩码流（tee_source）、解码输出（tee_decoded）与显示端（display）上统计数据量、帧数和延迟
+    pub fn attach(self: &Arc<Self>, pipeline: &Pipeline) {
+        if let Some(pad) = pipeline
+            .by_name("tee_source")
+            .and_then(|tee| tee.static_pad("sink"))
+        {
+            let counter = self.clone();
+            pad.add_probe(PadProbeType::BUFFER, move |_pad, info| {
+                if let Some(PadProbeData::Buffer(buffer)) = &info.data {
+                    counter
+                        .received_bytes
+                        .fetch_add(buffer.size() as u64, Ordering::Relaxed);
+                }
+                PadProbeReturn::Ok
+            });
+        }
+        if let Some(pad) = pipeline
+            .by_name("tee_decoded")
+            .and_then(|tee| tee.static_pad("sink"))
+        {
+            let counter = self.clone();
+            pad.add_probe(PadProbeType::BUFFER, move |_pad, _info| {
+                counter.decoded_frames.fetch_add(1, Ordering::Relaxed);
+                PadProbeReturn::Ok
+            });
+        }
+        if let Some(pad) = pipeline
+            .by_name("display")
+            .and_then(|sink| sink.static_pad("sink"))
+        {
+            let counter = self.clone();
+            let pipeline = pipeline.downgrade();
+            pad.add_probe(PadProbeType::BUFFER, move |_pad, info| {
+                counter.displayed_frames.fetch_add(1, Ordering::Relaxed);
+                *counter.last_frame.lock().unwrap() = Instant::now();
+                if let (Some(PadProbeData::Buffer(buffer)), Some(pipeline)) =
+                    (&info.data, pipeline.upgrade())
+                {
+                    if let (Some(pts), Some(running_time)) =
+                        (buffer.pts(), pipeline.current_running_time())
+                    {
+                        *counter.latency.lock().unwrap() = running_time.checked_sub(pts);
+                    }
+                    if let Some(capture_time) = ntp_capture_time(buffer) {
+                        *counter.end_to_end_latency.lock().unwrap() =
+                            ntp_now().checked_sub(capture_time);
+                    }
+                }
+                PadProbeReturn::Ok
+            });
+        }
+    }
+
+    // 距离上一帧到达显示端的时间，尚未收到任何帧时从创建时开始计算
+    pub fn since_last_frame(&self) -> Duration {
+        self.last_frame.lock().unwrap().elapsed()
+    }
+
+    pub fn has_frames(&self) -> bool {
+        self.displayed_frames.load(Ordering::Relaxed) > 0
+    }
+
+    pub fn snapshot(
+        &self,
+        pipeline: &Pipeline,
+        dropped_frames: u64,
+        processing_time: Option<Duration>,
+    ) -> VideoStats {
+        let received_bytes = self.received_bytes.load(Ordering::Relaxed);
+        let decoded_frames = self.decoded_frames.load(Ordering::Relaxed);
+        let displayed_frames = self.displayed_frames.load(Ordering::Relaxed);
+        let mut last_snapshot = self.last_snapshot.lock().unwrap();
+        let (last_instant, last_bytes, last_decoded_frames, last_displayed_frames) = *last_snapshot;
+        let now = Instant::now();
+        let elapsed = now
+            .duration_since(last_instant)
+            .as_secs_f64()
+            .max(f64::EPSILON);
+        *last_snapshot = (now, received_bytes, decoded_frames, displayed_frames);
+        VideoStats {
+            bitrate: pipeline
+                .by_name("tee_source")
+                .map(|_| (received_bytes - last_bytes) as f64 * 8.0 / elapsed),
+            decoded_fps: (decoded_frames - last_decoded_frames) as f64 / elapsed,
+            displayed_fps: (displayed_frames - last_displayed_frames) as f64 / elapsed,
+            frame_size: frame_size(pipeline),
+            latency: *self.latency.lock().unwrap(),
+            end_to_end_latency: *self.end_to_end_latency.lock().unwrap(),
+            dropped_frames,
+            processing_time,
+            jitter_buffer: jitter_buffer_stats(pipeline),
+        }
+    }
+}
+
+impl Default for VideoStatsCounter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// rtspsrc 收到 RTCP 发送端报告后为每个缓冲附加发送端的 NTP 采集时间
+fn ntp_capture_time(buffer: &gst::BufferRef) -> Option<gst::ClockTime> {
+    buffer
+        .iter_meta::<ReferenceTimestampMeta>()
+        .find(|meta| {
+            meta.reference()
+                .structure(0)
+                .map_or(false, |structure| structure.name() == "timestamp/x-ntp")
+        })
+        .map(|meta| meta.timestamp())
+}
+
+fn ntp_now() -> gst::ClockTime {
+    let since_epoch = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    gst::ClockTime::from_nseconds((since_epoch + NTP_UNIX_OFFSET).as_nanos() as u64)
+}
+
+fn frame_size(pipeline: &Pipeline) -> Option<(i32, i32)> {
+    let caps = pipeline
+        .by_name("display")?
+        .static_pad("sink")?
+        .current_caps()?;
+    let structure = caps.structure(0)?;
+    match (structure.get("width"), structure.get("height")) {
+        (Ok(width), Ok(height)) => Some((width, height)),
+        _ => None,
+    }
+}
+
+// rtspsrc 内部的 rtpjitterbuffer 位于子 bin 中，需要递归查找
+fn jitter_buffer_stats(pipeline: &Pipeline) -> Option<JitterBufferStats> {
+    let jitterbuffer = pipeline
+        .iterate_recurse()
+        .into_iter()
+        .flatten()
+        .find(|element| {
+            element.factory().map_or(false, |factory| {
+                factory.name().as_str() == "rtpjitterbuffer"
+            })
+        })?;
+    let stats = jitterbuffer.property::<gst::Structure>("stats");
+    Some(JitterBufferStats {
+        pushed: stats.get("num-pushed").unwrap_or_default(),
+        lost: stats.get("num-lost").unwrap_or_default(),
+        late: stats.get("num-late").unwrap_or_default(),
+        duplicates: stats.get("num-duplicates").unwrap_or_default(),
+        avg_jitter: gst::ClockTime::from_nseconds(stats.get("avg-jitter").unwrap_or_default()),
+    })
+}
+
+impl Display for VideoStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.bitrate {
+            Some(bitrate) => writeln!(f, "码率: {:.0} kbps", bitrate / 1000.0)?,
+            None => writeln!(f, "码率: N/A")?,
+        }
+        writeln!(
+            f,
+            "帧率: 解码 {:.1} fps / 显示 {:.1} fps",
+            self.decoded_fps, self.displayed_fps
+        )?;
+        match self.frame_size {
+            Some((width, height)) => writeln!(f, "分辨率: {}x{}", width, height)?,
+            None => writeln!(f, "分辨率: N/A")?,
+        }
+        match self.latency {
+            Some(latency) => writeln!(f, "管道延迟: {} ms", latency.mseconds())?,
+            None => writeln!(f, "管道延迟: N/A")?,
+        }
+        // 端到端延迟依赖 RTSP 的 RTCP 发送端报告，且要求机位与上位机时钟同步
+        match self.end_to_end_latency {
+            Some(latency) => writeln!(f, "端到端延迟: {} ms", latency.mseconds())?,
+            None => writeln!(f, "端到端延迟: N/A（需 RTSP 与时钟同步）")?,
+        }
+        if let Some(processing_time) = self.processing_time {
+            writeln!(
+                f,
+                "处理耗时: {:.1} ms/帧",
+                processing_time.as_secs_f64() * 1000.0
+            )?;
+        }
+        write!(f, "显示丢帧: {}", self.dropped_frames)?;
+        if let Some(jitter_buffer) = &self.jitter_buffer {
+            write!(
+                f,
+                "\n抖动缓冲: 接收 {} / 丢失 {} / 迟到 {} / 重复 {}\n平均抖动: {} ms",
+                jitter_buffer.pushed,
+                jitter_buffer.lost,
+                jitter_buffer.late,
+                jitter_buffer.duplicates,
+                jitter_buffer.avg_jitter.mseconds()
+            )?;
+        }
+        Ok(())
+    }
+}